@@ -1,5 +1,5 @@
 use clap::Parser;
-use daikin_client::{Daikin, ReqwestClient};
+use daikin_client::{Daikin, DaikinError, ReqwestClient};
 use dsiot::protocol::property::{Binary, Metadata};
 use std::net::Ipv4Addr;
 
@@ -22,6 +22,20 @@ async fn main() -> anyhow::Result<()> {
     get_status(addr).await
 }
 
+fn report(api: &str, error: &DaikinError) {
+    match error {
+        DaikinError::Transport(e) => println!("❌ {api}: Server not found. - {e}"),
+        DaikinError::Timeout => println!("❌ {api}: Server not responding."),
+        DaikinError::HttpStatus(status) => println!("❌ {api}: Not supported. - HTTP {status}"),
+        DaikinError::MalformedResponse(e) => println!("❌ {api}: Invalid response. - {e}"),
+        DaikinError::StatusCode { from, code } => {
+            println!("❌ {api}: Request rejected. - {from}: {code}")
+        }
+        DaikinError::MissingProperty(e) => println!("❌ {api}: Invalid response. - {e}"),
+        e => println!("❌ {api}: {e}"),
+    }
+}
+
 async fn get_status(ip_addr: Ipv4Addr) -> anyhow::Result<()> {
     let client = ReqwestClient::try_new()?;
     let daikin = Daikin::new(ip_addr, client);
@@ -31,15 +45,7 @@ async fn get_status(ip_addr: Ipv4Addr) -> anyhow::Result<()> {
     let info = match daikin.get_info().await {
         Ok(i) => i,
         Err(error) => {
-            if let Some(e) = error.downcast_ref::<reqwest::Error>() {
-                println!("❌ API endpoint: Server not found. - {e}");
-                return Ok(());
-            }
-            if let Some(e) = error.downcast_ref::<serde_json::Error>() {
-                println!("❌ API endpoint: Invalid response. - {e}");
-                return Ok(());
-            }
-            println!("❌ API endpoint: Unknown error.");
+            report("API endpoint", &error);
             return Ok(());
         }
     };
@@ -51,16 +57,7 @@ async fn get_status(ip_addr: Ipv4Addr) -> anyhow::Result<()> {
     let status = match daikin.get_status().await {
         Ok(s) => s,
         Err(error) => {
-            if let Some(e) = error.downcast_ref::<reqwest::Error>() {
-                println!("❌ Request API: Server not found. - {e}");
-                return Ok(());
-            }
-            if let Some(e) = error.downcast_ref::<serde_json::Error>() {
-                println!("❌ Request API: Invalid response. - {e}");
-                return Ok(());
-            }
-
-            println!("❌ Request API: Server not found.");
+            report("Request API", &error);
             return Ok(());
         }
     };
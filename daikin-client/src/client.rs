@@ -1,17 +1,33 @@
 //! HTTP client implementations for Daikin devices.
 
+use crate::error::DaikinError;
+use crate::retry::RetryPolicy;
 use async_lock::RwLock;
-use dsiot::protocol::{DaikinInfo, DaikinRequest, DaikinResponse, DaikinStatus};
+use dsiot::protocol::{
+    ChangeSet, DaikinInfo, DaikinRequest, DaikinResponse, DaikinStatus, Item, PropertyPath,
+};
+use serde::de::DeserializeOwned;
 use serde_json::json;
 use serde_json::value::Value;
-use std::net::Ipv4Addr;
-use std::sync::Arc;
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
+/// Default time a fetched status is served from the cache.
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Default time to wait for a device to answer.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Trait for HTTP clients that can communicate with Daikin devices.
-#[allow(async_fn_in_trait)]
-pub trait HttpClient {
-    async fn send_request(&self, url: &str, payload: Value) -> anyhow::Result<Value>;
+pub trait HttpClient: Send + Sync + 'static {
+    fn send_request(
+        &self,
+        url: &str,
+        payload: Value,
+    ) -> impl Future<Output = Result<Value, DaikinError>> + Send;
 }
 
 /// Reqwest-based HTTP client for Daikin devices.
@@ -23,33 +39,46 @@ pub struct ReqwestClient {
 impl ReqwestClient {
     /// Create a new ReqwestClient with default settings.
     pub fn try_new() -> Result<Self, reqwest::Error> {
+        Self::with_timeout(DEFAULT_TIMEOUT)
+    }
+
+    /// Create a new ReqwestClient that gives up on a request after `timeout`.
+    pub fn with_timeout(timeout: Duration) -> Result<Self, reqwest::Error> {
         Ok(ReqwestClient {
             client: reqwest::Client::builder()
                 .http1_title_case_headers()
-                .timeout(Duration::new(5, 0))
+                .timeout(timeout)
                 .build()?,
         })
     }
 }
 
 impl HttpClient for ReqwestClient {
-    async fn send_request(&self, url: &str, payload: Value) -> anyhow::Result<Value> {
-        let response = self.client.post(url).json(&payload).send().await?;
-        let body = response.json().await?;
-        Ok(body)
+    async fn send_request(&self, url: &str, payload: Value) -> Result<Value, DaikinError> {
+        let response = self
+            .client
+            .post(url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        let body = response.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
     }
 }
 
 struct Cache {
     last_updated: Instant,
     data: Option<DaikinStatus>,
+    ttl: Duration,
 }
 
 impl Cache {
-    fn new() -> Self {
+    fn new(ttl: Duration) -> Self {
         Cache {
             last_updated: Instant::now(),
             data: None,
+            ttl,
         }
     }
 
@@ -58,21 +87,181 @@ impl Cache {
         self.data = Some(value);
     }
 
+    fn apply(&mut self, changes: &ChangeSet) {
+        if let Some(data) = &mut self.data {
+            changes.apply_to(data);
+        }
+    }
+
     fn get(&self) -> Option<DaikinStatus> {
-        if self.last_updated.elapsed().as_millis() < 5000 {
+        if self.last_updated.elapsed() < self.ttl {
             self.data.clone()
         } else {
             None
         }
     }
+
+    fn get_stale(&self) -> Option<DaikinStatus> {
+        self.data.clone()
+    }
+
+    fn invalidate(&mut self) {
+        self.data = None;
+    }
+}
+
+/// Serializes the requests sent to a device and batches concurrent writes.
+#[derive(Default)]
+struct RequestQueue {
+    /// Held while a request is in flight; adapters handle one at a time.
+    in_flight: async_lock::Mutex<()>,
+    /// Held while a write, including its read-back and retries, is in progress.
+    writing: async_lock::Mutex<()>,
+    /// Changes waiting for the next write, and whether that write succeeded.
+    pending: Mutex<Option<(ChangeSet, Arc<OnceLock<bool>>)>>,
+}
+
+impl RequestQueue {
+    /// Add changes to the next write and return its outcome cell.
+    fn enqueue(&self, changes: &ChangeSet) -> Arc<OnceLock<bool>> {
+        let mut pending = self.pending.lock().expect("queue lock poisoned");
+        match &mut *pending {
+            Some((batch, done)) => {
+                batch.merge(changes.clone());
+                done.clone()
+            }
+            None => {
+                let done = Arc::new(OnceLock::new());
+                *pending = Some((changes.clone(), done.clone()));
+                done
+            }
+        }
+    }
+
+    /// Take the changes waiting for the next write if they are the batch
+    /// whose outcome cell is `done`.
+    fn take(&self, done: &Arc<OnceLock<bool>>) -> Option<ChangeSet> {
+        let mut pending = self.pending.lock().expect("queue lock poisoned");
+        match &*pending {
+            Some((_, cell)) if Arc::ptr_eq(cell, done) => pending.take().map(|(batch, _)| batch),
+            _ => None,
+        }
+    }
+}
+
+/// Marks a batch as failed unless its outcome was set, so that callers merged
+/// into the batch of a cancelled write send their own changes.
+struct BatchGuard(Arc<OnceLock<bool>>);
+
+impl Drop for BatchGuard {
+    fn drop(&mut self) {
+        let _ = self.0.set(false);
+    }
+}
+
+/// Builder for a `Daikin` client with non-default settings.
+#[derive(Debug)]
+pub struct DaikinBuilder<H: HttpClient> {
+    addr: SocketAddrV4,
+    client: H,
+    cache_ttl: Duration,
+    stale_while_revalidate: bool,
+    retry: RetryPolicy,
+    merge_writes: bool,
+}
+
+impl<H: HttpClient> DaikinBuilder<H> {
+    /// Start building a client for the device at the given IP address.
+    pub fn new(ip_addr: Ipv4Addr, client: H) -> Self {
+        Self {
+            addr: SocketAddrV4::new(ip_addr, 80),
+            client,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            stale_while_revalidate: false,
+            retry: RetryPolicy::default(),
+            merge_writes: true,
+        }
+    }
+
+    /// Set the HTTP port, e.g. for a local simulator.
+    pub fn port(mut self, port: u16) -> Self {
+        self.addr.set_port(port);
+        self
+    }
+
+    /// Set how long a fetched status is served from the cache.
+    /// `Duration::ZERO` disables caching. Defaults to 5 seconds.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Serve an expired cached status immediately and refresh it in the
+    /// background, instead of waiting for the device. Requires a Tokio runtime.
+    pub fn stale_while_revalidate(mut self, enabled: bool) -> Self {
+        self.stale_while_revalidate = enabled;
+        self
+    }
+
+    /// Set how failed requests are retried. Use `RetryPolicy::none()` to
+    /// send every request once.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Merge the changes of callers waiting for an in-flight write into a
+    /// single write. Enabled by default.
+    pub fn merge_writes(mut self, enabled: bool) -> Self {
+        self.merge_writes = enabled;
+        self
+    }
+
+    /// Build the client.
+    pub fn build(self) -> Daikin<H> {
+        let endpoint = if self.addr.port() == 80 {
+            format!("http://{}/dsiot/multireq", self.addr.ip())
+        } else {
+            format!("http://{}/dsiot/multireq", self.addr)
+        };
+        Daikin {
+            endpoint,
+            cache: Arc::new(RwLock::new(Cache::new(self.cache_ttl))),
+            client: Arc::new(self.client),
+            stale_while_revalidate: self.stale_while_revalidate,
+            refreshing: Arc::new(AtomicBool::new(false)),
+            retry: self.retry,
+            queue: Arc::new(RequestQueue::default()),
+            merge_writes: self.merge_writes,
+        }
+    }
 }
 
 /// Daikin device client.
-#[derive(Clone)]
 pub struct Daikin<H: HttpClient> {
     endpoint: String,
     cache: Arc<RwLock<Cache>>,
     client: Arc<H>,
+    stale_while_revalidate: bool,
+    refreshing: Arc<AtomicBool>,
+    retry: RetryPolicy,
+    queue: Arc<RequestQueue>,
+    merge_writes: bool,
+}
+
+impl<H: HttpClient> Clone for Daikin<H> {
+    fn clone(&self) -> Self {
+        Daikin {
+            endpoint: self.endpoint.clone(),
+            cache: self.cache.clone(),
+            client: self.client.clone(),
+            stale_while_revalidate: self.stale_while_revalidate,
+            refreshing: self.refreshing.clone(),
+            retry: self.retry.clone(),
+            queue: self.queue.clone(),
+            merge_writes: self.merge_writes,
+        }
+    }
 }
 
 impl<H: HttpClient> std::fmt::Debug for Daikin<H> {
@@ -84,18 +273,41 @@ impl<H: HttpClient> std::fmt::Debug for Daikin<H> {
 impl<H: HttpClient> Daikin<H> {
     /// Create a new Daikin client for the device at the given IP address.
     pub fn new(ip_addr: Ipv4Addr, client: H) -> Daikin<H> {
-        Daikin {
-            endpoint: format!("http://{ip_addr}/dsiot/multireq"),
-            cache: Arc::new(RwLock::new(Cache::new())),
-            client: Arc::new(client),
-        }
+        DaikinBuilder::new(ip_addr, client).build()
+    }
+
+    /// Create a new Daikin client for a device listening on a non-default port,
+    /// such as a local simulator.
+    pub fn with_socket_addr(addr: SocketAddrV4, client: H) -> Daikin<H> {
+        DaikinBuilder::new(*addr.ip(), client)
+            .port(addr.port())
+            .build()
+    }
+
+    /// Start building a client with non-default settings.
+    pub fn builder(ip_addr: Ipv4Addr, client: H) -> DaikinBuilder<H> {
+        DaikinBuilder::new(ip_addr, client)
     }
 
-    /// Get the current device status.
-    pub async fn get_status(&self) -> anyhow::Result<DaikinStatus> {
-        if let Some(status) = self.cache.read().await.get() {
+    /// Get the current device status, served from the cache while it is fresh.
+    pub async fn get_status(&self) -> Result<DaikinStatus, DaikinError> {
+        let cache = self.cache.read().await;
+        if let Some(status) = cache.get() {
             return Ok(status);
         }
+        if self.stale_while_revalidate
+            && let Some(status) = cache.get_stale()
+        {
+            drop(cache);
+            self.revalidate();
+            return Ok(status);
+        }
+        drop(cache);
+        self.get_status_fresh().await
+    }
+
+    /// Fetch the device status bypassing the cache, and store it in the cache.
+    pub async fn get_status_fresh(&self) -> Result<DaikinStatus, DaikinError> {
         let payload = json!({"requests": [
             {
                 "op": 2,
@@ -107,8 +319,11 @@ impl<H: HttpClient> Daikin<H> {
             }
         ]});
 
-        let body = self.client.send_request(&self.endpoint, payload).await?;
-        let status: DaikinStatus = serde_json::from_value::<DaikinResponse>(body)?.into();
+        let body = self.send(payload).await?;
+        let status: DaikinStatus = parse_response(body)?.into();
+        for path in &status.failed_paths {
+            debug!("Failed to get status from {path}");
+        }
 
         let mut cache = self.cache.write().await;
         cache.update(status.clone());
@@ -116,8 +331,27 @@ impl<H: HttpClient> Daikin<H> {
         Ok(status)
     }
 
+    /// Drop the cached status so that the next `get_status` asks the device.
+    pub async fn invalidate(&self) {
+        self.cache.write().await.invalidate();
+    }
+
+    /// Refresh the cache in the background unless a refresh is already running.
+    fn revalidate(&self) {
+        if self.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let daikin = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = daikin.get_status_fresh().await {
+                debug!("Failed to refresh {daikin:?}: {e}");
+            }
+            daikin.refreshing.store(false, Ordering::Release);
+        });
+    }
+
     /// Get device information.
-    pub async fn get_info(&self) -> anyhow::Result<DaikinInfo> {
+    pub async fn get_info(&self) -> Result<DaikinInfo, DaikinError> {
         let payload = json!({"requests": [
             {
                 "op": 2,
@@ -133,17 +367,376 @@ impl<H: HttpClient> Daikin<H> {
             }
         ]});
 
-        let body = self.client.send_request(&self.endpoint, payload).await?;
-        let info: DaikinInfo = serde_json::from_value::<DaikinResponse>(body)?.into();
+        let body = self.send(payload).await?;
+        let info: DaikinInfo = parse_response(body)?.into();
 
         Ok(info)
     }
 
+    /// Get a single property by path, including ones `DaikinStatus` does not model.
+    pub async fn get_property<T: Sized + DeserializeOwned + Into<f32>>(
+        &self,
+        path: &PropertyPath,
+    ) -> Result<Item<T>, DaikinError> {
+        let payload = json!({"requests": [
+            {
+                "op": 2,
+                "to": format!("{}?filter=pv,md", path.from())
+            }
+        ]});
+
+        let body = self.send(payload).await?;
+        Ok(parse_response(body)?.item(path)?)
+    }
+
     /// Update device status.
-    pub async fn update(&self, status: DaikinStatus) -> anyhow::Result<()> {
-        let payload = serde_json::to_value(DaikinRequest::from(status.clone()))?;
-        self.client.send_request(&self.endpoint, payload).await?;
-        self.cache.write().await.update(status);
-        Ok(())
+    ///
+    /// Accepts either a `ChangeSet`, which writes only the changed fields, or a
+    /// full `DaikinStatus`, which writes every writable field.
+    ///
+    /// Writes are sent one at a time in the order they were requested. Changes
+    /// requested while another write is in flight are merged into the next
+    /// write; if that merged write fails, each caller's changes are written on
+    /// their own, so a value rejected by the device only fails its own caller.
+    ///
+    /// When a write fails with a retryable error, the status is read back
+    /// first and the write is only sent again if it was not applied.
+    ///
+    /// Cancelling the caller that sends a merged write does not lose the
+    /// changes of the others; they are then written on their own.
+    pub async fn update(&self, changes: impl Into<ChangeSet>) -> Result<(), DaikinError> {
+        let changes = changes.into();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if !self.merge_writes {
+            let _writing = self.queue.writing.lock().await;
+            return self.write(&changes).await;
+        }
+
+        let done = self.queue.enqueue(&changes);
+        let _writing = self.queue.writing.lock().await;
+        match done.get() {
+            Some(true) => return Ok(()),
+            Some(false) => return self.write(&changes).await,
+            None => {}
+        }
+        let Some(batch) = self.queue.take(&done) else {
+            // The batch was taken by a write that did not finish
+            return self.write(&changes).await;
+        };
+        let guard = BatchGuard(done);
+        let result = self.write(&batch).await;
+        let _ = guard.0.set(result.is_ok());
+        if result.is_err() && batch != changes {
+            return self.write(&changes).await;
+        }
+        result
+    }
+
+    /// Write changes on their own, after any writes already queued, and
+    /// return the device's answer.
+    ///
+    /// The answer is `None` when the response was lost but reading back
+    /// showed the changes applied. A write the device refused as a whole is
+    /// returned rather than failed, so that every refused sub-request can be
+    /// reported.
+    pub(crate) async fn write_exclusive(
+        &self,
+        changes: &ChangeSet,
+    ) -> Result<Option<DaikinResponse>, DaikinError> {
+        let _writing = self.queue.writing.lock().await;
+        self.send_write(changes).await
+    }
+
+    /// Write changes to the device and apply them to the cache.
+    async fn write(&self, changes: &ChangeSet) -> Result<(), DaikinError> {
+        match self.send_write(changes).await?.as_ref().and_then(refused) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a write, retrying per the retry policy, and apply it to the
+    /// cache unless the device refused it as a whole.
+    async fn send_write(&self, changes: &ChangeSet) -> Result<Option<DaikinResponse>, DaikinError> {
+        let payload = serde_json::to_value(DaikinRequest::from(changes.clone()))
+            .expect("DaikinRequest is always serializable");
+        let mut attempt = 1;
+        loop {
+            let result = match self.request(payload.clone()).await {
+                Ok(body) => serde_json::from_value::<DaikinResponse>(body).map_err(Into::into),
+                Err(e) => Err(e),
+            };
+            let e = match result {
+                Ok(response) => match refused(&response) {
+                    Some(e) if self.retry.is_retryable(&e) => e,
+                    Some(_) => return Ok(Some(response)),
+                    None => {
+                        self.cache.write().await.apply(changes);
+                        return Ok(Some(response));
+                    }
+                },
+                Err(e) if self.retry.is_retryable(&e) => e,
+                Err(e) => return Err(e),
+            };
+            // The write may have been applied even though its response
+            // was lost, so read back before sending it again.
+            debug!("Write to {self:?} failed ({e}), reading back");
+            if let Ok(status) = self.get_status_fresh().await
+                && changes.is_applied_to(&status)
+            {
+                return Ok(None);
+            }
+            if attempt >= self.retry.attempts() {
+                return Err(e);
+            }
+            tokio::time::sleep(self.retry.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send a read request, retrying transient failures per the retry policy.
+    async fn send(&self, payload: Value) -> Result<Value, DaikinError> {
+        let mut attempt = 1;
+        loop {
+            match self.request(payload.clone()).await {
+                Err(e) if attempt < self.retry.attempts() && self.retry.is_retryable(&e) => {
+                    let delay = self.retry.delay(attempt);
+                    debug!("Request to {self:?} failed ({e}), retrying in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a single request once no other request to the device is in flight.
+    async fn request(&self, payload: Value) -> Result<Value, DaikinError> {
+        let _in_flight = self.queue.in_flight.lock().await;
+        self.client.send_request(&self.endpoint, payload).await
+    }
+}
+
+/// Parse a `/dsiot/multireq` response body.
+///
+/// Partial failures are left to the caller to report; this only fails when no
+/// sub-response succeeded.
+fn parse_response(body: Value) -> Result<DaikinResponse, DaikinError> {
+    let response: DaikinResponse = serde_json::from_value(body)?;
+    match refused(&response) {
+        Some(e) => Err(e),
+        None => Ok(response),
+    }
+}
+
+/// The error for a response whose every sub-response failed, naming the
+/// first of them.
+fn refused(response: &DaikinResponse) -> Option<DaikinError> {
+    if response.failed().count() < response.responses.len() {
+        return None;
+    }
+    let failed = response.responses.first()?;
+    Some(DaikinError::StatusCode {
+        from: failed.from.clone(),
+        code: failed.status_code,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use dsiot::{Mode, PowerState, TemperatureTarget};
+    use std::collections::VecDeque;
+
+    pub(crate) const STATUS: &str = include_str!("../../dsiot/src/fixtures/status.json");
+    pub(crate) const WRITE_REJECTED: &str =
+        r#"{"responses":[{"fr":"/dsiot/edge/adr_0100.dgc_status","rsc":4005}]}"#;
+    pub(crate) const WRITE_OK: &str =
+        r#"{"responses":[{"fr":"/dsiot/edge/adr_0100.dgc_status","rsc":2004}]}"#;
+
+    /// Answers requests from a queue of canned results and records the `op`
+    /// of every request it receives.
+    #[derive(Default)]
+    pub(crate) struct FakeClient {
+        results: Mutex<VecDeque<Result<Value, DaikinError>>>,
+        ops: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl FakeClient {
+        pub(crate) fn new(
+            results: impl IntoIterator<Item = Result<&'static str, DaikinError>>,
+        ) -> Self {
+            let results = results
+                .into_iter()
+                .map(|r| r.map(|body| serde_json::from_str(body).expect("Invalid JSON")))
+                .collect();
+            Self {
+                results: Mutex::new(results),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl HttpClient for FakeClient {
+        async fn send_request(&self, _url: &str, payload: Value) -> Result<Value, DaikinError> {
+            let op = payload["requests"][0]["op"].as_u64().unwrap_or_default();
+            self.ops.lock().unwrap().push(op);
+            // Let concurrent callers run while the request is in flight
+            tokio::task::yield_now().await;
+            self.results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("Unexpected request")
+        }
+    }
+
+    pub(crate) fn fake_daikin(client: FakeClient) -> (Daikin<FakeClient>, Arc<Mutex<Vec<u64>>>) {
+        let ops = client.ops.clone();
+        let policy = RetryPolicy::default().backoff(Duration::ZERO, Duration::ZERO);
+        let daikin = Daikin::builder(Ipv4Addr::LOCALHOST, client)
+            .retry(policy)
+            .build();
+        (daikin, ops)
+    }
+
+    pub(crate) fn status() -> DaikinStatus {
+        let res: DaikinResponse = serde_json::from_str(STATUS).expect("Invalid JSON file.");
+        res.into()
+    }
+
+    #[tokio::test]
+    async fn read_retry() {
+        let (daikin, ops) = fake_daikin(FakeClient::new([Err(DaikinError::Timeout), Ok(STATUS)]));
+        assert!(daikin.get_status_fresh().await.is_ok());
+        assert_eq!(*ops.lock().unwrap(), vec![2, 2]);
+
+        let (daikin, ops) = fake_daikin(FakeClient::new([
+            Err(DaikinError::Timeout),
+            Err(DaikinError::HttpStatus(503)),
+            Err(DaikinError::Timeout),
+        ]));
+        assert!(matches!(
+            daikin.get_status_fresh().await,
+            Err(DaikinError::Timeout)
+        ));
+        assert_eq!(ops.lock().unwrap().len(), 3);
+
+        let (daikin, ops) = fake_daikin(FakeClient::new([Err(DaikinError::HttpStatus(404))]));
+        assert!(matches!(
+            daikin.get_status_fresh().await,
+            Err(DaikinError::HttpStatus(404))
+        ));
+        assert_eq!(ops.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn write_retry() {
+        let base = status();
+        let changes = ChangeSet::builder(&base).power(PowerState::On).build();
+
+        // The response was lost but the device is still off: write again.
+        let (daikin, ops) = fake_daikin(FakeClient::new([
+            Err(DaikinError::Timeout),
+            Ok(STATUS),
+            Ok(WRITE_OK),
+        ]));
+        assert!(daikin.update(changes.clone()).await.is_ok());
+        assert_eq!(*ops.lock().unwrap(), vec![3, 2, 3]);
+        let cached = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&cached), Some(PowerState::On));
+
+        // The device already holds every written value, so the write must
+        // not be sent again.
+        let applied = ChangeSet::full(&base);
+        let (daikin, ops) = fake_daikin(FakeClient::new([Err(DaikinError::Timeout), Ok(STATUS)]));
+        assert!(daikin.update(applied).await.is_ok());
+        assert_eq!(*ops.lock().unwrap(), vec![3, 2]);
+    }
+
+    #[tokio::test]
+    async fn write_queue() {
+        let base = status();
+        let power = ChangeSet::builder(&base).power(PowerState::On).build();
+        let mode = ChangeSet::builder(&base).mode(Mode::Heating).build();
+        let target = ChangeSet::builder(&base)
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+
+        // The second and third writes wait for the first and are merged.
+        let (daikin, ops) = fake_daikin(FakeClient::new([Ok(WRITE_OK), Ok(WRITE_OK)]));
+        let (a, b, c) = tokio::join!(
+            daikin.update(power.clone()),
+            daikin.update(mode.clone()),
+            daikin.update(target.clone()),
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        assert_eq!(*ops.lock().unwrap(), vec![3, 3]);
+
+        // A rejected merged write is split, so only the rejected change fails.
+        let (daikin, ops) = fake_daikin(FakeClient::new([
+            Ok(STATUS),
+            Ok(WRITE_OK),
+            Ok(WRITE_REJECTED),
+            Ok(WRITE_OK),
+            Ok(WRITE_REJECTED),
+        ]));
+        daikin.get_status().await.unwrap();
+        let (a, b, c) = tokio::join!(
+            daikin.update(power),
+            daikin.update(mode),
+            daikin.update(target),
+        );
+        assert!(a.is_ok() && b.is_ok());
+        assert!(matches!(c, Err(DaikinError::StatusCode { code: 4005, .. })));
+        assert_eq!(*ops.lock().unwrap(), vec![2, 3, 3, 3, 3]);
+        let cached = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&cached), Some(PowerState::On));
+        assert_eq!(cached.mode.get_enum(), Some(Mode::Heating));
+        assert_eq!(cached.temperature.cooling, base.temperature.cooling);
+    }
+
+    #[tokio::test]
+    async fn write_queue_cancelled() {
+        let base = status();
+        let power = ChangeSet::builder(&base).power(PowerState::On).build();
+        let mode = ChangeSet::builder(&base).mode(Mode::Heating).build();
+        let target = ChangeSet::builder(&base)
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+
+        let (daikin, ops) = fake_daikin(FakeClient::new([Ok(STATUS), Ok(WRITE_OK), Ok(WRITE_OK)]));
+        daikin.get_status().await.unwrap();
+        let mut first = Box::pin(daikin.update(power));
+        let mut owner = Box::pin(daikin.update(mode));
+        let mut merged = Box::pin(daikin.update(target));
+        assert!(futures::poll!(&mut first).is_pending());
+        assert!(futures::poll!(&mut owner).is_pending());
+        assert!(futures::poll!(&mut merged).is_pending());
+        assert!(first.await.is_ok());
+
+        // The caller sending the merged write is dropped while it is in flight.
+        assert!(futures::poll!(&mut owner).is_pending());
+        assert_eq!(*ops.lock().unwrap(), vec![2, 3, 3]);
+        drop(owner);
+
+        assert!(merged.await.is_ok());
+        assert_eq!(*ops.lock().unwrap(), vec![2, 3, 3, 3]);
+        let cached = daikin.get_status().await.unwrap();
+        assert_eq!(cached.mode, base.mode);
+        assert_eq!(cached.temperature.cooling.get_f32(), Some(26.0));
+    }
+
+    #[tokio::test]
+    async fn write_rejected() {
+        let (daikin, ops) = fake_daikin(FakeClient::new([Ok(WRITE_REJECTED)]));
+        let changes = ChangeSet::builder(&status()).power(PowerState::On).build();
+        assert!(matches!(
+            daikin.update(changes).await,
+            Err(DaikinError::StatusCode { code: 4005, .. })
+        ));
+        assert_eq!(*ops.lock().unwrap(), vec![3]);
     }
 }
@@ -0,0 +1,76 @@
+//! Error type for Daikin device communication.
+
+use dsiot::protocol::PropertyError;
+
+/// Error returned by `Daikin` client operations.
+///
+/// New variants may be added; match with a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DaikinError {
+    /// The request could not be delivered, e.g. connection refused or reset.
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    /// The device did not answer in time.
+    Timeout,
+    /// The device answered with a non-success HTTP status.
+    HttpStatus(u16),
+    /// The response body is not a valid dsiot response.
+    MalformedResponse(serde_json::Error),
+    /// The device answered a request with a non-success `rsc`.
+    StatusCode {
+        /// Path of the rejected request, e.g. `/dsiot/edge/adr_0100.dgc_status`.
+        from: String,
+        /// Response status code, e.g. 4005.
+        code: u32,
+    },
+    /// A requested property is missing from the response.
+    MissingProperty(PropertyError),
+}
+
+impl std::fmt::Display for DaikinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "Request failed: {e}"),
+            Self::Timeout => write!(f, "Request timed out"),
+            Self::HttpStatus(status) => write!(f, "Unexpected HTTP status: {status}"),
+            Self::MalformedResponse(e) => write!(f, "Invalid response: {e}"),
+            Self::StatusCode { from, code } => write!(f, "Invalid status code from {from}: {code}"),
+            Self::MissingProperty(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for DaikinError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e.as_ref()),
+            Self::MalformedResponse(e) => Some(e),
+            Self::MissingProperty(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for DaikinError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else if let Some(status) = e.status() {
+            Self::HttpStatus(status.as_u16())
+        } else {
+            Self::Transport(Box::new(e))
+        }
+    }
+}
+
+impl From<serde_json::Error> for DaikinError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::MalformedResponse(e)
+    }
+}
+
+impl From<PropertyError> for DaikinError {
+    fn from(e: PropertyError) -> Self {
+        Self::MissingProperty(e)
+    }
+}
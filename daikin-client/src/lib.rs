@@ -7,9 +7,17 @@ extern crate log;
 
 mod client;
 mod discovery;
+mod error;
+mod retry;
+mod verify;
+mod watch;
 
-pub use client::{Daikin, HttpClient, ReqwestClient};
+pub use client::{Daikin, DaikinBuilder, HttpClient, ReqwestClient};
 pub use discovery::discovery;
+pub use error::DaikinError;
+pub use retry::{RetryPolicy, is_transient};
+pub use verify::{FieldReport, WriteOutcome, WriteReport};
+pub use watch::StatusEvent;
 
 // Re-export commonly used types from dsiot
 pub use dsiot::{DaikinInfo, DaikinStatus};
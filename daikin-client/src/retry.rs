@@ -0,0 +1,141 @@
+//! Retry policy for device requests.
+
+use crate::error::DaikinError;
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
+
+/// How often and how patiently a failed request is retried.
+///
+/// Daikin Wi-Fi adapters often drop the first request after idling, so by
+/// default a request is tried three times with exponential backoff starting at
+/// 200 ms. Only transport errors, timeouts and HTTP 5xx are retried; a device
+/// that answers with an error `rsc` is not asked again.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    retryable: fn(&DaikinError) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+            jitter: true,
+            retryable: is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Set the total number of attempts, including the first one.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry and the upper bound it doubles up to.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Randomize each delay between half and the full backoff, so that
+    /// several clients do not retry in lockstep. Enabled by default.
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Set which errors are worth retrying.
+    pub fn retry_on(mut self, retryable: fn(&DaikinError) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Returns the total number of attempts, including the first one.
+    pub fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns true if a request failing with `error` may be retried.
+    pub fn is_retryable(&self, error: &DaikinError) -> bool {
+        (self.retryable)(error)
+    }
+
+    /// Returns the delay before retrying after the given failed attempt (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << exp)
+            .min(self.max_backoff);
+        if self.jitter {
+            // Hash-based randomness is plenty for spreading out retries.
+            let random = RandomState::new().hash_one(attempt) as f64 / u64::MAX as f64;
+            backoff.mul_f64(0.5 + random / 2.0)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Default retry predicate: transport errors, timeouts and HTTP 5xx.
+pub fn is_transient(error: &DaikinError) -> bool {
+    match error {
+        DaikinError::Transport(_) | DaikinError::Timeout => true,
+        DaikinError::HttpStatus(status) => (500..600).contains(status),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let policy = RetryPolicy::default()
+            .backoff(Duration::from_millis(100), Duration::from_millis(300))
+            .jitter(false);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(300));
+        assert_eq!(policy.delay(40), Duration::from_millis(300));
+
+        let policy = policy.jitter(true);
+        for attempt in 1..5 {
+            let delay = policy.delay(attempt);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn retryable() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&DaikinError::Timeout));
+        assert!(policy.is_retryable(&DaikinError::HttpStatus(503)));
+        assert!(!policy.is_retryable(&DaikinError::HttpStatus(404)));
+        assert!(!policy.is_retryable(&DaikinError::StatusCode {
+            from: "/dsiot/edge/adr_0100.dgc_status".into(),
+            code: 4005,
+        }));
+
+        let policy = policy.retry_on(|e| matches!(e, DaikinError::HttpStatus(_)));
+        assert!(!policy.is_retryable(&DaikinError::Timeout));
+        assert!(policy.is_retryable(&DaikinError::HttpStatus(404)));
+    }
+}
@@ -0,0 +1,203 @@
+//! Verified writes that report what the device actually applied.
+
+use crate::client::{Daikin, HttpClient};
+use crate::error::DaikinError;
+use dsiot::protocol::{ChangeSet, PropValue, PropertyPath};
+
+/// What happened to a single field of a verified write.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WriteOutcome {
+    /// The device acknowledged the write; the value was not read back.
+    Accepted,
+    /// The device holds the requested value.
+    Applied,
+    /// The device holds a different value, e.g. a setpoint clamped to its range.
+    Clamped(PropValue),
+    /// The device refused the write with the given `rsc`, e.g. 4005.
+    ///
+    /// The device answers per resource, so without a read-back every field
+    /// of a refused resource is reported rejected. With one, fields of that
+    /// resource the device holds anyway are reported applied instead.
+    Rejected(u32),
+}
+
+/// Outcome of writing a single field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldReport {
+    /// Path of the written field.
+    pub path: PropertyPath,
+    /// Value that was written.
+    pub requested: PropValue,
+    /// What the device did with it.
+    pub outcome: WriteOutcome,
+}
+
+/// Per-field outcome of `Daikin::update_verified`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriteReport {
+    /// One entry per written field.
+    pub fields: Vec<FieldReport>,
+}
+
+impl WriteReport {
+    /// Returns true if no field was clamped or rejected.
+    pub fn is_applied(&self) -> bool {
+        self.fields
+            .iter()
+            .all(|f| matches!(f.outcome, WriteOutcome::Accepted | WriteOutcome::Applied))
+    }
+
+    /// Returns the fields the device holds a different value for.
+    pub fn clamped(&self) -> impl Iterator<Item = &FieldReport> {
+        self.fields
+            .iter()
+            .filter(|f| matches!(f.outcome, WriteOutcome::Clamped(_)))
+    }
+
+    /// Returns the fields the device refused.
+    pub fn rejected(&self) -> impl Iterator<Item = &FieldReport> {
+        self.fields
+            .iter()
+            .filter(|f| matches!(f.outcome, WriteOutcome::Rejected(_)))
+    }
+}
+
+impl<H: HttpClient> Daikin<H> {
+    /// Update device status and report which fields were applied, clamped or
+    /// rejected.
+    ///
+    /// Fields whose resource was answered with a failing `rsc` are reported as
+    /// rejected instead of failing the call, for every resource that failed.
+    /// With `read_back`, the status is fetched afterwards and each field is
+    /// compared with the value the device holds, which also narrows a refused
+    /// resource down to the fields that did not take; the cache then reflects
+    /// the device rather than the request. Unlike `update`, the write is never
+    /// merged with other callers'.
+    pub async fn update_verified(
+        &self,
+        changes: impl Into<ChangeSet>,
+        read_back: bool,
+    ) -> Result<WriteReport, DaikinError> {
+        let changes = changes.into();
+        if changes.is_empty() {
+            return Ok(WriteReport::default());
+        }
+        let refused: Vec<(String, u32)> = match self.write_exclusive(&changes).await {
+            Ok(response) => response
+                .iter()
+                .flat_map(|r| r.failed())
+                .map(|r| (r.from.clone(), r.status_code))
+                .collect(),
+            // Refused on every attempt with an error the retry policy retries
+            Err(DaikinError::StatusCode { from, code }) => vec![(from, code)],
+            Err(e) => return Err(e),
+        };
+
+        let status = if read_back {
+            match self.get_status_fresh().await {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    debug!("Failed to read back {self:?}: {e}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let rejected = |path: &PropertyPath| {
+            refused
+                .iter()
+                .find(|(from, _)| *from == path.from())
+                .map(|&(_, code)| code)
+        };
+        let report = |path, requested: &PropValue, outcome| FieldReport {
+            path,
+            requested: requested.clone(),
+            outcome,
+        };
+        let fields = match &status {
+            Some(status) => changes
+                .compare(status)
+                .map(|(path, requested, actual)| {
+                    let outcome = match rejected(&path) {
+                        _ if actual == requested => WriteOutcome::Applied,
+                        Some(code) => WriteOutcome::Rejected(code),
+                        None => WriteOutcome::Clamped(actual.clone()),
+                    };
+                    report(path, requested, outcome)
+                })
+                .collect(),
+            None => changes
+                .iter()
+                .map(|(path, requested)| {
+                    let outcome =
+                        rejected(&path).map_or(WriteOutcome::Accepted, WriteOutcome::Rejected);
+                    report(path, requested, outcome)
+                })
+                .collect(),
+        };
+        Ok(WriteReport { fields })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tests::{FakeClient, STATUS, WRITE_OK, WRITE_REJECTED, fake_daikin, status};
+    use dsiot::{PowerState, TemperatureTarget};
+
+    #[tokio::test]
+    async fn update_verified() {
+        let base = status();
+        let target = ChangeSet::builder(&base)
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+
+        // The device keeps its old setpoint but holds every other value.
+        let mut changes = ChangeSet::full(&base);
+        changes.merge(target.clone());
+        let (daikin, ops) = fake_daikin(FakeClient::new([Ok(WRITE_OK), Ok(STATUS)]));
+        let report = daikin.update_verified(changes, true).await.unwrap();
+        assert_eq!(*ops.lock().unwrap(), vec![3, 2]);
+        assert!(!report.is_applied());
+        let clamped: Vec<_> = report.clamped().collect();
+        assert_eq!(clamped.len(), 1);
+        assert_eq!(
+            clamped[0].path.to_string(),
+            "adr_0100.dgc_status/e_1002/e_3001/p_02"
+        );
+        assert_eq!(
+            clamped[0].outcome,
+            WriteOutcome::Clamped(base.temperature.cooling.value.clone())
+        );
+        let cached = daikin.get_status().await.unwrap();
+        assert_eq!(cached.temperature.cooling, base.temperature.cooling);
+
+        let (daikin, _) = fake_daikin(FakeClient::new([Ok(WRITE_OK)]));
+        let report = daikin.update_verified(target.clone(), false).await.unwrap();
+        assert!(report.is_applied());
+        assert_eq!(report.fields[0].outcome, WriteOutcome::Accepted);
+
+        let (daikin, _) = fake_daikin(FakeClient::new([Ok(WRITE_REJECTED)]));
+        let changes = ChangeSet::builder(&base).power(PowerState::On).build();
+        let report = daikin.update_verified(changes, false).await.unwrap();
+        assert_eq!(report.rejected().count(), 1);
+        assert_eq!(report.fields[0].outcome, WriteOutcome::Rejected(4005));
+
+        // Reading back narrows a refused resource to the fields not held.
+        let mut changes = ChangeSet::full(&base);
+        changes.merge(target);
+        let (daikin, ops) = fake_daikin(FakeClient::new([Ok(WRITE_REJECTED), Ok(STATUS)]));
+        let report = daikin.update_verified(changes, true).await.unwrap();
+        assert_eq!(*ops.lock().unwrap(), vec![3, 2]);
+        let rejected: Vec<_> = report.rejected().collect();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(
+            rejected[0].path.to_string(),
+            "adr_0100.dgc_status/e_1002/e_3001/p_02"
+        );
+        assert_eq!(rejected[0].outcome, WriteOutcome::Rejected(4005));
+        assert_eq!(report.clamped().count(), 0);
+    }
+}
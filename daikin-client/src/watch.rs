@@ -0,0 +1,127 @@
+//! Push-based status change stream.
+
+use crate::client::{Daikin, HttpClient};
+use async_stream::stream;
+use dsiot::{DaikinStatus, Mode, PowerState, SensorReadings, TemperatureSettings, WindSettings};
+use futures::Stream;
+use std::time::Duration;
+
+/// Upper bound of the poll interval while the device is unreachable.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A change in device status detected by `Daikin::watch`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatusEvent {
+    /// Power was turned on or off.
+    PowerChanged(PowerState),
+    /// Operating mode changed.
+    ModeChanged(Mode),
+    /// One or more target temperatures changed.
+    SetpointChanged(TemperatureSettings),
+    /// Indoor or outdoor sensor readings changed.
+    SensorChanged(SensorReadings),
+    /// Fan speed or louver direction changed for any mode.
+    WindChanged(Box<WindSettings>),
+    /// The device stopped or started answering requests.
+    ReachabilityChanged(bool),
+}
+
+impl StatusEvent {
+    /// Collect the events describing the transition from `old` to `new`.
+    pub fn diff(old: &DaikinStatus, new: &DaikinStatus) -> Vec<StatusEvent> {
+        let mut events = Vec::new();
+        if let Some(power) = PowerState::from_status(new)
+            && PowerState::from_status(old) != Some(power)
+        {
+            events.push(StatusEvent::PowerChanged(power));
+        }
+        if let Some(mode) = new.mode.get_enum()
+            && old.mode.get_enum() != Some(mode)
+        {
+            events.push(StatusEvent::ModeChanged(mode));
+        }
+        if old.temperature != new.temperature {
+            events.push(StatusEvent::SetpointChanged(new.temperature.clone()));
+        }
+        if old.sensors != new.sensors {
+            events.push(StatusEvent::SensorChanged(new.sensors.clone()));
+        }
+        if old.wind != new.wind {
+            events.push(StatusEvent::WindChanged(Box::new(new.wind.clone())));
+        }
+        events
+    }
+}
+
+impl<H: HttpClient> Daikin<H> {
+    /// Poll the device every `interval` and yield the changes between polls.
+    ///
+    /// The first successful poll only sets the baseline. While the device is
+    /// unreachable, the interval doubles on each failure up to five minutes
+    /// and returns to `interval` once the device answers again.
+    pub fn watch(&self, interval: Duration) -> impl Stream<Item = StatusEvent> + '_ {
+        stream! {
+            let mut last: Option<DaikinStatus> = None;
+            let mut reachable = true;
+            let mut delay = interval;
+            loop {
+                match self.get_status_fresh().await {
+                    Ok(status) => {
+                        if !reachable {
+                            reachable = true;
+                            yield StatusEvent::ReachabilityChanged(true);
+                        }
+                        delay = interval;
+                        if let Some(old) = &last {
+                            for event in StatusEvent::diff(old, &status) {
+                                yield event;
+                            }
+                        }
+                        last = Some(status);
+                    }
+                    Err(e) => {
+                        debug!("Failed to poll {self:?}: {e}");
+                        if reachable {
+                            reachable = false;
+                            yield StatusEvent::ReachabilityChanged(false);
+                        }
+                        delay = (delay * 2).min(MAX_BACKOFF.max(interval));
+                    }
+                }
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dsiot::{DaikinResponse, WindSpeed};
+
+    #[test]
+    fn diff() {
+        let res: DaikinResponse =
+            serde_json::from_str(include_str!("../../dsiot/src/fixtures/status.json"))
+                .expect("Invalid JSON file.");
+        let old: DaikinStatus = res.into();
+        let mut new = old.clone();
+        assert!(StatusEvent::diff(&old, &new).is_empty());
+
+        new.power.set_value(1.0);
+        new.mode.set_value(Mode::Heating);
+        new.wind.heating.speed.set_value(WindSpeed::Lev3);
+        let events = StatusEvent::diff(&old, &new);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], StatusEvent::PowerChanged(PowerState::On));
+        assert_eq!(events[1], StatusEvent::ModeChanged(Mode::Heating));
+        assert!(matches!(events[2], StatusEvent::WindChanged(_)));
+
+        let mut new = old.clone();
+        new.sensors.temperature.set_value(21.0);
+        new.temperature.heating.set_value(22.0);
+        let events = StatusEvent::diff(&old, &new);
+        assert!(matches!(events[0], StatusEvent::SetpointChanged(_)));
+        assert!(matches!(events[1], StatusEvent::SensorChanged(_)));
+    }
+}
@@ -0,0 +1,230 @@
+//! Capability detection from property metadata.
+//!
+//! Units differ in what they can do: some have no humidity sensor, some only
+//! cool. This module reads the capabilities of a unit from the metadata of
+//! its status, so that smart home platforms only offer controls that work.
+
+use serde::de::DeserializeOwned;
+
+use crate::protocol::property::{Item, Metadata};
+use crate::protocol::status::DaikinStatus;
+use crate::types::{HorizontalDirection, Mode, VerticalDirection, WindSpeed};
+
+/// What a unit supports, according to the metadata of its status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// Operating modes the unit allows.
+    pub modes: Vec<Mode>,
+    /// Whether the unit has an indoor humidity sensor.
+    pub humidity: bool,
+    /// Whether the unit reports the outdoor temperature.
+    pub outdoor_temperature: bool,
+    /// Whether the unit reports its power consumption.
+    pub power_consumption: bool,
+    /// Wind speeds the unit allows in any mode.
+    pub wind_speeds: Vec<WindSpeed>,
+    /// Vertical directions the unit allows in any mode.
+    pub vertical_directions: Vec<VerticalDirection>,
+    /// Horizontal directions the unit allows in any mode.
+    pub horizontal_directions: Vec<HorizontalDirection>,
+}
+
+impl Capabilities {
+    /// Read the capabilities from the metadata of `status`.
+    ///
+    /// The items of a sub-response that failed have no metadata; they are
+    /// taken to be supported, so a passing error does not hide a control
+    /// for good.
+    pub fn from_status(status: &DaikinStatus) -> Self {
+        if status.indoor_failed() {
+            return Self::all();
+        }
+        let outdoor_failed = status.outdoor_failed();
+        let wind = &status.wind;
+        Self {
+            modes: status.mode.allowed_enums(),
+            humidity: is_present(&status.sensors.humidity),
+            outdoor_temperature: outdoor_failed || is_present(&status.sensors.outdoor_temperature),
+            power_consumption: outdoor_failed || is_present(&status.power_consumption),
+            wind_speeds: union([
+                wind.cooling.speed.allowed_enums(),
+                wind.heating.speed.allowed_enums(),
+                wind.fan.speed.allowed_enums(),
+                wind.dehumidify.speed.allowed_enums(),
+            ]),
+            vertical_directions: union([
+                wind.cooling.vertical_direction.allowed_enums(),
+                wind.heating.vertical_direction.allowed_enums(),
+                wind.fan.vertical_direction.allowed_enums(),
+                wind.dehumidify.vertical_direction.allowed_enums(),
+                wind.auto.vertical_direction.allowed_enums(),
+            ]),
+            horizontal_directions: union([
+                wind.cooling.horizontal_direction.allowed_enums(),
+                wind.heating.horizontal_direction.allowed_enums(),
+                wind.fan.horizontal_direction.allowed_enums(),
+                wind.dehumidify.horizontal_direction.allowed_enums(),
+                wind.auto.horizontal_direction.allowed_enums(),
+            ]),
+        }
+    }
+
+    /// Everything a unit may support.
+    pub fn all() -> Self {
+        Self {
+            modes: vec![
+                Mode::Fan,
+                Mode::Heating,
+                Mode::Cooling,
+                Mode::Auto,
+                Mode::Dehumidify,
+            ],
+            humidity: true,
+            outdoor_temperature: true,
+            power_consumption: true,
+            wind_speeds: vec![
+                WindSpeed::Silent,
+                WindSpeed::Lev1,
+                WindSpeed::Lev2,
+                WindSpeed::Lev3,
+                WindSpeed::Lev4,
+                WindSpeed::Lev5,
+                WindSpeed::Auto,
+            ],
+            vertical_directions: vec![
+                VerticalDirection::TopMost,
+                VerticalDirection::Top,
+                VerticalDirection::Center,
+                VerticalDirection::Bottom,
+                VerticalDirection::BottomMost,
+                VerticalDirection::Swing,
+                VerticalDirection::Auto,
+                VerticalDirection::Nice,
+            ],
+            horizontal_directions: vec![
+                HorizontalDirection::LeftMost,
+                HorizontalDirection::Left,
+                HorizontalDirection::LeftCenter,
+                HorizontalDirection::Center,
+                HorizontalDirection::RightCenter,
+                HorizontalDirection::Right,
+                HorizontalDirection::RightMost,
+                HorizontalDirection::Swing,
+                HorizontalDirection::Auto,
+            ],
+        }
+    }
+
+    /// Whether the unit allows `mode`.
+    pub fn has_mode(&self, mode: Mode) -> bool {
+        self.modes.contains(&mode)
+    }
+
+    /// Whether the unit has a louver that can swing.
+    pub fn can_swing(&self) -> bool {
+        self.vertical_directions.contains(&VerticalDirection::Swing)
+            || self
+                .horizontal_directions
+                .contains(&HorizontalDirection::Swing)
+    }
+}
+
+/// Whether the unit returned the item at all.
+fn is_present<T: Sized + DeserializeOwned + Into<f32>>(item: &Item<T>) -> bool {
+    !matches!(item.metadata, Metadata::Undefined)
+}
+
+/// Values allowed in any of `lists`, in the order first seen.
+fn union<T: PartialEq, const N: usize>(lists: [Vec<T>; N]) -> Vec<T> {
+    let mut values = Vec::new();
+    for value in lists.into_iter().flatten() {
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::response::DaikinResponse;
+
+    fn fixture() -> serde_json::Value {
+        serde_json::from_str(include_str!("fixtures/status.json")).expect("Invalid JSON file.")
+    }
+
+    fn status(body: serde_json::Value) -> DaikinStatus {
+        let res: DaikinResponse = serde_json::from_value(body).expect("Invalid response.");
+        res.into()
+    }
+
+    #[test]
+    fn test_from_status() {
+        let caps = Capabilities::from_status(&status(fixture()));
+        assert_eq!(
+            caps.modes,
+            vec![
+                Mode::Fan,
+                Mode::Heating,
+                Mode::Cooling,
+                Mode::Auto,
+                Mode::Dehumidify
+            ]
+        );
+        assert!(caps.humidity);
+        assert!(caps.outdoor_temperature);
+        assert!(caps.power_consumption);
+        assert!(caps.wind_speeds.contains(&WindSpeed::Lev5));
+        assert!(caps.wind_speeds.contains(&WindSpeed::Silent));
+        assert!(caps.vertical_directions.contains(&VerticalDirection::Nice));
+        assert!(caps.can_swing());
+    }
+
+    /// Children of the property named `name` among `props`.
+    fn children<'a>(
+        props: &'a mut [serde_json::Value],
+        name: &str,
+    ) -> &'a mut Vec<serde_json::Value> {
+        props
+            .iter_mut()
+            .find(|p| p["pn"] == name)
+            .and_then(|p| p["pch"].as_array_mut())
+            .expect("Missing property.")
+    }
+
+    #[test]
+    fn test_cooling_only_without_humidity() {
+        let mut body = fixture();
+        let unit = body["responses"][0]["pc"]["pch"]
+            .as_array_mut()
+            .expect("Missing entities.");
+        let entities = children(unit, "e_1002");
+        children(entities, "e_A00B").retain(|p| p["pn"] != "p_02");
+        let mode = children(entities, "e_3001")
+            .iter_mut()
+            .find(|p| p["pn"] == "p_01")
+            .expect("Missing mode.");
+        // Fan, Cooling and Dehumidify only
+        mode["md"]["mx"] = "2500".into();
+
+        let caps = Capabilities::from_status(&status(body));
+        assert_eq!(caps.modes, vec![Mode::Fan, Mode::Cooling, Mode::Dehumidify]);
+        assert!(!caps.has_mode(Mode::Heating));
+        assert!(!caps.humidity);
+        assert!(caps.outdoor_temperature);
+    }
+
+    #[test]
+    fn test_failed_outdoor_unit() {
+        let mut body = fixture();
+        body["responses"][1] = serde_json::json!({
+            "fr": "/dsiot/edge/adr_0200.dgc_status",
+            "rsc": 4004
+        });
+        let caps = Capabilities::from_status(&status(body));
+        assert!(caps.outdoor_temperature);
+        assert!(caps.power_consumption);
+        assert!(caps.humidity);
+    }
+}
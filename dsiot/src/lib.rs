@@ -3,6 +3,7 @@
 //! This crate provides protocol-agnostic abstractions for HVAC control,
 //! with specific implementations for Daikin devices.
 
+pub mod capabilities;
 pub mod constraints;
 pub mod mapping;
 pub mod protocol;
@@ -11,6 +12,7 @@ pub mod temperature;
 pub mod types;
 
 // Re-export commonly used types at crate root for convenience
+pub use capabilities::Capabilities;
 pub use constraints::ValueConstraints;
 pub use state::{DeviceState, PowerState, StateTransition, StateTransitionError};
 pub use temperature::{TemperatureError, TemperatureTarget};
@@ -18,8 +20,9 @@ pub use types::{AutoModeWindSpeed, HorizontalDirection, Mode, VerticalDirection,
 
 // Re-export protocol types for backward compatibility
 pub use protocol::{
-    AutoModeWindSettings, Binary, BinaryEnum, BinaryStep, DaikinInfo, DaikinRequest,
-    DaikinResponse, DaikinStatus, Item, Metadata, ModeWindSettings, PropValue, Property,
+    AutoModeWindSettings, Binary, BinaryEnum, BinaryStep, ChangeSet, ChangeSetBuilder, DaikinInfo,
+    DaikinRequest, DaikinResponse, DaikinStatus, IndoorUnitEntities, Item, Metadata,
+    ModeWindSettings, OutdoorUnitEntities, PropValue, Property, PropertyError, PropertyPath,
     SensorReadings, TemperatureSettings, WindSettings,
 };
 
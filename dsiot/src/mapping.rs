@@ -108,10 +108,42 @@ pub mod swing {
         matches!(direction, Some(VerticalDirection::Swing))
     }
 
+    /// Vertical direction after turning swing on or off for that axis alone.
+    ///
+    /// Turning swing off goes back to `previous`, the last direction other
+    /// than Swing, so a fixed louver position survives a swing. Without one
+    /// the louver goes to Auto.
+    pub fn vertical(enabled: bool, previous: Option<VerticalDirection>) -> VerticalDirection {
+        if enabled {
+            return VerticalDirection::Swing;
+        }
+        match previous {
+            Some(VerticalDirection::Swing | VerticalDirection::Unknown) | None => {
+                VerticalDirection::Auto
+            }
+            Some(direction) => direction,
+        }
+    }
+
+    /// Horizontal direction after turning swing on or off for that axis
+    /// alone. See [`vertical`].
+    pub fn horizontal(enabled: bool, previous: Option<HorizontalDirection>) -> HorizontalDirection {
+        if enabled {
+            return HorizontalDirection::Swing;
+        }
+        match previous {
+            Some(HorizontalDirection::Swing | HorizontalDirection::Unknown) | None => {
+                HorizontalDirection::Auto
+            }
+            Some(direction) => direction,
+        }
+    }
+
     /// Convert swing enabled state to direction settings.
     ///
     /// When swing is disabled, both directions are set to Auto.
     /// When swing is enabled, both directions are set to Swing.
+    /// Use [`vertical`] and [`horizontal`] to keep fixed positions instead.
     pub fn from_enabled(enabled: bool) -> (VerticalDirection, HorizontalDirection) {
         if enabled {
             (VerticalDirection::Swing, HorizontalDirection::Swing)
@@ -260,6 +292,37 @@ mod tests {
             assert!(!swing::to_enabled(None));
         }
 
+        #[test]
+        fn test_per_axis() {
+            assert_eq!(
+                swing::vertical(true, Some(VerticalDirection::Center)),
+                VerticalDirection::Swing
+            );
+            assert_eq!(
+                swing::vertical(false, Some(VerticalDirection::Center)),
+                VerticalDirection::Center
+            );
+            assert_eq!(
+                swing::vertical(false, Some(VerticalDirection::Nice)),
+                VerticalDirection::Nice
+            );
+            assert_eq!(swing::vertical(false, None), VerticalDirection::Auto);
+            assert_eq!(
+                swing::vertical(false, Some(VerticalDirection::Swing)),
+                VerticalDirection::Auto
+            );
+
+            assert_eq!(
+                swing::horizontal(true, Some(HorizontalDirection::LeftCenter)),
+                HorizontalDirection::Swing
+            );
+            assert_eq!(
+                swing::horizontal(false, Some(HorizontalDirection::LeftCenter)),
+                HorizontalDirection::LeftCenter
+            );
+            assert_eq!(swing::horizontal(false, None), HorizontalDirection::Auto);
+        }
+
         #[test]
         fn test_from_enabled() {
             assert_eq!(
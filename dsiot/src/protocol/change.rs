@@ -0,0 +1,373 @@
+//! Minimal write requests built from status differences.
+
+use super::path::PropertyPath;
+use super::property::{Item, Metadata, PropValue, Property, PropertyType};
+use super::request::{DaikinRequest, Request};
+use super::status::DaikinStatus;
+use crate::state::{PowerState, StateTransition, StateTransitionError};
+use crate::temperature::TemperatureTarget;
+use crate::types::Mode;
+
+const RESOURCE: &str = "adr_0100.dgc_status";
+
+/// A writable field of `DaikinStatus` and its location in `dgc_status`.
+struct Field {
+    path: [&'static str; 3],
+    get: fn(&DaikinStatus) -> &PropValue,
+    get_mut: fn(&mut DaikinStatus) -> &mut PropValue,
+    metadata: fn(&DaikinStatus) -> &Metadata,
+}
+
+macro_rules! field {
+    ($e:literal, $p:literal, $($f:ident).+) => {
+        Field {
+            path: ["e_1002", $e, $p],
+            get: |s| &s.$($f).+.value,
+            get_mut: |s| &mut s.$($f).+.value,
+            metadata: |s| &s.$($f).+.metadata,
+        }
+    };
+}
+
+/// All fields written by `DaikinRequest::from(DaikinStatus)`.
+const FIELDS: [Field; 20] = [
+    field!("e_A002", "p_01", power),
+    field!("e_3001", "p_01", mode),
+    field!("e_3001", "p_02", temperature.cooling),
+    field!("e_3001", "p_03", temperature.heating),
+    field!("e_3001", "p_1F", temperature.automatic),
+    field!("e_3001", "p_09", wind.cooling.speed),
+    field!("e_3001", "p_05", wind.cooling.vertical_direction),
+    field!("e_3001", "p_06", wind.cooling.horizontal_direction),
+    field!("e_3001", "p_0A", wind.heating.speed),
+    field!("e_3001", "p_07", wind.heating.vertical_direction),
+    field!("e_3001", "p_08", wind.heating.horizontal_direction),
+    field!("e_3001", "p_28", wind.fan.speed),
+    field!("e_3001", "p_24", wind.fan.vertical_direction),
+    field!("e_3001", "p_25", wind.fan.horizontal_direction),
+    field!("e_3001", "p_27", wind.dehumidify.speed),
+    field!("e_3001", "p_22", wind.dehumidify.vertical_direction),
+    field!("e_3001", "p_23", wind.dehumidify.horizontal_direction),
+    field!("e_3001", "p_26", wind.auto.speed),
+    field!("e_3001", "p_20", wind.auto.vertical_direction),
+    field!("e_3001", "p_21", wind.auto.horizontal_direction),
+];
+
+/// A set of property changes to write to the device.
+///
+/// Unlike `DaikinRequest::from(DaikinStatus)`, which writes every writable
+/// field, a change set only contains the fields that actually changed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangeSet {
+    /// Index into `FIELDS` and the encoded value to write.
+    changes: Vec<(usize, PropValue)>,
+}
+
+impl ChangeSet {
+    /// Create an empty change set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect the writable fields whose value differs between `old` and `new`.
+    pub fn diff(old: &DaikinStatus, new: &DaikinStatus) -> Self {
+        let changes = FIELDS
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| (f.get)(old) != (f.get)(new))
+            .map(|(i, f)| (i, (f.get)(new).clone()))
+            .collect();
+        Self { changes }
+    }
+
+    /// Collect every writable field of `status`.
+    pub fn full(status: &DaikinStatus) -> Self {
+        let changes = FIELDS
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (i, (f.get)(status).clone()))
+            .collect();
+        Self { changes }
+    }
+
+    /// Start building a change set on top of `base`.
+    pub fn builder(base: &DaikinStatus) -> ChangeSetBuilder {
+        ChangeSetBuilder {
+            base: base.clone(),
+            target: base.clone(),
+        }
+    }
+
+    /// Returns true if there is nothing to write.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the number of changed fields.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns the paths of the changed fields.
+    pub fn paths(&self) -> impl Iterator<Item = PropertyPath> + '_ {
+        self.changes
+            .iter()
+            .map(|(i, _)| PropertyPath::new(RESOURCE, &FIELDS[*i].path))
+    }
+
+    /// Returns the paths and encoded values of the changed fields.
+    pub fn iter(&self) -> impl Iterator<Item = (PropertyPath, &PropValue)> + '_ {
+        self.changes
+            .iter()
+            .map(|(i, value)| (PropertyPath::new(RESOURCE, &FIELDS[*i].path), value))
+    }
+
+    /// Returns the paths of the changes whose value the metadata of the same
+    /// field in `status` does not allow, e.g. to refuse a write before it is
+    /// sent.
+    pub fn disallowed<'a>(
+        &'a self,
+        status: &'a DaikinStatus,
+    ) -> impl Iterator<Item = PropertyPath> + 'a {
+        self.changes
+            .iter()
+            .filter(|(i, value)| !(FIELDS[*i].metadata)(status).allows(value))
+            .map(|(i, _)| PropertyPath::new(RESOURCE, &FIELDS[*i].path))
+    }
+
+    /// Merge `other` into this change set. Values in `other` take precedence.
+    pub fn merge(&mut self, other: ChangeSet) {
+        for (i, value) in other.changes {
+            match self.changes.iter_mut().find(|(j, _)| *j == i) {
+                Some(change) => change.1 = value,
+                None => self.changes.push((i, value)),
+            }
+        }
+    }
+
+    /// Returns true if `status` already holds every value of this change set,
+    /// e.g. to check whether a write reached the device.
+    pub fn is_applied_to(&self, status: &DaikinStatus) -> bool {
+        self.changes
+            .iter()
+            .all(|(i, value)| (FIELDS[*i].get)(status) == value)
+    }
+
+    /// Pair each change with the value `status` holds for the same field,
+    /// e.g. to see which values a device actually applied.
+    pub fn compare<'a>(
+        &'a self,
+        status: &'a DaikinStatus,
+    ) -> impl Iterator<Item = (PropertyPath, &'a PropValue, &'a PropValue)> + 'a {
+        self.changes.iter().map(|(i, value)| {
+            (
+                PropertyPath::new(RESOURCE, &FIELDS[*i].path),
+                value,
+                (FIELDS[*i].get)(status),
+            )
+        })
+    }
+
+    /// Apply the changes to a status, e.g. to keep a cached copy up to date.
+    pub fn apply_to(&self, status: &mut DaikinStatus) {
+        for (i, value) in &self.changes {
+            *(FIELDS[*i].get_mut)(status) = value.clone();
+        }
+    }
+}
+
+impl From<DaikinStatus> for ChangeSet {
+    fn from(status: DaikinStatus) -> Self {
+        Self::full(&status)
+    }
+}
+
+impl From<ChangeSet> for DaikinRequest {
+    fn from(changes: ChangeSet) -> Self {
+        let mut prop = Property::new_tree("dgc_status");
+        for (i, value) in changes.changes {
+            let [entity_group, entity, name] = FIELDS[i].path;
+            let mut node = &mut prop;
+            for tree in [entity_group, entity] {
+                if node.find(tree).is_none() {
+                    node.push(Property::new_tree(tree));
+                }
+                node = node.find_mut(tree).expect("tree was just inserted");
+            }
+            node.push(Property::Node(Item::<f32> {
+                name: name.to_string(),
+                type_: PropertyType::ReadWrite,
+                value,
+                metadata: Metadata::Undefined,
+                phantom: std::marker::PhantomData,
+            }));
+        }
+
+        DaikinRequest {
+            requests: vec![Request {
+                op: 3,
+                pc: prop,
+                to: PropertyPath::new(RESOURCE, &[]).from(),
+            }],
+        }
+    }
+}
+
+/// Builder for a `ChangeSet` relative to a base status.
+#[derive(Clone, Debug)]
+pub struct ChangeSetBuilder {
+    base: DaikinStatus,
+    target: DaikinStatus,
+}
+
+impl ChangeSetBuilder {
+    /// Set power state.
+    pub fn power(mut self, power: PowerState) -> Self {
+        self.target.power.set_value(power.to_f32());
+        self
+    }
+
+    /// Set operating mode.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.target.mode.set_value(mode);
+        self
+    }
+
+    /// Apply a state transition.
+    pub fn transition(
+        mut self,
+        transition: &StateTransition,
+    ) -> Result<Self, StateTransitionError> {
+        transition.apply_to_status(&mut self.target)?;
+        Ok(self)
+    }
+
+    /// Set a temperature target.
+    pub fn temperature(mut self, target: TemperatureTarget) -> Self {
+        target.apply_to_status(&mut self.target);
+        self
+    }
+
+    /// Modify any other writable field of the status.
+    pub fn with(mut self, f: impl FnOnce(&mut DaikinStatus)) -> Self {
+        f(&mut self.target);
+        self
+    }
+
+    /// Build the change set containing only the fields that differ from the base.
+    pub fn build(self) -> ChangeSet {
+        ChangeSet::diff(&self.base, &self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::response::DaikinResponse;
+    use crate::types::WindSpeed;
+
+    fn fixture() -> DaikinStatus {
+        let res: DaikinResponse = serde_json::from_str(include_str!("../fixtures/status.json"))
+            .expect("Invalid JSON file.");
+        res.into()
+    }
+
+    #[test]
+    fn diff() {
+        let old = fixture();
+        let mut new = old.clone();
+        assert!(ChangeSet::diff(&old, &new).is_empty());
+
+        new.wind.cooling.speed.set_value(WindSpeed::Lev2);
+        let changes = ChangeSet::diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes.paths().map(|p| p.to_string()).collect::<Vec<_>>(),
+            vec!["adr_0100.dgc_status/e_1002/e_3001/p_09"]
+        );
+
+        let (_, value) = changes.iter().next().unwrap();
+        assert_eq!(value, &PropValue::String("0400".into()));
+
+        let req: DaikinRequest = changes.into();
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"requests":[{"op":3,"pc":{"pn":"dgc_status","pch":[{"pn":"e_1002","pch":[{"pn":"e_3001","pch":[{"pn":"p_09","pv":"0400"}]}]}]},"to":"/dsiot/edge/adr_0100.dgc_status"}]}"#
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let base = fixture();
+        let changes = ChangeSet::builder(&base)
+            .power(PowerState::On)
+            .mode(Mode::Cooling) // unchanged
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+        assert_eq!(changes.len(), 2);
+
+        let req: DaikinRequest = changes.into();
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"requests":[{"op":3,"pc":{"pn":"dgc_status","pch":[{"pn":"e_1002","pch":[{"pn":"e_A002","pch":[{"pn":"p_01","pv":"01"}]},{"pn":"e_3001","pch":[{"pn":"p_02","pv":"34"}]}]}]},"to":"/dsiot/edge/adr_0100.dgc_status"}]}"#
+        );
+    }
+
+    #[test]
+    fn merge_and_apply() {
+        let base = fixture();
+        let mut changes = ChangeSet::builder(&base)
+            .power(PowerState::On)
+            .mode(Mode::Heating)
+            .build();
+        changes.merge(ChangeSet::builder(&base).mode(Mode::Fan).build());
+        assert_eq!(changes.len(), 2);
+
+        let mut status = base.clone();
+        assert!(!changes.is_applied_to(&status));
+        changes.apply_to(&mut status);
+        assert!(changes.is_applied_to(&status));
+        assert!(changes.compare(&status).all(|(_, want, got)| want == got));
+        let (path, want, got) = changes.compare(&base).nth(1).unwrap();
+        assert_eq!(path.to_string(), "adr_0100.dgc_status/e_1002/e_3001/p_01");
+        assert_ne!(want, got);
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::On));
+        assert_eq!(status.mode.get_enum(), Some(Mode::Fan));
+    }
+
+    #[test]
+    fn disallowed() {
+        let base = fixture();
+        let allowed = ChangeSet::builder(&base)
+            .mode(Mode::Heating)
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+        assert_eq!(allowed.disallowed(&base).count(), 0);
+
+        let mut status = base.clone();
+        status.temperature.cooling.set_value(40.0);
+        status.wind.cooling.speed.set_raw_enum(0x0F);
+        let changes = ChangeSet::diff(&base, &status);
+        assert_eq!(
+            changes
+                .disallowed(&base)
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "adr_0100.dgc_status/e_1002/e_3001/p_02",
+                "adr_0100.dgc_status/e_1002/e_3001/p_09",
+            ]
+        );
+    }
+
+    #[test]
+    fn full() {
+        let status = fixture();
+        let changes = ChangeSet::full(&status);
+        assert_eq!(changes.len(), FIELDS.len());
+        assert_eq!(
+            serde_json::to_value(DaikinRequest::from(changes)).unwrap(),
+            serde_json::to_value(DaikinRequest::from(status)).unwrap()
+        );
+    }
+}
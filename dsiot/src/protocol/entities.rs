@@ -0,0 +1,660 @@
+//! Entities of `dgc_status` that are not mapped onto `DaikinStatus` fields.
+//!
+//! Property semantics are mostly undocumented by the vendor. Descriptions
+//! below are based on the metadata (`st`/`mi`/`mx`) and observed values; where
+//! the meaning is unknown, only the value range is given. All values are kept
+//! as raw `Item`s so they can be decoded with `get_f32`, `get_enum` or
+//! `get_string` depending on their metadata; properties whose meaning is
+//! known also have typed accessors: error codes (`EntityA004::error_codes`),
+//! timers (`EntityE3003::off_timer`), powerful operation and the streamer
+//! (`EntityE3003::powerful`, `EntityE3003::streamer`) and econo operation
+//! (`EntityE3002::econo`).
+
+use std::time::Duration;
+
+use super::path::PropertyPath;
+use super::property::{Item, Property};
+use super::response::DaikinResponse;
+
+const INDOOR: &str = "adr_0100.dgc_status";
+const OUTDOOR: &str = "adr_0200.dgc_status";
+
+/// Error code string of a slot while the unit reports no error.
+const NO_ERROR: &str = "00-";
+
+/// The error code held by an error code slot, if any.
+fn error_code(slot: &Item<f32>) -> Option<String> {
+    slot.get_string()
+        .map(|code| code.trim().to_string())
+        .filter(|code| !code.is_empty() && code != NO_ERROR)
+}
+
+/// A flag property, `None` while the unit does not report it.
+fn flag(item: &Item<f32>) -> Option<bool> {
+    item.get_f32().map(|v| v != 0.0)
+}
+
+/// Value of `e_3003/p_2D` while powerful operation is running.
+const POWERFUL: u8 = 4;
+
+/// A timer property in minutes, `None` while the timer is not set.
+fn timer(minutes: &Item<f32>) -> Option<Duration> {
+    minutes
+        .get_f32()
+        .filter(|m| *m > 0.0)
+        .map(|m| Duration::from_secs(m as u64 * 60))
+}
+
+macro_rules! entity {
+    (
+        $(#[$meta:meta])*
+        $name:ident {
+            $( $(#[$fmeta:meta])* $field:ident: $prop:ident ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $name {
+            $( $(#[$fmeta])* pub $field: Item<f32>, )*
+        }
+
+        impl $name {
+            fn from_entity(entity: Option<&Property>) -> Self {
+                Self {
+                    $( $field: get_child_prop!({ entity }.$prop), )*
+                }
+            }
+        }
+    };
+}
+
+entity! {
+    /// Unit identification (`e_A001`), present on both indoor and outdoor units.
+    ///
+    /// Most properties are binary strings. The outdoor unit only reports a
+    /// subset of them, padded with spaces or `null`.
+    EntityA001 {
+        /// String, 30 bytes.
+        p_01: p_01,
+        /// String, 15 bytes.
+        p_02: p_02,
+        /// 0.0–25.5 in 0.1 steps.
+        p_03: p_03,
+        /// Enum (0–2).
+        p_07: p_07,
+        /// 0–26.
+        p_08: p_08,
+        /// String, e.g. `"03"`.
+        p_09: p_09,
+        /// Flag (0/1).
+        p_0c: p_0C,
+        /// String, e.g. `"0123"`.
+        p_0d: p_0D,
+        /// Enum (0–1).
+        p_0e: p_0E,
+        /// String holding the adapter MAC address (read-write).
+        p_0f: p_0F,
+        /// 0–255.
+        p_10: p_10,
+        /// String, e.g. `"1"`.
+        p_11: p_11,
+        /// String, e.g. `"0"`.
+        p_12: p_12,
+        /// Date string in `YYYYMMDD` format.
+        p_13: p_13,
+        /// Date string in `YYMMDD` format followed by six digits.
+        p_14: p_14,
+        /// String, 30 bytes.
+        p_18: p_18,
+    }
+}
+
+entity! {
+    /// Error code slots (`e_A004`), present on both indoor and outdoor units.
+    ///
+    /// Each slot is a code string followed by three enums. The code reads
+    /// `"00-"` while the unit reports no error.
+    EntityA004 {
+        /// First error code string.
+        p_02: p_02,
+        /// Enum (0–3) belonging to the first code.
+        p_03: p_03,
+        /// Enum (0–4) belonging to the first code.
+        p_04: p_04,
+        /// Enum (0–2) belonging to the first code.
+        p_05: p_05,
+        /// Second error code string (indoor unit only).
+        p_09: p_09,
+        /// Enum (0–3) belonging to the second code.
+        p_0a: p_0A,
+        /// Enum (0–4) belonging to the second code.
+        p_0b: p_0B,
+        /// Enum (0–2) belonging to the second code.
+        p_0c: p_0C,
+    }
+}
+
+impl EntityA004 {
+    /// Error codes the unit reports, e.g. `"U4-"`, leaving out slots that
+    /// hold no error.
+    pub fn error_codes(&self) -> Vec<String> {
+        [&self.p_02, &self.p_09]
+            .into_iter()
+            .filter_map(error_code)
+            .collect()
+    }
+}
+
+entity! {
+    /// Counters and strings of the indoor unit (`e_A006`).
+    EntityA006 {
+        /// 0–255.
+        p_0e: p_0E,
+        /// 0–15.
+        p_0f: p_0F,
+        /// String, e.g. `"0606060303"`.
+        p_10: p_10,
+        /// 0–255.
+        p_11: p_11,
+        /// 0–15.
+        p_13: p_13,
+        /// Enum (0–1).
+        p_14: p_14,
+        /// String, e.g. `"0000868686"`.
+        p_15: p_15,
+        /// 0–255.
+        p_16: p_16,
+        /// 0–255.
+        p_1a: p_1A,
+        /// 0–255.
+        p_1b: p_1B,
+        /// 0–255.
+        p_1c: p_1C,
+    }
+}
+
+entity! {
+    /// Enums of `e_A010`. The indoor unit reports `p_07` and `p_09`, the
+    /// outdoor unit reports `p_08`.
+    EntityA010 {
+        /// Enum (0–1).
+        p_07: p_07,
+        /// 1–15.
+        p_08: p_08,
+        /// Enum (1–3).
+        p_09: p_09,
+    }
+}
+
+entity! {
+    /// Flags and counters of the indoor unit (`e_A011`).
+    EntityA011 {
+        /// Flag (0/1).
+        p_01: p_01,
+        /// Flag (0/1).
+        p_02: p_02,
+        /// Flag (0/1, read-write).
+        p_03: p_03,
+        /// 0–255.
+        p_06: p_06,
+        /// 0–255.
+        p_07: p_07,
+        /// 0–255.
+        p_08: p_08,
+        /// 0–255.
+        p_09: p_09,
+        /// 0–255.
+        p_0a: p_0A,
+        /// 0–255.
+        p_0b: p_0B,
+        /// 0–255.
+        p_0c: p_0C,
+    }
+}
+
+entity! {
+    /// Signed 16-bit values of `e_2015`.
+    ///
+    /// The indoor unit reports this entity twice with different ranges
+    /// (-416 to 5440 and -112 to 2144).
+    EntityE2015 {
+        /// Signed value.
+        p_02: p_02,
+        /// Signed value.
+        p_03: p_03,
+    }
+}
+
+entity! {
+    /// Raw 4-byte value of `e_2016`, e.g. `"01AD0021"`.
+    EntityE2016 {
+        /// Raw value without a step or range.
+        p_01: p_01,
+    }
+}
+
+entity! {
+    /// Signed 16-bit values of `e_2025` (-64 to 2416).
+    EntityE2025 {
+        /// Signed value.
+        p_02: p_02,
+        /// Signed value.
+        p_03: p_03,
+    }
+}
+
+entity! {
+    /// Counters of `e_2007`, nested in `e_2028`.
+    EntityE2007 {
+        /// 0.001 steps.
+        p_02: p_02,
+        /// 0.01 steps.
+        p_03: p_03,
+    }
+}
+
+/// Counters of `e_2028`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityE2028 {
+    /// 0–2550 in steps of 10.
+    pub p_03: Item<f32>,
+    /// 0–1850 in steps of 10.
+    pub p_04: Item<f32>,
+    /// Nested `e_2007` entity.
+    pub e_2007: EntityE2007,
+}
+
+impl EntityE2028 {
+    fn from_entity(entity: Option<&Property>) -> Self {
+        Self {
+            p_03: get_child_prop!({ entity }.p_03),
+            p_04: get_child_prop!({ entity }.p_04),
+            e_2007: EntityE2007::from_entity(entity.and_then(|p| p.find("e_2007"))),
+        }
+    }
+}
+
+entity! {
+    /// Temperature of `e_2029`.
+    EntityE2029 {
+        /// Temperature in Celsius (-32.0 to 95.5).
+        p_02: p_02,
+    }
+}
+
+entity! {
+    /// Settings and state of the indoor unit (`e_3003`).
+    ///
+    /// `p_31`–`p_34` mirror the layout of an `e_A004` error code slot.
+    EntityE3003 {
+        /// Enum (0–2, 5).
+        p_01: p_01,
+        /// Flag (0/1).
+        p_02: p_02,
+        /// Temperature in Celsius (14.0 to 33.0).
+        p_0c: p_0C,
+        /// Percentage (0–100).
+        p_1a: p_1A,
+        /// Flag (0/1).
+        p_1b: p_1B,
+        /// Flag (0/1).
+        p_1d: p_1D,
+        /// Flag (0/1).
+        p_1e: p_1E,
+        /// Flag (0/1).
+        p_20: p_20,
+        /// Enum (0–3).
+        p_21: p_21,
+        /// Enum (0–2).
+        p_22: p_22,
+        /// Flag (0/1).
+        p_25: p_25,
+        /// 0–15.
+        p_26: p_26,
+        /// Flag (0/1).
+        p_28: p_28,
+        /// Flag (0/1).
+        p_29: p_29,
+        /// 0–11.
+        p_2a: p_2A,
+        /// Flag (0/1).
+        p_2c: p_2C,
+        /// Enum (read-write) for powerful operation; see `powerful`.
+        p_2d: p_2D,
+        /// 0–10.
+        p_2f: p_2F,
+        /// Flag (0/1).
+        p_30: p_30,
+        /// Error code string, as in `e_A004`; see `error_code`.
+        p_31: p_31,
+        /// Enum (0–3).
+        p_32: p_32,
+        /// Enum (0–4).
+        p_33: p_33,
+        /// Enum (0–2).
+        p_34: p_34,
+        /// Flag (0/1, read-write) of the streamer; see `streamer`.
+        p_36: p_36,
+        /// Enum (0–3).
+        p_37: p_37,
+        /// 24-bit value.
+        p_38: p_38,
+        /// Minutes (0–1440) of the off timer; see `off_timer`.
+        p_3a: p_3A,
+        /// Minutes (0–1440) of the on timer; see `on_timer`.
+        p_3b: p_3B,
+        /// String holding the adapter MAC address (read-write).
+        p_41: p_41,
+        /// Enum (0–1).
+        p_4d: p_4D,
+    }
+}
+
+impl EntityE3003 {
+    /// Error code of the indoor unit (`p_31`), as in `EntityA004`.
+    pub fn error_code(&self) -> Option<String> {
+        error_code(&self.p_31)
+    }
+
+    /// Time until the off timer turns the unit off (`p_3a`).
+    ///
+    /// `p_3a` and `p_3b` are taken to be the off and on timers in the order
+    /// of the remote's timer menu; this is not confirmed for every model.
+    pub fn off_timer(&self) -> Option<Duration> {
+        timer(&self.p_3a)
+    }
+
+    /// Time until the on timer turns the unit on (`p_3b`).
+    pub fn on_timer(&self) -> Option<Duration> {
+        timer(&self.p_3b)
+    }
+
+    /// Whether powerful operation is running (`p_2d`).
+    ///
+    /// `p_2d` is the writable enum of the indoor unit whose metadata only
+    /// allows `4` besides the `0` it reads while nothing special runs.
+    pub fn powerful(&self) -> Option<bool> {
+        self.p_2d.get_raw_enum().map(|v| v == POWERFUL)
+    }
+
+    /// Whether the streamer is on (`p_36`), the writable flag of the indoor
+    /// unit.
+    pub fn streamer(&self) -> Option<bool> {
+        flag(&self.p_36)
+    }
+}
+
+entity! {
+    /// Energy counters of the outdoor unit (`e_A00A`), in 0.01 steps.
+    EntityA00A {
+        /// Counter.
+        p_03: p_03,
+        /// Counter.
+        p_04: p_04,
+        /// Counter.
+        p_07: p_07,
+        /// Counter.
+        p_08: p_08,
+    }
+}
+
+entity! {
+    /// Settings and state of the outdoor unit (`e_3002`).
+    EntityE3002 {
+        /// Enum (0–2, 5).
+        p_02: p_02,
+        /// Flag (0/1).
+        p_04: p_04,
+        /// Enum (0–1).
+        p_5c: p_5C,
+        /// Flag (0/1).
+        p_5d: p_5D,
+        /// Flag (0/1, read-write) of econo operation; see `econo`.
+        p_5e: p_5E,
+        /// 0–255.
+        p_5f: p_5F,
+        /// 0–255.
+        p_60: p_60,
+        /// 24-bit value.
+        p_61: p_61,
+        /// 24-bit value.
+        p_62: p_62,
+        /// 24-bit value.
+        p_63: p_63,
+        /// 24-bit value.
+        p_64: p_64,
+        /// 0–255.
+        p_65: p_65,
+        /// 0–255.
+        p_66: p_66,
+        /// 0–255.
+        p_67: p_67,
+        /// Temperature in Celsius (-32.0 to 95.5).
+        p_68: p_68,
+        /// Temperature in Celsius (-32.0 to 95.5).
+        p_69: p_69,
+    }
+}
+
+impl EntityE3002 {
+    /// Whether econo operation is running (`p_5e`), the writable flag of the
+    /// outdoor unit, whose power it limits.
+    pub fn econo(&self) -> Option<bool> {
+        flag(&self.p_5e)
+    }
+}
+
+/// Unmapped entities of the indoor unit (`e_1002`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndoorUnitEntities {
+    /// Unit identification.
+    pub e_a001: EntityA001,
+    /// Error code slots.
+    pub e_a004: EntityA004,
+    /// Counters and strings.
+    pub e_a006: EntityA006,
+    /// Enums.
+    pub e_a010: EntityA010,
+    /// Flags and counters.
+    pub e_a011: EntityA011,
+    /// All `e_2015` entities, in response order.
+    pub e_2015: Vec<EntityE2015>,
+    /// Raw value.
+    pub e_2016: EntityE2016,
+    /// Signed values.
+    pub e_2025: EntityE2025,
+    /// Counters.
+    pub e_2028: EntityE2028,
+    /// Temperature.
+    pub e_2029: EntityE2029,
+    /// Settings and state.
+    pub e_3003: EntityE3003,
+}
+
+/// Unmapped entities of the outdoor unit (`e_1003`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutdoorUnitEntities {
+    /// Unit identification.
+    pub e_a001: EntityA001,
+    /// Error code slot.
+    pub e_a004: EntityA004,
+    /// Energy counters.
+    pub e_a00a: EntityA00A,
+    /// Enums.
+    pub e_a010: EntityA010,
+    /// Settings and state.
+    pub e_3002: EntityE3002,
+}
+
+impl From<&DaikinResponse> for IndoorUnitEntities {
+    fn from(response: &DaikinResponse) -> Self {
+        let unit = entity(response, INDOOR, "e_1002");
+        let find = |name| unit.and_then(|p| p.find(name));
+        IndoorUnitEntities {
+            e_a001: EntityA001::from_entity(find("e_A001")),
+            e_a004: EntityA004::from_entity(find("e_A004")),
+            e_a006: EntityA006::from_entity(find("e_A006")),
+            e_a010: EntityA010::from_entity(find("e_A010")),
+            e_a011: EntityA011::from_entity(find("e_A011")),
+            e_2015: children(unit, "e_2015")
+                .map(|p| EntityE2015::from_entity(Some(p)))
+                .collect(),
+            e_2016: EntityE2016::from_entity(find("e_2016")),
+            e_2025: EntityE2025::from_entity(find("e_2025")),
+            e_2028: EntityE2028::from_entity(find("e_2028")),
+            e_2029: EntityE2029::from_entity(find("e_2029")),
+            e_3003: EntityE3003::from_entity(find("e_3003")),
+        }
+    }
+}
+
+impl From<&DaikinResponse> for OutdoorUnitEntities {
+    fn from(response: &DaikinResponse) -> Self {
+        let unit = entity(response, OUTDOOR, "e_1003");
+        let find = |name| unit.and_then(|p| p.find(name));
+        OutdoorUnitEntities {
+            e_a001: EntityA001::from_entity(find("e_A001")),
+            e_a004: EntityA004::from_entity(find("e_A004")),
+            e_a00a: EntityA00A::from_entity(find("e_A00A")),
+            e_a010: EntityA010::from_entity(find("e_A010")),
+            e_3002: EntityE3002::from_entity(find("e_3002")),
+        }
+    }
+}
+
+fn entity<'a>(response: &'a DaikinResponse, resource: &str, name: &str) -> Option<&'a Property> {
+    response
+        .property(&PropertyPath::new(resource, &[name]))
+        .ok()
+}
+
+/// Iterate over all children with the given name, including duplicates.
+fn children<'a>(parent: Option<&'a Property>, name: &'a str) -> impl Iterator<Item = &'a Property> {
+    let children = match parent {
+        Some(Property::Tree { children, .. }) => children.as_slice(),
+        _ => &[],
+    };
+    children.iter().filter(move |p| p.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::property::PropValue;
+
+    #[test]
+    fn indoor() {
+        let res: DaikinResponse = serde_json::from_str(include_str!("../fixtures/status.json"))
+            .expect("Invalid JSON file.");
+        let indoor = IndoorUnitEntities::from(&res);
+
+        assert_eq!(indoor.e_a001.p_03.get_f32(), Some(5.6));
+        assert_eq!(indoor.e_a001.p_0f.get_string(), Some("00005E005342".into()));
+        assert_eq!(indoor.e_a001.p_13.get_string(), Some("20220626".into()));
+        assert_eq!(indoor.e_a004.p_02.get_string(), Some("00-".into()));
+        assert_eq!(indoor.e_a004.p_0b.get_enum(), Some(0.0));
+        assert_eq!(indoor.e_a006.p_0f.get_f32(), Some(3.0));
+        assert_eq!(indoor.e_a010.p_09.get_enum(), Some(1.0));
+        assert_eq!(indoor.e_a010.p_08.get_f32(), None);
+        assert_eq!(indoor.e_a011.p_02.get_f32(), Some(1.0));
+
+        assert_eq!(indoor.e_2015.len(), 2);
+        assert_eq!(indoor.e_2015[0].p_02.get_f32(), Some(5440.0));
+        assert_eq!(indoor.e_2015[1].p_03.get_f32(), Some(1248.0));
+        assert_eq!(
+            indoor.e_2016.p_01.value,
+            PropValue::String("01AD0021".into())
+        );
+        assert_eq!(indoor.e_2025.p_02.get_f32(), Some(1328.0));
+        assert_eq!(indoor.e_2028.p_03.get_f32(), Some(0.0));
+        assert_eq!(
+            indoor
+                .e_2028
+                .e_2007
+                .p_03
+                .get_f32()
+                .map(|v| (v * 100.0).round()),
+            Some(27764.0)
+        );
+        assert_eq!(indoor.e_2029.p_02.get_f32(), Some(23.5));
+
+        assert_eq!(indoor.e_3003.p_0c.get_f32(), Some(24.5));
+        assert_eq!(indoor.e_3003.p_1a.get_f32(), Some(50.0));
+        assert_eq!(indoor.e_3003.p_20.get_f32(), Some(1.0));
+        assert_eq!(indoor.e_3003.p_2f.get_f32(), Some(4.0));
+        assert_eq!(indoor.e_3003.p_3a.get_f32(), Some(0.0));
+        assert_eq!(indoor.e_3003.p_41.get_string(), Some("00005E005342".into()));
+    }
+
+    #[test]
+    fn typed_accessors() {
+        let res: DaikinResponse = serde_json::from_str(include_str!("../fixtures/status.json"))
+            .expect("Invalid JSON file.");
+        let mut indoor = IndoorUnitEntities::from(&res);
+        assert!(indoor.e_a004.error_codes().is_empty());
+        assert_eq!(indoor.e_3003.error_code(), None);
+        assert_eq!(indoor.e_3003.off_timer(), None);
+        assert_eq!(indoor.e_3003.on_timer(), None);
+
+        // Binary strings are stored in reverse byte order
+        indoor.e_a004.p_09.value = PropValue::String("2D3455".into());
+        indoor.e_3003.p_3a.set_value(90.0);
+        assert_eq!(indoor.e_a004.error_codes(), vec!["U4-".to_string()]);
+        assert_eq!(
+            indoor.e_3003.off_timer(),
+            Some(Duration::from_secs(90 * 60))
+        );
+
+        let outdoor = OutdoorUnitEntities::from(&res);
+        assert!(outdoor.e_a004.error_codes().is_empty());
+    }
+
+    #[test]
+    fn special_operation() {
+        let res: DaikinResponse = serde_json::from_str(include_str!("../fixtures/status.json"))
+            .expect("Invalid JSON file.");
+        let mut indoor = IndoorUnitEntities::from(&res);
+        let mut outdoor = OutdoorUnitEntities::from(&res);
+        // The fixture unit is cooling with nothing special running
+        assert_eq!(indoor.e_3003.powerful(), Some(false));
+        assert_eq!(indoor.e_3003.streamer(), Some(false));
+        assert_eq!(outdoor.e_3002.econo(), Some(false));
+        assert_eq!(indoor.e_3003.p_2d.allowed_raw_enums(), vec![POWERFUL]);
+
+        indoor.e_3003.p_2d.set_raw_enum(POWERFUL);
+        indoor.e_3003.p_36.set_value(1.0);
+        outdoor.e_3002.p_5e.set_value(1.0);
+        assert_eq!(indoor.e_3003.powerful(), Some(true));
+        assert_eq!(indoor.e_3003.streamer(), Some(true));
+        assert_eq!(outdoor.e_3002.econo(), Some(true));
+
+        // Not reported while the outdoor unit does not answer
+        let mut res = res;
+        res.responses.retain(|r| r.from != "/dsiot/edge/adr_0200.dgc_status");
+        assert_eq!(OutdoorUnitEntities::from(&res).e_3002.econo(), None);
+    }
+
+    #[test]
+    fn outdoor() {
+        let res: DaikinResponse = serde_json::from_str(include_str!("../fixtures/status.json"))
+            .expect("Invalid JSON file.");
+        let outdoor = OutdoorUnitEntities::from(&res);
+
+        assert_eq!(outdoor.e_a001.p_01.get_string(), None);
+        assert_eq!(outdoor.e_a001.p_13.get_string(), Some(" ".repeat(8)));
+        assert_eq!(outdoor.e_a004.p_02.get_string(), Some("00-".into()));
+        assert_eq!(outdoor.e_a004.p_09.get_string(), None);
+        assert_eq!(
+            outdoor.e_a00a.p_03.get_f32().map(|v| (v * 100.0).round()),
+            Some(172.0)
+        );
+        assert_eq!(
+            outdoor.e_a00a.p_07.get_f32().map(|v| (v * 100.0).round()),
+            Some(182.0)
+        );
+        assert_eq!(outdoor.e_a010.p_08.get_f32(), Some(1.0));
+        assert_eq!(outdoor.e_3002.p_02.get_enum(), Some(2.0));
+        assert_eq!(outdoor.e_3002.p_65.get_f32(), Some(255.0));
+        assert_eq!(outdoor.e_3002.p_68.get_f32(), Some(-32.0));
+    }
+}
@@ -2,13 +2,19 @@
 
 #[macro_use]
 mod macros;
+pub mod change;
+pub mod entities;
 pub mod info;
+pub mod path;
 pub mod property;
 pub mod request;
 pub mod response;
 pub mod status;
 
+pub use change::{ChangeSet, ChangeSetBuilder};
+pub use entities::{IndoorUnitEntities, OutdoorUnitEntities};
 pub use info::DaikinInfo;
+pub use path::{PropertyError, PropertyPath};
 pub use property::{Binary, BinaryEnum, BinaryStep, Item, Metadata, PropValue, Property};
 pub use request::DaikinRequest;
 pub use response::DaikinResponse;
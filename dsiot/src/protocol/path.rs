@@ -0,0 +1,195 @@
+//! Runtime, path-addressed property access.
+//!
+//! Paths are written as `<resource>/<entity>/.../<property>`, for example
+//! `adr_0100.dgc_status/e_1002/e_3001/p_01` or `edge.adp_i/func/en_ipower`.
+
+use super::property::{Item, Property};
+use super::response::DaikinResponse;
+use serde::de::DeserializeOwned;
+use std::str::FromStr;
+
+/// Error type for path-addressed property access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyError {
+    /// The path string could not be parsed.
+    InvalidPath(String),
+    /// The response does not contain the requested resource.
+    ResponseNotFound(String),
+    /// The resource does not contain the requested property.
+    PropertyNotFound(String),
+    /// The path points to a tree instead of a value.
+    NotAValue(String),
+}
+
+impl std::fmt::Display for PropertyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPath(path) => write!(f, "Invalid property path: {path:?}"),
+            Self::ResponseNotFound(from) => write!(f, "No response from {from}"),
+            Self::PropertyNotFound(path) => write!(f, "Property not found: {path}"),
+            Self::NotAValue(path) => write!(f, "Property {path} is a tree, not a value"),
+        }
+    }
+}
+
+impl std::error::Error for PropertyError {}
+
+/// Parsed property path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PropertyPath {
+    /// Resource name, e.g. `adr_0100.dgc_status`.
+    pub resource: String,
+    /// Property names below the resource root, e.g. `["e_1002", "e_3001", "p_01"]`.
+    pub segments: Vec<String>,
+}
+
+impl PropertyPath {
+    /// Create a path from a resource name and property names.
+    pub fn new(resource: &str, segments: &[&str]) -> Self {
+        Self {
+            resource: resource.to_string(),
+            segments: segments.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Returns the `fr`/`to` path of the resource, e.g. `/dsiot/edge/adr_0100.dgc_status`.
+    pub fn from(&self) -> String {
+        if self.resource.starts_with("edge") {
+            format!("/dsiot/{}", self.resource)
+        } else {
+            format!("/dsiot/edge/{}", self.resource)
+        }
+    }
+}
+
+impl FromStr for PropertyPath {
+    type Err = PropertyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim_matches('/').split('/');
+        let resource = parts.next().unwrap_or_default();
+        let segments: Vec<String> = parts.map(str::to_string).collect();
+        if resource.is_empty() || segments.is_empty() || segments.iter().any(String::is_empty) {
+            return Err(PropertyError::InvalidPath(s.to_string()));
+        }
+        Ok(Self {
+            resource: resource.to_string(),
+            segments,
+        })
+    }
+}
+
+impl std::fmt::Display for PropertyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.resource, self.segments.join("/"))
+    }
+}
+
+impl DaikinResponse {
+    /// Look up the property tree or node at the given path.
+    pub fn property(&self, path: &PropertyPath) -> Result<&Property, PropertyError> {
+        let from = path.from();
+        let root = self
+            .responses
+            .iter()
+            .find(|r| r.from == from)
+            .and_then(|r| r.content.as_ref())
+            .ok_or(PropertyError::ResponseNotFound(from))?;
+        path.segments
+            .iter()
+            .try_fold(root, |p, name| p.find(name))
+            .ok_or_else(|| PropertyError::PropertyNotFound(path.to_string()))
+    }
+
+    /// Look up the value at the given path as a typed `Item`.
+    ///
+    /// ```
+    /// # use dsiot::protocol::{DaikinResponse, Item, PropertyPath};
+    /// # use dsiot::Mode;
+    /// # fn example(res: &DaikinResponse) -> Result<(), Box<dyn std::error::Error>> {
+    /// let path: PropertyPath = "adr_0100.dgc_status/e_1002/e_3001/p_01".parse()?;
+    /// let mode: Item<Mode> = res.item(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn item<T: Sized + DeserializeOwned + Into<f32>>(
+        &self,
+        path: &PropertyPath,
+    ) -> Result<Item<T>, PropertyError> {
+        match self.property(path)? {
+            Property::Node(item) => Ok(Item {
+                name: item.name.clone(),
+                type_: item.type_,
+                value: item.value.clone(),
+                metadata: item.metadata.clone(),
+                phantom: std::marker::PhantomData,
+            }),
+            Property::Tree { .. } => Err(PropertyError::NotAValue(path.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Mode;
+
+    fn fixture() -> DaikinResponse {
+        serde_json::from_str(include_str!("../fixtures/status.json")).expect("Invalid JSON file.")
+    }
+
+    #[test]
+    fn parse() {
+        let path: PropertyPath = "adr_0100.dgc_status/e_1002/e_3001/p_01".parse().unwrap();
+        assert_eq!(path.resource, "adr_0100.dgc_status");
+        assert_eq!(path.segments, vec!["e_1002", "e_3001", "p_01"]);
+        assert_eq!(path.from(), "/dsiot/edge/adr_0100.dgc_status");
+        assert_eq!(path.to_string(), "adr_0100.dgc_status/e_1002/e_3001/p_01");
+
+        let path: PropertyPath = "edge.adp_i/func/en_ipower".parse().unwrap();
+        assert_eq!(path.from(), "/dsiot/edge.adp_i");
+
+        assert!("adr_0100.dgc_status".parse::<PropertyPath>().is_err());
+        assert!("adr_0100.dgc_status//p_01".parse::<PropertyPath>().is_err());
+        assert!("".parse::<PropertyPath>().is_err());
+    }
+
+    #[test]
+    fn item() {
+        let res = fixture();
+
+        let path = "adr_0100.dgc_status/e_1002/e_3001/p_01".parse().unwrap();
+        let mode: Item<Mode> = res.item(&path).unwrap();
+        assert_eq!(mode.get_enum(), Some(Mode::Cooling));
+
+        // Properties not modeled by DaikinStatus are reachable too
+        let path = "adr_0200.dgc_status/e_1003/e_2009/p_01".parse().unwrap();
+        let temp: Item = res.item(&path).unwrap();
+        assert_eq!(temp.get_f32(), Some(19.0));
+    }
+
+    #[test]
+    fn item_errors() {
+        let res = fixture();
+
+        let path = "adr_0300.dgc_status/e_1002/p_01".parse().unwrap();
+        assert_eq!(
+            res.item::<f32>(&path),
+            Err(PropertyError::ResponseNotFound(
+                "/dsiot/edge/adr_0300.dgc_status".into()
+            ))
+        );
+
+        let path = "adr_0100.dgc_status/e_1002/e_3001/p_FF".parse().unwrap();
+        assert!(matches!(
+            res.item::<f32>(&path),
+            Err(PropertyError::PropertyNotFound(_))
+        ));
+
+        let path = "adr_0100.dgc_status/e_1002/e_3001".parse().unwrap();
+        assert!(matches!(
+            res.item::<f32>(&path),
+            Err(PropertyError::NotAValue(_))
+        ));
+    }
+}
@@ -188,6 +188,53 @@ impl<T: Sized + DeserializeOwned + Into<f32>> Item<T> {
         }
     }
 
+    /// Enum values the metadata allows, leaving out those `T` does not know.
+    ///
+    /// Empty if the item is not an enum.
+    pub fn allowed_enums(&self) -> Vec<T> {
+        match &self.metadata {
+            Metadata::Binary(Binary::Enum(e)) => e
+                .allowed_values()
+                .into_iter()
+                .filter_map(|value| serde_json::from_value(serde_json::Value::from(value)).ok())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Raw value of an enum item, including values `T` does not know.
+    pub fn get_raw_enum(&self) -> Option<u8> {
+        match self {
+            Item {
+                value: PropValue::String(pv),
+                metadata: Metadata::Binary(Binary::Enum(_)),
+                ..
+            } => Some(hex2int(pv) as u8),
+            _ => None,
+        }
+    }
+
+    /// Raw enum values the metadata allows, including those `T` does not
+    /// know.
+    ///
+    /// Empty if the item is not an enum.
+    pub fn allowed_raw_enums(&self) -> Vec<u8> {
+        match &self.metadata {
+            Metadata::Binary(Binary::Enum(e)) => e.allowed_values(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Set the raw value of an enum item, for values `T` does not know.
+    ///
+    /// Does nothing if the item is not an enum.
+    pub fn set_raw_enum(&mut self, value: u8) {
+        if let Metadata::Binary(Binary::Enum(e)) = &self.metadata {
+            let bytes = (value as i64).to_le_bytes();
+            self.value = PropValue::String(hex::encode(&bytes[..(e.max.len() / 2)]));
+        }
+    }
+
     pub fn get_string(&self) -> Option<String> {
         match self {
             Item {
@@ -243,6 +290,23 @@ pub enum Metadata {
     Undefined,
 }
 
+impl Metadata {
+    /// Returns false if `value` is an enum value or number this metadata
+    /// does not allow. Other kinds of values are not checked.
+    pub fn allows(&self, value: &PropValue) -> bool {
+        match (self, value) {
+            (Metadata::Binary(Binary::Enum(e)), PropValue::String(pv)) => {
+                e.is_allowed(hex2int(pv) as u8)
+            }
+            (Metadata::Binary(Binary::Step(step)), PropValue::String(pv)) => {
+                let unit = if step.step == 0 { 1.0 } else { step.step() };
+                step.range().contains(&(hex2int(pv) as f32 * unit))
+            }
+            _ => true,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 #[serde(untagged)]
 pub enum Binary {
@@ -426,6 +490,94 @@ mod tests {
         assert_eq!(p.get_string(), None);
     }
 
+    #[test]
+    fn allowed_enums() {
+        use crate::types::VerticalDirection;
+
+        let json = json!({
+            "pn": "p_05",
+            "pt": 3,
+            "pv": "100000",
+            "md": {
+                "pt": "b",
+                "mx": "3F808100"
+            }
+        });
+        let item: Item<VerticalDirection> =
+            serde_json::from_value(json).expect("Invalid JSON structure.");
+        assert_eq!(
+            item.allowed_enums(),
+            vec![
+                VerticalDirection::TopMost,
+                VerticalDirection::Top,
+                VerticalDirection::Center,
+                VerticalDirection::Bottom,
+                VerticalDirection::BottomMost,
+                VerticalDirection::Swing,
+                VerticalDirection::Auto,
+                VerticalDirection::Nice,
+            ]
+        );
+
+        // Bit 0 is allowed but is no VerticalDirection
+        let json = json!({
+            "pn": "p_05",
+            "pt": 3,
+            "pv": "100000",
+            "md": {
+                "pt": "b",
+                "mx": "0380"
+            }
+        });
+        let item: Item<VerticalDirection> =
+            serde_json::from_value(json).expect("Invalid JSON structure.");
+        assert_eq!(
+            item.allowed_enums(),
+            vec![VerticalDirection::TopMost, VerticalDirection::Swing]
+        );
+
+        let json = json!({
+            "pn": "p_01",
+            "pt": 3,
+            "pv": "2600",
+            "md": {
+                "pt": "b",
+                "st": 245,
+                "mi": "EEFF",
+                "mx": "4E00"
+            }
+        });
+        let item: Item = serde_json::from_value(json).expect("Invalid JSON structure.");
+        assert!(item.allowed_enums().is_empty());
+    }
+
+    #[test]
+    fn raw_enums() {
+        use crate::types::Mode;
+
+        // Mode 4 is allowed but is no Mode
+        let json = json!({
+            "pn": "p_01",
+            "pt": 3,
+            "pv": "0200",
+            "md": {
+                "pt": "b",
+                "mx": "3F00"
+            }
+        });
+        let mut item: Item<Mode> = serde_json::from_value(json).expect("Invalid JSON structure.");
+        assert_eq!(item.allowed_raw_enums(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(item.get_raw_enum(), Some(2));
+
+        item.set_raw_enum(4);
+        assert_eq!(item.value, PropValue::String("0400".into()));
+        assert_eq!(item.get_raw_enum(), Some(4));
+        assert_eq!(item.get_enum(), None);
+
+        item.set_raw_enum(5);
+        assert_eq!(item.get_enum(), Some(Mode::Dehumidify));
+    }
+
     #[test]
     fn propvalue() {
         let json = json!({
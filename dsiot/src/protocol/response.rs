@@ -1,5 +1,5 @@
 use super::property::Property;
-use serde::{Deserialize, Deserializer, de};
+use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct DaikinResponse {
@@ -12,19 +12,24 @@ pub struct Response {
     pub from: String,
     #[serde(rename = "pc")]
     pub content: Option<Property>,
-    #[serde(rename = "rsc", deserialize_with = "verify_status_code")]
+    #[serde(rename = "rsc")]
     pub status_code: u32, // response status code
 }
 
-fn verify_status_code<'de, D>(deserializer: D) -> Result<u32, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let value: u32 = Deserialize::deserialize(deserializer)?;
-    if value / 10 == 200 {
-        Ok(value)
-    } else {
-        Err(de::Error::custom(format!("Invalid status code: {value}")))
+impl Response {
+    /// Returns true if the status code is a success code (`200x`).
+    pub fn is_success(&self) -> bool {
+        self.status_code / 10 == 200
+    }
+}
+
+impl DaikinResponse {
+    /// Returns the sub-responses whose status code is not a success code.
+    ///
+    /// A multireq can partially fail, e.g. units without an outdoor power
+    /// meter reject one path and answer the others.
+    pub fn failed(&self) -> impl Iterator<Item = &Response> {
+        self.responses.iter().filter(|r| !r.is_success())
     }
 }
 
@@ -60,12 +65,23 @@ mod tests {
                     "fr": "/dsiot/edge/adr_0100.dgc_status",
                     "pt": 1,
                     "pc": {"pn": "1234","pt": 3,"pv": "ok","md": {"pt": "s"}},
+                    "rsc": 2000
+                },
+                {
+                    "fr": "/dsiot/edge/adr_0200.dgc_status",
                     "rsc": 4041
                 }
             ]
         }
         "#;
-        let result: Result<DaikinResponse, serde_json::Error> = serde_json::from_str(json_data);
-        assert!(result.is_err());
+        let response: DaikinResponse =
+            serde_json::from_str(json_data).expect("Failed to deserialize");
+        assert!(response.responses[0].is_success());
+        assert!(!response.responses[1].is_success());
+
+        let failed: Vec<_> = response.failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].from, "/dsiot/edge/adr_0200.dgc_status");
+        assert_eq!(failed[0].status_code, 4041);
     }
 }
@@ -1,8 +1,15 @@
+use super::entities::{IndoorUnitEntities, OutdoorUnitEntities};
 use super::property::{Item, Property};
 use super::request::{DaikinRequest, Request};
 use super::response::DaikinResponse;
 use crate::types::{AutoModeWindSpeed, HorizontalDirection, Mode, VerticalDirection, WindSpeed};
 
+/// Sub-response holding the indoor unit settings and sensors.
+const INDOOR_PATH: &str = "/dsiot/edge/adr_0100.dgc_status";
+
+/// Sub-response holding the outdoor unit sensors and power consumption.
+const OUTDOOR_PATH: &str = "/dsiot/edge/adr_0200.dgc_status";
+
 /// Sensor readings from the device (read-only values).
 #[derive(Clone, Debug, PartialEq)]
 pub struct SensorReadings {
@@ -77,6 +84,25 @@ pub struct DaikinStatus {
     pub wind: WindSettings,
     /// Instantaneous power consumption in watts (requires en_ipower).
     pub power_consumption: Item<f32>,
+    /// Indoor unit entities not mapped onto the fields above.
+    pub indoor_unit: IndoorUnitEntities,
+    /// Outdoor unit entities not mapped onto the fields above.
+    pub outdoor_unit: OutdoorUnitEntities,
+    /// Paths of the sub-responses that failed. Fields under these paths are
+    /// left without a value.
+    pub failed_paths: Vec<String>,
+}
+
+impl DaikinStatus {
+    /// Whether the indoor unit failed to answer.
+    pub fn indoor_failed(&self) -> bool {
+        self.failed_paths.iter().any(|p| p == INDOOR_PATH)
+    }
+
+    /// Whether the outdoor unit failed to answer.
+    pub fn outdoor_failed(&self) -> bool {
+        self.failed_paths.iter().any(|p| p == OUTDOOR_PATH)
+    }
 }
 
 impl From<DaikinResponse> for DaikinStatus {
@@ -122,6 +148,9 @@ impl From<DaikinResponse> for DaikinStatus {
                 },
             },
             power_consumption: get_prop!(response."/dsiot/edge/adr_0200.dgc_status".e_1003.e_A005.p_01),
+            indoor_unit: IndoorUnitEntities::from(&response),
+            outdoor_unit: OutdoorUnitEntities::from(&response),
+            failed_paths: response.failed().map(|r| r.from.clone()).collect(),
         }
     }
 }
@@ -216,6 +245,38 @@ mod tests {
 
         // Power consumption
         assert_eq!(status.power_consumption.get_f32(), Some(0.0));
+
+        // Unmapped entities
+        assert_eq!(status.indoor_unit.e_3003.p_0c.get_f32(), Some(24.5));
+        assert_eq!(
+            status
+                .outdoor_unit
+                .e_a00a
+                .p_03
+                .get_f32()
+                .map(|v| (v * 100.0).round()),
+            Some(172.0)
+        );
+    }
+
+    #[test]
+    fn partial_failure() {
+        let mut body: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/status.json"))
+                .expect("Invalid JSON file.");
+        body["responses"][1] = serde_json::json!({
+            "fr": "/dsiot/edge/adr_0200.dgc_status",
+            "rsc": 4004
+        });
+        let res: DaikinResponse = serde_json::from_value(body).expect("Invalid response.");
+        let status: DaikinStatus = res.into();
+
+        assert_eq!(status.failed_paths, vec!["/dsiot/edge/adr_0200.dgc_status"]);
+        assert!(status.outdoor_failed());
+        assert!(!status.indoor_failed());
+        assert_eq!(status.mode.get_enum(), Some(Mode::Cooling));
+        assert_eq!(status.sensors.outdoor_temperature.get_f32(), None);
+        assert_eq!(status.power_consumption.get_f32(), None);
     }
 
     #[test]
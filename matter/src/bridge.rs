@@ -1,25 +1,39 @@
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use dsiot::Capabilities;
 use dsiot::protocol::DaikinInfo;
 use rs_matter::dm::AttrChangeNotifier;
 use rs_matter::dm::clusters::decl::bridged_device_basic_information;
 use rs_matter::dm::clusters::decl::electrical_power_measurement;
 use rs_matter::dm::clusters::decl::fan_control as rs_fan_control;
+use rs_matter::dm::clusters::decl::mode_select;
 use rs_matter::dm::clusters::decl::relative_humidity_measurement;
+use rs_matter::dm::clusters::decl::temperature_measurement;
 use rs_matter::dm::clusters::decl::thermostat as rs_thermostat;
 use rs_matter::dm::clusters::decl::wi_fi_network_diagnostics;
 use rs_matter::dm::clusters::decl::{identify, on_off};
-use rs_matter::dm::clusters::desc::{self, ClusterHandler as _};
+use rs_matter::dm::clusters::desc::{self, ClusterHandler as _, PartsMatcher};
 use rs_matter::dm::devices::{DEV_TYPE_AGGREGATOR, DEV_TYPE_BRIDGED_NODE};
 use rs_matter::dm::subscriptions::Subscriptions;
 use rs_matter::dm::{
-    Dataver, DeviceType, Endpoint, Handler, InvokeContext, InvokeReply, Matcher, Node,
-    NonBlockingHandler, OperationContext, ReadContext, ReadReply, WriteContext,
+    Cluster, Dataver, DeviceType, Endpoint, EndptId, Handler, InvokeContext, InvokeReply, Matcher,
+    Metadata, MetadataGuard, Node, NonBlockingHandler, OperationContext, ReadContext, ReadReply,
+    WriteContext,
 };
 use rs_matter::error::{Error, ErrorCode};
 use rs_matter::{clusters, devices, root_endpoint};
 
 use crate::bridged_info::BridgedInfo;
-use crate::identify::StubIdentify;
-use crate::{device, fan_control, humidity, onoff, power, thermostat, wifi_diag};
+use crate::config::{ClusterKind, DeviceConfig};
+use crate::identify::IdentifyHandler;
+use crate::louver::{self, Axis, LouverHandler};
+use crate::outdoor::{self, OutdoorTemperatureHandler};
+use crate::presets::PresetStore;
+use crate::schedules::ScheduleStore;
+use crate::tags::{self, TaggedDescHandler};
+use crate::{device, fan_control, humidity, onoff, operating_mode, power, thermostat, wifi_diag};
 
 pub(crate) const DEV_TYPE_ROOM_AC: DeviceType = DeviceType {
     dtype: 0x0072,
@@ -34,123 +48,496 @@ const AGGREGATOR_EP: Endpoint<'static> = Endpoint {
     clusters: clusters!(desc::DescHandler::CLUSTER),
 };
 
-const BRIDGED_EP: Endpoint<'static> = Endpoint {
-    id: 0, // placeholder, overridden at runtime
-    device_types: devices!(DEV_TYPE_ROOM_AC, DEV_TYPE_BRIDGED_NODE),
-    clusters: clusters!(
-        desc::DescHandler::CLUSTER,
-        StubIdentify::CLUSTER,
-        BridgedInfo::CLUSTER,
-        onoff::OnOffHandler::CLUSTER,
-        thermostat::ThermostatHandler::CLUSTER,
-        fan_control::FanControlHandler::CLUSTER,
-        humidity::HumidityHandler::CLUSTER,
-        wifi_diag::WifiDiagHandler::CLUSTER
-    ),
-};
+const BRIDGED_DEVICE_TYPES: &[DeviceType] = devices!(DEV_TYPE_ROOM_AC, DEV_TYPE_BRIDGED_NODE);
 
-const BRIDGED_EP_POWER: Endpoint<'static> = Endpoint {
-    id: 0, // placeholder, overridden at runtime
-    device_types: devices!(DEV_TYPE_ROOM_AC, DEV_TYPE_BRIDGED_NODE),
-    clusters: clusters!(
-        desc::DescHandler::CLUSTER,
-        StubIdentify::CLUSTER,
-        BridgedInfo::CLUSTER,
-        onoff::OnOffHandler::CLUSTER,
-        thermostat::ThermostatHandler::CLUSTER,
-        fan_control::FanControlHandler::CLUSTER,
-        humidity::HumidityHandler::CLUSTER,
-        power::PowerHandler::CLUSTER,
-        wifi_diag::WifiDiagHandler::CLUSTER
-    ),
-};
+const MODE_SELECT_DEVICE_TYPES: &[DeviceType] = devices!(louver::DEV_TYPE_MODE_SELECT);
 
-pub(crate) fn build_node(devices: &[(u16, bool)]) -> Node<'static> {
-    let mut endpoints = vec![ROOT_EP, AGGREGATOR_EP];
-    for &(id, has_power) in devices {
-        let template = if has_power {
-            BRIDGED_EP_POWER
-        } else {
-            BRIDGED_EP
+const OUTDOOR_DEVICE_TYPES: &[DeviceType] =
+    devices!(outdoor::DEV_TYPE_TEMP_SENSOR, DEV_TYPE_BRIDGED_NODE);
+
+/// Endpoint as it is added to the node.
+pub(crate) struct EndpointDef {
+    pub(crate) id: u16,
+    pub(crate) device_types: &'static [DeviceType],
+    pub(crate) clusters: Vec<Cluster<'static>>,
+}
+
+/// Node whose bridged endpoints can be added and removed while the Matter
+/// stack is running.
+pub(crate) struct DynamicNode {
+    endpoints: RefCell<Vec<Endpoint<'static>>>,
+    /// Cluster lists by their cluster IDs and feature maps, leaked once per
+    /// distinct list so that a device coming and going does not leak each
+    /// time.
+    cluster_lists: RefCell<HashMap<Vec<(u32, u32)>, &'static [Cluster<'static>]>>,
+    /// Descriptor parts lists by their endpoint IDs, leaked the same way.
+    parts_lists: RefCell<HashMap<Vec<u16>, &'static ChildParts>>,
+}
+
+impl DynamicNode {
+    pub(crate) fn new() -> Self {
+        Self {
+            endpoints: RefCell::new(vec![ROOT_EP, AGGREGATOR_EP]),
+            cluster_lists: RefCell::new(HashMap::new()),
+            parts_lists: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Add the endpoints of a bridged device, replacing those with the same
+    /// IDs.
+    ///
+    /// Fails if the node is being read at the moment; try again later.
+    pub(crate) fn add(&self, defs: &[EndpointDef]) -> Result<(), Error> {
+        let mut endpoints = self
+            .endpoints
+            .try_borrow_mut()
+            .map_err(|_| Error::from(ErrorCode::Busy))?;
+        for def in defs {
+            let ids = def
+                .clusters
+                .iter()
+                .map(|c| (c.id, c.feature_map))
+                .collect::<Vec<_>>();
+            let clusters = *self
+                .cluster_lists
+                .borrow_mut()
+                .entry(ids)
+                .or_insert_with(|| Box::leak(def.clusters.clone().into_boxed_slice()));
+            endpoints.retain(|ep| ep.id != def.id);
+            endpoints.push(Endpoint {
+                id: def.id,
+                device_types: def.device_types,
+                clusters,
+            });
+        }
+        endpoints.sort_by_key(|ep| ep.id);
+        Ok(())
+    }
+
+    /// Remove the endpoints of a bridged device.
+    ///
+    /// Fails if the node is being read at the moment; try again later.
+    pub(crate) fn remove(&self, ids: &[u16]) -> Result<(), Error> {
+        let mut endpoints = self
+            .endpoints
+            .try_borrow_mut()
+            .map_err(|_| Error::from(ErrorCode::Busy))?;
+        endpoints.retain(|ep| !ids.contains(&ep.id));
+        Ok(())
+    }
+
+    /// Parts list for the descriptor of an endpoint whose children are `ids`.
+    fn child_parts(&self, ids: Vec<u16>) -> &'static ChildParts {
+        *self
+            .parts_lists
+            .borrow_mut()
+            .entry(ids)
+            .or_insert_with_key(|ids| Box::leak(Box::new(ChildParts(ids.clone()))))
+    }
+}
+
+/// Lists the child endpoints of a bridged device in its PartsList.
+struct ChildParts(Vec<u16>);
+
+impl PartsMatcher for ChildParts {
+    fn describe(&self, _our_endpoint: EndptId, endpoint: EndptId) -> bool {
+        self.0.contains(&endpoint)
+    }
+}
+
+pub(crate) struct DynamicNodeGuard<'a>(Ref<'a, Vec<Endpoint<'static>>>);
+
+impl MetadataGuard for DynamicNodeGuard<'_> {
+    fn node(&self) -> Node<'_> {
+        Node { endpoints: &self.0 }
+    }
+}
+
+impl Metadata for DynamicNode {
+    type MetadataGuard<'a>
+        = DynamicNodeGuard<'a>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::MetadataGuard<'_> {
+        DynamicNodeGuard(self.endpoints.borrow())
+    }
+}
+
+/// Settings kept in the data directory and shared by every bridged device.
+#[derive(Clone)]
+pub(crate) struct Stores {
+    pub(crate) presets: Arc<PresetStore>,
+    pub(crate) schedules: Arc<ScheduleStore>,
+}
+
+/// Child endpoint of a bridged device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PartKind {
+    VerticalLouver,
+    HorizontalLouver,
+    OutdoorTemperature,
+}
+
+impl PartKind {
+    /// Parts of a device with `config` and `caps`.
+    pub(crate) fn for_device(caps: &Capabilities, config: &DeviceConfig) -> Vec<Self> {
+        let mut kinds = Vec::new();
+        if config.has_cluster(ClusterKind::Louvers) {
+            if !caps.vertical_directions.is_empty() {
+                kinds.push(PartKind::VerticalLouver);
+            }
+            if !caps.horizontal_directions.is_empty() {
+                kinds.push(PartKind::HorizontalLouver);
+            }
+        }
+        if config.has_cluster(ClusterKind::OutdoorTemperature) && caps.outdoor_temperature {
+            kinds.push(PartKind::OutdoorTemperature);
+        }
+        kinds
+    }
+
+    /// Name of the part in the endpoint map.
+    pub(crate) fn role(self) -> &'static str {
+        match self {
+            PartKind::VerticalLouver => "vertical_louver",
+            PartKind::HorizontalLouver => "horizontal_louver",
+            PartKind::OutdoorTemperature => "outdoor_temperature",
+        }
+    }
+}
+
+enum PartHandler {
+    Louver(LouverHandler),
+    /// The outdoor unit, reachable on its own.
+    Outdoor {
+        info: BridgedInfo,
+        temperature: OutdoorTemperatureHandler,
+    },
+}
+
+pub(crate) struct PartEndpoint {
+    pub(crate) ep_id: u16,
+    pub(crate) kind: PartKind,
+    desc: desc::HandlerAdaptor<TaggedDescHandler>,
+    handler: PartHandler,
+}
+
+impl PartEndpoint {
+    fn new(
+        kind: PartKind,
+        ep_id: u16,
+        rand: &mut impl rand::RngCore,
+        device: &device::Device,
+        info: &BridgedInfo,
+    ) -> Self {
+        let handler = match kind {
+            PartKind::VerticalLouver => PartHandler::Louver(LouverHandler::new(
+                Dataver::new_rand(rand),
+                device.clone(),
+                Axis::Vertical,
+            )),
+            PartKind::HorizontalLouver => PartHandler::Louver(LouverHandler::new(
+                Dataver::new_rand(rand),
+                device.clone(),
+                Axis::Horizontal,
+            )),
+            PartKind::OutdoorTemperature => PartHandler::Outdoor {
+                info: info.outdoor_unit(Dataver::new_rand(rand)),
+                temperature: OutdoorTemperatureHandler::new(
+                    Dataver::new_rand(rand),
+                    device.clone(),
+                ),
+            },
+        };
+        let tag_list: &'static [tags::SemanticTag] = match kind {
+            PartKind::VerticalLouver => &[tags::VERTICAL_LOUVER],
+            PartKind::HorizontalLouver => &[tags::HORIZONTAL_LOUVER],
+            PartKind::OutdoorTemperature => &[tags::OUTDOOR],
+        };
+        let desc = desc::DescHandler::new(Dataver::new_rand(rand));
+        Self {
+            ep_id,
+            kind,
+            desc: TaggedDescHandler::new(desc, tag_list).adapt(),
+            handler,
+        }
+    }
+
+    /// The cluster of the part that follows the device status.
+    pub(crate) fn cluster(&self) -> Cluster<'static> {
+        match self.handler {
+            PartHandler::Louver(_) => LouverHandler::CLUSTER,
+            PartHandler::Outdoor { .. } => OutdoorTemperatureHandler::CLUSTER,
+        }
+    }
+
+    pub(crate) fn dataver(&self) -> &Dataver {
+        match &self.handler {
+            PartHandler::Louver(l) => &l.dataver,
+            PartHandler::Outdoor { temperature, .. } => &temperature.dataver,
+        }
+    }
+
+    /// Bridged device information of a part that is reachable on its own.
+    pub(crate) fn bridged_info(&self) -> Option<&BridgedInfo> {
+        match &self.handler {
+            PartHandler::Louver(_) => None,
+            PartHandler::Outdoor { info, .. } => Some(info),
+        }
+    }
+
+    fn endpoint(&self) -> EndpointDef {
+        let (device_types, clusters) = match self.handler {
+            PartHandler::Louver(_) => (MODE_SELECT_DEVICE_TYPES, vec![LouverHandler::CLUSTER]),
+            PartHandler::Outdoor { .. } => (
+                OUTDOOR_DEVICE_TYPES,
+                vec![BridgedInfo::CLUSTER, OutdoorTemperatureHandler::CLUSTER],
+            ),
         };
-        endpoints.push(Endpoint { id, ..template });
+        EndpointDef {
+            id: self.ep_id,
+            device_types,
+            clusters: [vec![self.desc.0.cluster()], clusters].concat(),
+        }
     }
-    Node {
-        endpoints: Box::leak(endpoints.into_boxed_slice()),
+
+    fn read(&self, ctx: impl ReadContext, reply: impl ReadReply) -> Result<(), Error> {
+        let cl = ctx.cluster();
+        if cl == desc::DescHandler::CLUSTER.id {
+            return self.desc.read(ctx, reply);
+        }
+        match &self.handler {
+            PartHandler::Louver(l) if cl == LouverHandler::CLUSTER.id => {
+                mode_select::HandlerAdaptor(l).read(ctx, reply)
+            }
+            PartHandler::Outdoor { info, .. } if cl == BridgedInfo::CLUSTER.id => {
+                bridged_device_basic_information::HandlerAdaptor(info).read(ctx, reply)
+            }
+            PartHandler::Outdoor { temperature, .. }
+                if cl == OutdoorTemperatureHandler::CLUSTER.id =>
+            {
+                temperature_measurement::HandlerAdaptor(temperature).read(ctx, reply)
+            }
+            _ => Err(ErrorCode::ClusterNotFound.into()),
+        }
+    }
+
+    fn write(&self, ctx: impl WriteContext) -> Result<(), Error> {
+        let cl = ctx.cluster();
+        match &self.handler {
+            PartHandler::Outdoor { info, .. } if cl == BridgedInfo::CLUSTER.id => {
+                bridged_device_basic_information::HandlerAdaptor(info).write(ctx)
+            }
+            _ => Err(ErrorCode::AttributeNotFound.into()),
+        }
+    }
+
+    fn invoke(&self, ctx: impl InvokeContext, reply: impl InvokeReply) -> Result<(), Error> {
+        let cl = ctx.cluster();
+        match &self.handler {
+            PartHandler::Louver(l) if cl == LouverHandler::CLUSTER.id => {
+                mode_select::HandlerAdaptor(l).invoke(ctx, reply)
+            }
+            _ => Err(ErrorCode::CommandNotFound.into()),
+        }
     }
 }
 
 pub(crate) struct BridgedDevice {
     pub(crate) ep_id: u16,
+    pub(crate) mac: String,
     desc: desc::HandlerAdaptor<desc::DescHandler<'static>>,
-    identify: StubIdentify,
+    pub(crate) identify: IdentifyHandler,
     pub(crate) bridged_info: BridgedInfo,
     pub(crate) on_off: onoff::OnOffHandler,
     pub(crate) therm: thermostat::ThermostatHandler,
-    pub(crate) fan_ctl: fan_control::FanControlHandler,
-    pub(crate) humidity: humidity::HumidityHandler,
+    pub(crate) fan_ctl: Option<fan_control::FanControlHandler>,
+    pub(crate) humidity: Option<humidity::HumidityHandler>,
     pub(crate) power: Option<power::PowerHandler>,
-    pub(crate) wifi_diag: wifi_diag::WifiDiagHandler,
+    pub(crate) wifi_diag: Option<wifi_diag::WifiDiagHandler>,
+    pub(crate) operating_mode: Option<operating_mode::OperatingModeHandler>,
+    pub(crate) parts: Vec<PartEndpoint>,
     pub(crate) device: device::Device,
+    /// What the device supports, which decides the features of its clusters.
+    caps: Capabilities,
 }
 
 impl BridgedDevice {
     pub(crate) fn new(
         ep_id: u16,
         rand: &mut impl rand::RngCore,
-        bridged_info: BridgedInfo,
         device: device::Device,
         info: DaikinInfo,
+        caps: Capabilities,
+        config: &DeviceConfig,
+        stores: &Stores,
     ) -> Self {
-        let power = if info.en_ipower {
-            Some(power::PowerHandler::new(
-                Dataver::new_rand(rand),
-                device.clone(),
-            ))
-        } else {
-            None
-        };
-        let wifi_diag =
-            wifi_diag::WifiDiagHandler::new(Dataver::new_rand(rand), info, device.clone());
+        let device_mac = info.mac.clone();
+        let bridged_info = BridgedInfo::new(Dataver::new_rand(rand), &info, device.clone());
+        // Clusters are left out for what the device does not have
+        let fan_ctl = (config.has_cluster(ClusterKind::FanControl) && !caps.wind_speeds.is_empty())
+            .then(|| fan_control::FanControlHandler::new(Dataver::new_rand(rand), device.clone()));
+        let humidity = (config.has_cluster(ClusterKind::Humidity) && caps.humidity)
+            .then(|| humidity::HumidityHandler::new(Dataver::new_rand(rand), device.clone()));
+        let power =
+            (info.en_ipower && caps.power_consumption && config.has_cluster(ClusterKind::Power))
+                .then(|| power::PowerHandler::new(Dataver::new_rand(rand), device.clone()));
+        let wifi_diag = config.has_cluster(ClusterKind::WifiDiagnostics).then(|| {
+            wifi_diag::WifiDiagHandler::new(Dataver::new_rand(rand), info, device.clone())
+        });
+        let operating_mode = config.has_cluster(ClusterKind::OperatingMode).then(|| {
+            operating_mode::OperatingModeHandler::new(Dataver::new_rand(rand), device.clone())
+        });
+        let therm = thermostat::ThermostatHandler::new(
+            Dataver::new_rand(rand),
+            device.clone(),
+            config.cooling_setpoint,
+            config.heating_setpoint,
+            stores.presets.clone(),
+            stores.schedules.clone(),
+            device_mac.clone(),
+        );
         Self {
             ep_id,
+            mac: device_mac,
             desc: desc::DescHandler::new(Dataver::new_rand(rand)).adapt(),
-            identify: StubIdentify::new(Dataver::new_rand(rand)),
+            identify: IdentifyHandler::new(Dataver::new_rand(rand), device.clone()),
             bridged_info,
             on_off: onoff::OnOffHandler::new(Dataver::new_rand(rand), device.clone()),
-            therm: thermostat::ThermostatHandler::new(Dataver::new_rand(rand), device.clone()),
-            fan_ctl: fan_control::FanControlHandler::new(Dataver::new_rand(rand), device.clone()),
-            humidity: humidity::HumidityHandler::new(Dataver::new_rand(rand), device.clone()),
+            therm,
+            fan_ctl,
+            humidity,
             power,
             wifi_diag,
+            operating_mode,
+            parts: Vec::new(),
             device,
+            caps,
         }
     }
+
+    /// Give the device child endpoints, listed in the PartsList of its own.
+    pub(crate) fn with_parts(
+        mut self,
+        parts: &[(PartKind, u16)],
+        rand: &mut impl rand::RngCore,
+        node: &DynamicNode,
+    ) -> Self {
+        self.parts = parts
+            .iter()
+            .map(|&(kind, ep_id)| {
+                PartEndpoint::new(kind, ep_id, rand, &self.device, &self.bridged_info)
+            })
+            .collect();
+        let ids = parts.iter().map(|&(_, ep_id)| ep_id).collect();
+        self.desc =
+            desc::DescHandler::new_matching(Dataver::new_rand(rand), node.child_parts(ids)).adapt();
+        self
+    }
+
+    /// IDs of the endpoint and its parts.
+    pub(crate) fn ep_ids(&self) -> Vec<u16> {
+        let mut ids = vec![self.ep_id];
+        ids.extend(self.parts.iter().map(|p| p.ep_id));
+        ids
+    }
+
+    /// The endpoint and its parts, as they are added to the node.
+    pub(crate) fn endpoints(&self) -> Vec<EndpointDef> {
+        let mut defs = vec![EndpointDef {
+            id: self.ep_id,
+            device_types: BRIDGED_DEVICE_TYPES,
+            clusters: self.clusters(),
+        }];
+        defs.extend(self.parts.iter().map(PartEndpoint::endpoint));
+        defs
+    }
+
+    fn part(&self, ep_id: u16) -> Option<&PartEndpoint> {
+        self.parts.iter().find(|p| p.ep_id == ep_id)
+    }
+
+    /// Clusters of the endpoint, in the order they are listed to controllers.
+    fn clusters(&self) -> Vec<Cluster<'static>> {
+        let mut clusters = vec![
+            desc::DescHandler::CLUSTER,
+            IdentifyHandler::CLUSTER,
+            BridgedInfo::CLUSTER,
+            onoff::OnOffHandler::CLUSTER,
+            thermostat::ThermostatHandler::cluster(&self.caps),
+        ];
+        if self.fan_ctl.is_some() {
+            clusters.push(fan_control::FanControlHandler::cluster(&self.caps));
+        }
+        if self.humidity.is_some() {
+            clusters.push(humidity::HumidityHandler::CLUSTER);
+        }
+        if self.power.is_some() {
+            clusters.push(power::PowerHandler::CLUSTER);
+        }
+        if self.wifi_diag.is_some() {
+            clusters.push(wifi_diag::WifiDiagHandler::CLUSTER);
+        }
+        if self.operating_mode.is_some() {
+            clusters.push(operating_mode::OperatingModeHandler::CLUSTER);
+        }
+        clusters
+    }
 }
 
 pub(crate) struct BridgeHandler {
-    pub(crate) devices: Vec<BridgedDevice>,
+    pub(crate) devices: RefCell<Vec<BridgedDevice>>,
     pub(crate) subscriptions: &'static Subscriptions,
 }
 
 impl BridgeHandler {
-    fn find(&self, ep_id: u16) -> Option<&BridgedDevice> {
-        self.devices.iter().find(|d| d.ep_id == ep_id)
+    fn find(&self, ep_id: u16) -> Option<Ref<'_, BridgedDevice>> {
+        Ref::filter_map(self.devices.borrow(), |devices| {
+            devices
+                .iter()
+                .find(|d| d.ep_id == ep_id || d.part(ep_id).is_some())
+        })
+        .ok()
     }
 
     fn notify_all_clusters(&self, ep: u16) {
+        let Some(dev) = self.find(ep) else {
+            return;
+        };
+        for part in &dev.parts {
+            self.subscriptions
+                .notify_attr_changed(part.ep_id, part.cluster().id, 0);
+        }
+        let ep = dev.ep_id;
+        self.subscriptions
+            .notify_attr_changed(ep, IdentifyHandler::CLUSTER.id, 0);
         self.subscriptions
             .notify_attr_changed(ep, onoff::OnOffHandler::CLUSTER.id, 0);
         self.subscriptions
             .notify_attr_changed(ep, thermostat::ThermostatHandler::CLUSTER.id, 0);
-        self.subscriptions
-            .notify_attr_changed(ep, fan_control::FanControlHandler::CLUSTER.id, 0);
-        self.subscriptions
-            .notify_attr_changed(ep, humidity::HumidityHandler::CLUSTER.id, 0);
-        if self.find(ep).is_some_and(|d| d.power.is_some()) {
+        if dev.fan_ctl.is_some() {
+            self.subscriptions.notify_attr_changed(
+                ep,
+                fan_control::FanControlHandler::CLUSTER.id,
+                0,
+            );
+        }
+        if dev.humidity.is_some() {
+            self.subscriptions
+                .notify_attr_changed(ep, humidity::HumidityHandler::CLUSTER.id, 0);
+        }
+        if dev.power.is_some() {
             self.subscriptions
                 .notify_attr_changed(ep, power::PowerHandler::CLUSTER.id, 0);
         }
+        if dev.operating_mode.is_some() {
+            self.subscriptions.notify_attr_changed(
+                ep,
+                operating_mode::OperatingModeHandler::CLUSTER.id,
+                0,
+            );
+        }
     }
 }
 
@@ -170,10 +557,13 @@ impl Handler for BridgeHandler {
         let dev = self
             .find(ep)
             .ok_or(Error::from(ErrorCode::EndpointNotFound))?;
+        if let Some(part) = dev.part(ep) {
+            return part.read(ctx, reply);
+        }
 
         if cl == desc::DescHandler::CLUSTER.id {
             dev.desc.read(ctx, reply)
-        } else if cl == StubIdentify::CLUSTER.id {
+        } else if cl == IdentifyHandler::CLUSTER.id {
             identify::HandlerAdaptor(&dev.identify).read(ctx, reply)
         } else if cl == BridgedInfo::CLUSTER.id {
             bridged_device_basic_information::HandlerAdaptor(&dev.bridged_info).read(ctx, reply)
@@ -182,16 +572,30 @@ impl Handler for BridgeHandler {
         } else if cl == thermostat::ThermostatHandler::CLUSTER.id {
             rs_thermostat::HandlerAdaptor(&dev.therm).read(ctx, reply)
         } else if cl == fan_control::FanControlHandler::CLUSTER.id {
-            rs_fan_control::HandlerAdaptor(&dev.fan_ctl).read(ctx, reply)
+            match &dev.fan_ctl {
+                Some(f) => rs_fan_control::HandlerAdaptor(f).read(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
         } else if cl == humidity::HumidityHandler::CLUSTER.id {
-            relative_humidity_measurement::HandlerAdaptor(&dev.humidity).read(ctx, reply)
+            match &dev.humidity {
+                Some(h) => relative_humidity_measurement::HandlerAdaptor(h).read(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
         } else if cl == power::PowerHandler::CLUSTER.id {
             match &dev.power {
                 Some(p) => electrical_power_measurement::HandlerAdaptor(p).read(ctx, reply),
                 None => Err(ErrorCode::ClusterNotFound.into()),
             }
         } else if cl == wifi_diag::WifiDiagHandler::CLUSTER.id {
-            wi_fi_network_diagnostics::HandlerAdaptor(&dev.wifi_diag).read(ctx, reply)
+            match &dev.wifi_diag {
+                Some(w) => wi_fi_network_diagnostics::HandlerAdaptor(w).read(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
+        } else if cl == operating_mode::OperatingModeHandler::CLUSTER.id {
+            match &dev.operating_mode {
+                Some(m) => mode_select::HandlerAdaptor(m).read(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
         } else {
             Err(ErrorCode::ClusterNotFound.into())
         }
@@ -204,16 +608,21 @@ impl Handler for BridgeHandler {
             .find(ep)
             .ok_or(Error::from(ErrorCode::EndpointNotFound))?;
 
-        let result = if cl == BridgedInfo::CLUSTER.id {
+        let result = if let Some(part) = dev.part(ep) {
+            part.write(ctx)
+        } else if cl == BridgedInfo::CLUSTER.id {
             bridged_device_basic_information::HandlerAdaptor(&dev.bridged_info).write(ctx)
-        } else if cl == StubIdentify::CLUSTER.id {
+        } else if cl == IdentifyHandler::CLUSTER.id {
             identify::HandlerAdaptor(&dev.identify).write(ctx)
         } else if cl == onoff::OnOffHandler::CLUSTER.id {
             on_off::HandlerAdaptor(&dev.on_off).write(ctx)
         } else if cl == thermostat::ThermostatHandler::CLUSTER.id {
             rs_thermostat::HandlerAdaptor(&dev.therm).write(ctx)
         } else if cl == fan_control::FanControlHandler::CLUSTER.id {
-            rs_fan_control::HandlerAdaptor(&dev.fan_ctl).write(ctx)
+            match &dev.fan_ctl {
+                Some(f) => rs_fan_control::HandlerAdaptor(f).write(ctx),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
         } else {
             Err(ErrorCode::AttributeNotFound.into())
         };
@@ -230,16 +639,29 @@ impl Handler for BridgeHandler {
             .find(ep)
             .ok_or(Error::from(ErrorCode::EndpointNotFound))?;
 
-        let result = if cl == StubIdentify::CLUSTER.id {
+        let result = if let Some(part) = dev.part(ep) {
+            part.invoke(ctx, reply)
+        } else if cl == IdentifyHandler::CLUSTER.id {
             identify::HandlerAdaptor(&dev.identify).invoke(ctx, reply)
         } else if cl == onoff::OnOffHandler::CLUSTER.id {
             on_off::HandlerAdaptor(&dev.on_off).invoke(ctx, reply)
         } else if cl == thermostat::ThermostatHandler::CLUSTER.id {
             rs_thermostat::HandlerAdaptor(&dev.therm).invoke(ctx, reply)
         } else if cl == fan_control::FanControlHandler::CLUSTER.id {
-            rs_fan_control::HandlerAdaptor(&dev.fan_ctl).invoke(ctx, reply)
+            match &dev.fan_ctl {
+                Some(f) => rs_fan_control::HandlerAdaptor(f).invoke(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
         } else if cl == wifi_diag::WifiDiagHandler::CLUSTER.id {
-            wi_fi_network_diagnostics::HandlerAdaptor(&dev.wifi_diag).invoke(ctx, reply)
+            match &dev.wifi_diag {
+                Some(w) => wi_fi_network_diagnostics::HandlerAdaptor(w).invoke(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
+        } else if cl == operating_mode::OperatingModeHandler::CLUSTER.id {
+            match &dev.operating_mode {
+                Some(m) => mode_select::HandlerAdaptor(m).invoke(ctx, reply),
+                None => Err(ErrorCode::ClusterNotFound.into()),
+            }
         } else {
             Err(ErrorCode::CommandNotFound.into())
         };
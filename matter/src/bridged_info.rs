@@ -9,10 +9,14 @@ use crate::device::Device;
 
 pub(crate) struct BridgedInfo {
     pub(crate) dataver: Dataver,
-    device_name: &'static str,
-    unique_id: &'static str,
-    firmware_version: &'static str,
+    device_name: String,
+    product_name: &'static str,
+    unique_id: String,
+    firmware_version: String,
     device: Device,
+    /// Whether this describes the outdoor unit, which is reachable only
+    /// while it answers along with the indoor unit.
+    outdoor_unit: bool,
 }
 
 impl BridgedInfo {
@@ -36,12 +40,32 @@ impl BridgedInfo {
     pub(crate) fn new(dataver: Dataver, info: &DaikinInfo, device: Device) -> Self {
         Self {
             dataver,
-            device_name: Box::leak(info.name.clone().into_boxed_str()),
-            unique_id: Box::leak(info.mac.clone().into_boxed_str()),
-            firmware_version: Box::leak(info.version.clone().into_boxed_str()),
+            device_name: info.name.clone(),
+            product_name: "Air Conditioner",
+            unique_id: info.mac.clone(),
+            firmware_version: info.version.clone(),
             device,
+            outdoor_unit: false,
         }
     }
+
+    /// Information for a child endpoint of the outdoor unit of this device.
+    pub(crate) fn outdoor_unit(&self, dataver: Dataver) -> Self {
+        Self {
+            dataver,
+            device_name: format!("{} outdoor", self.device_name),
+            product_name: "Outdoor Unit",
+            unique_id: format!("{}-outdoor", self.unique_id),
+            firmware_version: self.firmware_version.clone(),
+            device: self.device.clone(),
+            outdoor_unit: true,
+        }
+    }
+
+    pub(crate) fn is_reachable(&self) -> bool {
+        self.device.is_reachable()
+            && !(self.outdoor_unit && self.device.get_status().outdoor_failed())
+    }
 }
 
 impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
@@ -59,7 +83,7 @@ impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
         _ctx: impl ReadContext,
         builder: Utf8StrBuilder<P>,
     ) -> Result<P, Error> {
-        builder.set(self.device_name)
+        builder.set(&self.device_name)
     }
 
     fn vendor_name<P: TLVBuilderParent>(
@@ -75,7 +99,7 @@ impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
         _ctx: impl ReadContext,
         builder: Utf8StrBuilder<P>,
     ) -> Result<P, Error> {
-        builder.set("Air Conditioner")
+        builder.set(self.product_name)
     }
 
     fn serial_number<P: TLVBuilderParent>(
@@ -83,7 +107,7 @@ impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
         _ctx: impl ReadContext,
         builder: Utf8StrBuilder<P>,
     ) -> Result<P, Error> {
-        builder.set(self.unique_id)
+        builder.set(&self.unique_id)
     }
 
     fn software_version(&self, _ctx: impl ReadContext) -> Result<u32, Error> {
@@ -95,7 +119,7 @@ impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
         _ctx: impl ReadContext,
         builder: Utf8StrBuilder<P>,
     ) -> Result<P, Error> {
-        builder.set(self.firmware_version)
+        builder.set(&self.firmware_version)
     }
 
     fn product_url<P: TLVBuilderParent>(
@@ -107,7 +131,7 @@ impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
     }
 
     fn reachable(&self, _ctx: impl ReadContext) -> Result<bool, Error> {
-        Ok(self.device.is_reachable())
+        Ok(self.is_reachable())
     }
 
     fn unique_id<P: TLVBuilderParent>(
@@ -115,7 +139,7 @@ impl bridged_device_basic_information::ClusterHandler for BridgedInfo {
         _ctx: impl ReadContext,
         builder: Utf8StrBuilder<P>,
     ) -> Result<P, Error> {
-        builder.set(self.unique_id)
+        builder.set(&self.unique_id)
     }
 
     fn handle_keep_active(
@@ -0,0 +1,458 @@
+//! TOML configuration file for the bridge.
+
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use dsiot::ValueConstraints;
+use serde::Deserialize;
+
+/// Error loading the configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(PathBuf, std::io::Error),
+    /// The file is not valid TOML or has unknown or mistyped keys.
+    Parse(PathBuf, toml::de::Error),
+    /// The file parsed but a value is not acceptable.
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "Failed to read {}: {e}", path.display()),
+            Self::Parse(path, e) => write!(f, "Invalid config {}: {e}", path.display()),
+            Self::Invalid(msg) => write!(f, "Invalid config: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::Parse(_, e) => Some(e),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+/// Top-level configuration.
+///
+/// ```toml
+/// data_dir = "/var/lib/daikin-matter"
+///
+/// [discovery]
+/// timeout = 5000
+/// interval = 300
+/// remove_after = 86400
+///
+/// [poll]
+/// interval = 30
+///
+/// [[device]]
+/// ip = "192.168.1.20"
+/// name = "Living room"
+/// endpoint = 10
+///
+/// [[device]]
+/// mac = "00:00:5E:00:53:42"
+/// clusters = ["fan_control", "humidity"]
+/// cooling_setpoint = { min = 24.0 }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Directory to store persistent data.
+    pub data_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub poll: PollSettings,
+    /// Devices to bridge. Empty means every discovered device.
+    #[serde(default, rename = "device")]
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// Discovery settings.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// Discovery timeout in milliseconds.
+    pub timeout: Option<u64>,
+    /// Expected number of devices to discover.
+    pub count: Option<usize>,
+    /// Seconds between discoveries of devices that appear after startup.
+    /// 0 disables them.
+    pub interval: Option<u64>,
+    /// Seconds a device may be unreachable before its endpoint is removed.
+    /// Unset keeps unreachable devices.
+    pub remove_after: Option<u64>,
+}
+
+/// Poll intervals in seconds for all devices.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PollSettings {
+    pub interval: Option<u64>,
+    pub fast_interval: Option<u64>,
+    /// How long to poll at `fast_interval` after a change. 0 disables it.
+    pub fast_window: Option<u64>,
+    /// Upper bound of the interval while a device is unreachable.
+    pub max_backoff: Option<u64>,
+}
+
+/// Optional clusters of a bridged endpoint. `OnOff` and `Thermostat` are
+/// always present as the Room Air Conditioner device type requires them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterKind {
+    FanControl,
+    Humidity,
+    Power,
+    WifiDiagnostics,
+    /// Mode Select endpoints for the louver position of each axis.
+    Louvers,
+    /// Mode Select cluster listing every operating mode of the device.
+    OperatingMode,
+    /// Temperature Sensor endpoint for the outdoor temperature.
+    OutdoorTemperature,
+}
+
+/// Settings for a single device, identified by IP address or MAC address.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub ip: Option<Ipv4Addr>,
+    pub mac: Option<String>,
+    /// Name shown in the controller instead of the one set on the device.
+    pub name: Option<String>,
+    /// Fixed Matter endpoint ID instead of the one stored in the data directory.
+    pub endpoint: Option<u16>,
+    /// Optional clusters to expose. Defaults to all the device supports.
+    pub clusters: Option<Vec<ClusterKind>>,
+    /// Poll interval in seconds for this device.
+    pub poll_interval: Option<u64>,
+    #[serde(default)]
+    pub cooling_setpoint: SetpointLimits,
+    #[serde(default)]
+    pub heating_setpoint: SetpointLimits,
+}
+
+/// Narrower setpoint range than the device allows, in °C.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetpointLimits {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl SetpointLimits {
+    /// Narrow the device constraints to these limits.
+    pub fn apply(&self, c: ValueConstraints) -> ValueConstraints {
+        let min = self.min.map_or(c.min, |min| min.clamp(c.min, c.max));
+        let max = self.max.map_or(c.max, |max| max.clamp(min, c.max));
+        ValueConstraints::new(min, max, c.step)
+    }
+}
+
+impl DeviceConfig {
+    /// Settings for a device given by IP address only.
+    pub fn with_ip(ip: Ipv4Addr) -> Self {
+        Self {
+            ip: Some(ip),
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if `mac`, as reported by the device, is this device's.
+    pub fn matches_mac(&self, mac: &str) -> bool {
+        self.mac
+            .as_deref()
+            .is_some_and(|m| normalize_mac(m) == normalize_mac(mac))
+    }
+
+    /// Returns true if the optional `cluster` should be exposed.
+    pub fn has_cluster(&self, cluster: ClusterKind) -> bool {
+        self.clusters.as_ref().is_none_or(|c| c.contains(&cluster))
+    }
+}
+
+/// Uppercase hex digits without separators, as the adapter reports it.
+fn normalize_mac(mac: &str) -> String {
+    mac.chars()
+        .filter(|c| !matches!(c, ':' | '-'))
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+impl Config {
+    /// Read and validate the file at `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        let config: Config =
+            toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Settings of the device at `ip`, if listed.
+    pub fn device_by_ip(&self, ip: Ipv4Addr) -> Option<&DeviceConfig> {
+        self.devices.iter().find(|d| d.ip == Some(ip))
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: String| Err(ConfigError::Invalid(msg));
+        if [
+            self.poll.interval,
+            self.poll.fast_interval,
+            self.poll.max_backoff,
+        ]
+        .contains(&Some(0))
+        {
+            return invalid("poll intervals must be at least 1 second".into());
+        }
+        if self.discovery.remove_after == Some(0) {
+            return invalid("remove_after must be at least 1 second".into());
+        }
+
+        let mut ips = HashSet::new();
+        let mut macs = HashSet::new();
+        let mut endpoints = HashSet::new();
+        for (i, dev) in self.devices.iter().enumerate() {
+            let id = match (&dev.ip, &dev.mac) {
+                (Some(ip), None) => {
+                    if !ips.insert(*ip) {
+                        return invalid(format!("device {ip} is listed twice"));
+                    }
+                    ip.to_string()
+                }
+                (None, Some(mac)) => {
+                    let normalized = normalize_mac(mac);
+                    if normalized.len() != 12 || !normalized.chars().all(|c| c.is_ascii_hexdigit())
+                    {
+                        return invalid(format!("device {}: bad MAC address {mac:?}", i + 1));
+                    }
+                    if !macs.insert(normalized) {
+                        return invalid(format!("device {mac} is listed twice"));
+                    }
+                    mac.clone()
+                }
+                _ => {
+                    return invalid(format!(
+                        "device {} needs exactly one of `ip` or `mac`",
+                        i + 1
+                    ));
+                }
+            };
+            if let Some(ep) = dev.endpoint {
+                if ep < 2 {
+                    return invalid(format!(
+                        "device {id}: endpoint {ep} is reserved for the root and aggregator"
+                    ));
+                }
+                if !endpoints.insert(ep) {
+                    return invalid(format!("device {id}: endpoint {ep} is used twice"));
+                }
+            }
+            if dev.poll_interval == Some(0) {
+                return invalid(format!(
+                    "device {id}: poll interval must be at least 1 second"
+                ));
+            }
+            for (kind, limits) in [
+                ("cooling", dev.cooling_setpoint),
+                ("heating", dev.heating_setpoint),
+            ] {
+                if let (Some(min), Some(max)) = (limits.min, limits.max)
+                    && min > max
+                {
+                    return invalid(format!(
+                        "device {id}: {kind} setpoint min {min} is above max {max}"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, ConfigError> {
+        let config: Config =
+            toml::from_str(text).map_err(|e| ConfigError::Parse("test.toml".into(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn invalid(text: &str) -> String {
+        match parse(text) {
+            Err(ConfigError::Invalid(msg)) => msg,
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn example() {
+        let config = parse(
+            r#"
+            data_dir = "/var/lib/daikin-matter"
+
+            [discovery]
+            timeout = 5000
+            interval = 300
+            remove_after = 86400
+
+            [poll]
+            interval = 30
+            fast_window = 0
+
+            [[device]]
+            ip = "192.168.1.20"
+            name = "Living room"
+            endpoint = 10
+
+            [[device]]
+            mac = "00:00:5E:00:53:42"
+            clusters = ["fan_control", "humidity"]
+            cooling_setpoint = { min = 24.0 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.poll.interval, Some(30));
+        assert_eq!(config.poll.fast_window, Some(0));
+        assert_eq!(config.poll.max_backoff, None);
+        assert_eq!(config.discovery.remove_after, Some(86400));
+        let ip = config.device_by_ip(Ipv4Addr::new(192, 168, 1, 20)).unwrap();
+        assert_eq!(ip.endpoint, Some(10));
+        assert!(ip.has_cluster(ClusterKind::Power));
+        let mac = &config.devices[1];
+        assert!(mac.matches_mac("00005e005342"));
+        assert!(mac.matches_mac("00-00-5E-00-53-42"));
+        assert!(!mac.matches_mac("00005E005343"));
+        assert!(mac.has_cluster(ClusterKind::Humidity));
+        assert!(!mac.has_cluster(ClusterKind::Power));
+        assert_eq!(mac.cooling_setpoint.min, Some(24.0));
+        assert_eq!(mac.heating_setpoint, SetpointLimits::default());
+
+        let empty = parse("").unwrap();
+        assert!(empty.devices.is_empty());
+    }
+
+    #[test]
+    fn unknown_fields() {
+        for text in [
+            "datadir = \"/tmp\"",
+            "[discovery]\ntimeout_ms = 5000",
+            "[poll]\nfast = 5",
+            "[[device]]\nip = \"192.168.1.20\"\nport = 80",
+            "[[device]]\nip = \"192.168.1.20\"\ncooling_setpoint = { low = 20.0 }",
+            "[[device]]\nip = \"192.168.1.20\"\nclusters = [\"scenes\"]",
+            "[[device]]\nip = \"192.168.1.300\"",
+        ] {
+            assert!(
+                matches!(parse(text), Err(ConfigError::Parse(..))),
+                "{text:?} parsed"
+            );
+        }
+    }
+
+    #[test]
+    fn validation_errors() {
+        for (text, expected) in [
+            (
+                "[poll]\ninterval = 0",
+                "poll intervals must be at least 1 second",
+            ),
+            (
+                "[poll]\nfast_interval = 0",
+                "poll intervals must be at least 1 second",
+            ),
+            (
+                "[poll]\nmax_backoff = 0",
+                "poll intervals must be at least 1 second",
+            ),
+            (
+                "[discovery]\nremove_after = 0",
+                "remove_after must be at least 1 second",
+            ),
+            (
+                "[[device]]\nname = \"Nowhere\"",
+                "device 1 needs exactly one of `ip` or `mac`",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\nmac = \"00005E005342\"",
+                "device 1 needs exactly one of `ip` or `mac`",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\n[[device]]\nip = \"192.168.1.20\"",
+                "device 192.168.1.20 is listed twice",
+            ),
+            (
+                "[[device]]\nmac = \"00:00:5E:00:53\"",
+                "device 1: bad MAC address \"00:00:5E:00:53\"",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\n[[device]]\nmac = \"00:00:5E:00:53:4G\"",
+                "device 2: bad MAC address \"00:00:5E:00:53:4G\"",
+            ),
+            (
+                "[[device]]\nmac = \"00005E005342\"\n[[device]]\nmac = \"00-00-5e-00-53-42\"",
+                "device 00-00-5e-00-53-42 is listed twice",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\nendpoint = 0",
+                "device 192.168.1.20: endpoint 0 is reserved for the root and aggregator",
+            ),
+            (
+                "[[device]]\nmac = \"00005E005342\"\nendpoint = 1",
+                "device 00005E005342: endpoint 1 is reserved for the root and aggregator",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\nendpoint = 5\n\
+                 [[device]]\nip = \"192.168.1.21\"\nendpoint = 5",
+                "device 192.168.1.21: endpoint 5 is used twice",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\npoll_interval = 0",
+                "device 192.168.1.20: poll interval must be at least 1 second",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\ncooling_setpoint = { min = 26, max = 24 }",
+                "device 192.168.1.20: cooling setpoint min 26 is above max 24",
+            ),
+            (
+                "[[device]]\nip = \"192.168.1.20\"\nheating_setpoint = { min = 22.5, max = 20 }",
+                "device 192.168.1.20: heating setpoint min 22.5 is above max 20",
+            ),
+        ] {
+            assert_eq!(invalid(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn apply_limits() {
+        let device = ValueConstraints::new(18.0, 32.0, 0.5);
+        let apply = |min, max| {
+            let device = device.clone();
+            let c = SetpointLimits { min, max }.apply(device);
+            (c.min, c.max, c.step)
+        };
+        assert_eq!(apply(None, None), (18.0, 32.0, 0.5));
+        assert_eq!(apply(Some(20.0), Some(26.0)), (20.0, 26.0, 0.5));
+        // Limits outside the device range are narrowed to it
+        assert_eq!(apply(Some(10.0), Some(40.0)), (18.0, 32.0, 0.5));
+        // A min above the device max leaves only the device max
+        assert_eq!(apply(Some(35.0), None), (32.0, 32.0, 0.5));
+        // A max below the device min leaves only the device min
+        assert_eq!(apply(None, Some(10.0)), (18.0, 18.0, 0.5));
+        // A max below the min never crosses it
+        assert_eq!(apply(Some(25.0), Some(20.0)), (25.0, 25.0, 0.5));
+        assert_eq!(apply(Some(35.0), Some(10.0)), (32.0, 32.0, 0.5));
+    }
+}
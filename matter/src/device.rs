@@ -1,43 +1,663 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use daikin_client::{Daikin, ReqwestClient};
-use dsiot::DaikinStatus;
-use dsiot::protocol::DaikinInfo;
+use daikin_client::{Daikin, DaikinError, ReqwestClient};
+use dsiot::{ChangeSet, DaikinStatus, HorizontalDirection, VerticalDirection};
+use rs_matter::error::{Error, ErrorCode};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
 
+use crate::fan_control::current_directions;
+
+/// Minimum age of the RSSI before it is fetched again.
+const RSSI_TTL: Duration = Duration::from_secs(60);
+
+/// How often devices are polled.
+#[derive(Clone, Copy, Debug)]
+pub struct PollConfig {
+    /// Interval between polls while nothing is happening.
+    pub interval: Duration,
+    /// Interval between polls shortly after a write or a detected change.
+    pub fast_interval: Duration,
+    /// How long to keep polling at `fast_interval`.
+    pub fast_window: Duration,
+    /// Upper bound of the interval while a device is unreachable.
+    pub max_backoff: Duration,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            fast_interval: Duration::from_secs(5),
+            fast_window: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Per-device polling state.
+struct Poller {
+    config: PollConfig,
+    fast_until: Option<Instant>,
+    failures: u32,
+}
+
+impl Poller {
+    fn new(config: PollConfig) -> Self {
+        Self {
+            config,
+            fast_until: None,
+            failures: 0,
+        }
+    }
+
+    /// Poll faster for a while, e.g. after a write or a change by the remote.
+    fn hurry(&mut self) {
+        self.fast_until = Some(Instant::now() + self.config.fast_window);
+    }
+
+    fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+
+    fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    /// Time to wait before the next poll.
+    fn delay(&self) -> Duration {
+        let PollConfig {
+            interval,
+            fast_interval,
+            max_backoff,
+            ..
+        } = self.config;
+        if self.failures > 0 {
+            // Back off while unreachable, doubling on each failed poll
+            let exp = self.failures.min(16);
+            interval
+                .saturating_mul(1 << exp)
+                .min(max_backoff.max(interval))
+        } else if self.fast_until.is_some_and(|until| Instant::now() < until) {
+            fast_interval.min(interval)
+        } else {
+            interval
+        }
+    }
+}
+
+/// Last known state of a device, kept up to date by its background task.
+struct Snapshot {
+    status: DaikinStatus,
+    reachable: bool,
+    /// When the first of the current run of failed polls happened.
+    unreachable_since: Option<Instant>,
+    /// When the running Identify ends.
+    identify_until: Option<Instant>,
+    rssi: Option<(Instant, i8)>,
+    /// Last direction of each louver axis other than Swing, to go back to
+    /// when swing is turned off.
+    fixed_directions: (Option<VerticalDirection>, Option<HorizontalDirection>),
+    /// Changes queued by `Device::update` that the background task has not
+    /// taken yet, in order. A poll result does not hold them, so they are
+    /// applied on top of it.
+    pending: VecDeque<ChangeSet>,
+}
+
+impl Snapshot {
+    fn new(status: DaikinStatus) -> Self {
+        let mut snapshot = Self {
+            status,
+            reachable: true,
+            unreachable_since: None,
+            identify_until: None,
+            rssi: None,
+            fixed_directions: (None, None),
+            pending: VecDeque::new(),
+        };
+        snapshot.remember_directions();
+        snapshot
+    }
+
+    /// Mark the device unreachable, keeping the last known status.
+    fn poll_failed(&mut self) {
+        self.reachable = false;
+        self.unreachable_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Undo a write the device refused, keeping the writes queued after it.
+    fn write_failed(&mut self, undo: &ChangeSet) {
+        undo.apply_to(&mut self.status);
+        for changes in &self.pending {
+            changes.apply_to(&mut self.status);
+        }
+        self.remember_directions();
+    }
+
+    /// Note the louver directions of the status that are not Swing.
+    fn remember_directions(&mut self) {
+        let (vertical, horizontal) = current_directions(&self.status);
+        if let Some(v) = vertical
+            && !matches!(v, VerticalDirection::Swing | VerticalDirection::Unknown)
+        {
+            self.fixed_directions.0 = Some(v);
+        }
+        if let Some(h) = horizontal
+            && !matches!(h, HorizontalDirection::Swing | HorizontalDirection::Unknown)
+        {
+            self.fixed_directions.1 = Some(h);
+        }
+    }
+}
+
+/// Handle to a bridged AC for the synchronous Matter cluster handlers.
+///
+/// Reads are served from a snapshot and never touch the network. Writes are
+/// applied to the snapshot right away and queued to a background task on the
+/// Tokio runtime, which sends them to the device and polls its status, so a
+/// slow unit cannot stall the Matter stack.
 #[derive(Clone)]
 pub struct Device {
-    dk: Daikin<ReqwestClient>,
-    rt: tokio::runtime::Handle,
-    reachable: Arc<AtomicBool>,
+    snapshot: Arc<RwLock<Snapshot>>,
+    /// Changes to write and how to undo them in the snapshot.
+    writes: UnboundedSender<(ChangeSet, ChangeSet)>,
+    identify: UnboundedSender<(Instant, ChangeSet)>,
+}
+
+/// A running Identify: the nudge sent to the device and how to undo it.
+struct Identifying {
+    until: Instant,
+    nudge: ChangeSet,
+    restore: ChangeSet,
 }
 
 impl Device {
-    pub fn new(dk: Daikin<ReqwestClient>, rt: tokio::runtime::Handle) -> Self {
-        Self {
+    /// Start the background task for `dk`, whose status was just fetched.
+    ///
+    /// `ep_id` is sent on `changed` whenever the task refreshed the snapshot.
+    pub fn spawn(
+        dk: Daikin<ReqwestClient>,
+        status: DaikinStatus,
+        rt: &tokio::runtime::Handle,
+        poll: PollConfig,
+        ep_id: u16,
+        changed: UnboundedSender<u16>,
+    ) -> Self {
+        let snapshot = Arc::new(RwLock::new(Snapshot::new(status)));
+        let (writes, rx) = unbounded_channel();
+        let (identify, identify_rx) = unbounded_channel();
+        rt.spawn(run(
             dk,
-            rt,
-            reachable: Arc::new(AtomicBool::new(true)),
+            snapshot.clone(),
+            rx,
+            identify_rx,
+            poll,
+            ep_id,
+            changed,
+        ));
+        Self {
+            snapshot,
+            writes,
+            identify,
         }
     }
 
-    pub fn get_status(&self) -> anyhow::Result<DaikinStatus> {
-        let result = self.rt.block_on(self.dk.get_status());
-        self.reachable.store(result.is_ok(), Ordering::Relaxed);
-        result
+    pub fn get_status(&self) -> DaikinStatus {
+        self.snapshot
+            .read()
+            .expect("snapshot lock poisoned")
+            .status
+            .clone()
     }
 
-    pub fn update(&self, status: DaikinStatus) -> anyhow::Result<()> {
-        let result = self.rt.block_on(self.dk.update(status));
-        self.reachable.store(result.is_ok(), Ordering::Relaxed);
-        result
+    /// Queue changes for the device.
+    ///
+    /// Fails with `Busy` while the device is unreachable, and with
+    /// `ConstraintError` for a value the device says it does not take. A
+    /// write the device rejects later is undone in the snapshot, and the
+    /// controller learns of it from the attribute reports that follow.
+    pub fn update(&self, changes: ChangeSet) -> Result<(), Error> {
+        let mut snapshot = self.snapshot.write().expect("snapshot lock poisoned");
+        if !snapshot.reachable {
+            return Err(ErrorCode::Busy.into());
+        }
+        if let Some(path) = changes.disallowed(&snapshot.status).next() {
+            debug!("Refusing write of {path}: outside what the device allows");
+            return Err(ErrorCode::ConstraintError.into());
+        }
+        let before = snapshot.status.clone();
+        changes.apply_to(&mut snapshot.status);
+        let undo = ChangeSet::diff(&snapshot.status, &before);
+        snapshot.remember_directions();
+        snapshot.pending.push_back(changes.clone());
+        self.writes
+            .send((changes, undo))
+            .map_err(|_| Error::from(ErrorCode::Busy))
+    }
+
+    /// Apply `nudge` for `duration` so the unit can be spotted, then undo it.
+    ///
+    /// A zero `duration` ends a running Identify early. While one is running
+    /// a new call only moves its end.
+    pub fn identify(&self, duration: Duration, nudge: ChangeSet) -> Result<(), Error> {
+        let mut snapshot = self.snapshot.write().expect("snapshot lock poisoned");
+        if !snapshot.reachable {
+            return Err(ErrorCode::Busy.into());
+        }
+        let until = Instant::now() + duration;
+        snapshot.identify_until = (!duration.is_zero()).then_some(until);
+        self.identify
+            .send((until, nudge))
+            .map_err(|_| Error::from(ErrorCode::Busy))
     }
 
-    pub fn get_info(&self) -> anyhow::Result<DaikinInfo> {
-        self.rt.block_on(self.dk.get_info())
+    /// Time left of the running Identify, if any.
+    pub fn identify_remaining(&self) -> Option<Duration> {
+        let snapshot = self.snapshot.read().expect("snapshot lock poisoned");
+        snapshot
+            .identify_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|left| !left.is_zero())
     }
 
     pub fn is_reachable(&self) -> bool {
-        self.reachable.load(Ordering::Relaxed)
+        self.snapshot
+            .read()
+            .expect("snapshot lock poisoned")
+            .reachable
+    }
+
+    /// How long the device has been unreachable, if it is.
+    pub fn unreachable_for(&self) -> Option<Duration> {
+        let snapshot = self.snapshot.read().expect("snapshot lock poisoned");
+        snapshot.unreachable_since.map(|since| since.elapsed())
+    }
+
+    /// Last direction of each louver axis other than Swing.
+    pub fn fixed_directions(&self) -> (Option<VerticalDirection>, Option<HorizontalDirection>) {
+        self.snapshot
+            .read()
+            .expect("snapshot lock poisoned")
+            .fixed_directions
+    }
+
+    pub fn rssi(&self) -> Option<i8> {
+        let snapshot = self.snapshot.read().expect("snapshot lock poisoned");
+        snapshot.rssi.map(|(_, rssi)| rssi)
+    }
+}
+
+/// Send queued writes to the device and refresh the snapshot after each
+/// write and on every poll.
+async fn run(
+    dk: Daikin<ReqwestClient>,
+    snapshot: Arc<RwLock<Snapshot>>,
+    mut writes: UnboundedReceiver<(ChangeSet, ChangeSet)>,
+    mut identify: UnboundedReceiver<(Instant, ChangeSet)>,
+    poll: PollConfig,
+    ep_id: u16,
+    changed: UnboundedSender<u16>,
+) {
+    let mut poller = Poller::new(poll);
+    let mut identifying: Option<Identifying> = None;
+    loop {
+        let identify_end = identifying
+            .as_ref()
+            .map_or_else(Instant::now, |id| id.until);
+        tokio::select! {
+            request = identify.recv() => {
+                let Some((until, nudge)) = request else {
+                    return;
+                };
+                match &mut identifying {
+                    Some(id) => id.until = until,
+                    None if until > Instant::now() => {
+                        identifying = start_identify(&dk, &snapshot, until, nudge, ep_id).await;
+                    }
+                    None => {}
+                }
+            }
+            _ = tokio::time::sleep_until(identify_end.into()), if identifying.is_some() => {
+                if let Some(id) = identifying.take() {
+                    end_identify(&dk, &snapshot, id, ep_id).await;
+                }
+                poller.hurry();
+            }
+            changes = writes.recv() => {
+                let Some((mut changes, mut undo)) = changes else {
+                    return;
+                };
+                // Send everything queued so far in one request
+                let mut taken = 1;
+                while let Ok((more, more_undo)) = writes.try_recv() {
+                    changes.merge(more);
+                    // The earliest undo of a field goes back the furthest
+                    undo = {
+                        let mut merged = more_undo;
+                        merged.merge(undo);
+                        merged
+                    };
+                    taken += 1;
+                }
+                {
+                    let mut snapshot = snapshot.write().expect("snapshot lock poisoned");
+                    let taken = taken.min(snapshot.pending.len());
+                    snapshot.pending.drain(..taken);
+                }
+                if let Err(e) = dk.update(changes).await {
+                    warn!(
+                        "Failed to update (ep {ep_id}, {:?}): {e}",
+                        error_code(&e)
+                    );
+                    snapshot
+                        .write()
+                        .expect("snapshot lock poisoned")
+                        .write_failed(&undo);
+                }
+                poller.hurry();
+            }
+            _ = tokio::time::sleep(poller.delay()) => {}
+        }
+
+        match dk.get_status_fresh().await {
+            // The outdoor unit answering alone leaves every setting null
+            Ok(status) if status.indoor_failed() => {
+                warn!("Poll failed (ep {ep_id}): indoor unit did not answer");
+                snapshot
+                    .write()
+                    .expect("snapshot lock poisoned")
+                    .poll_failed();
+                poller.failed();
+            }
+            Ok(mut status) => {
+                let mut snapshot = snapshot.write().expect("snapshot lock poisoned");
+                // Writes queued while polling are not in the result yet
+                for changes in &snapshot.pending {
+                    changes.apply_to(&mut status);
+                }
+                if !ChangeSet::diff(&snapshot.status, &status).is_empty() {
+                    debug!("Poll ep {ep_id}: settings changed, polling faster");
+                    poller.hurry();
+                }
+                snapshot.status = status;
+                snapshot.remember_directions();
+                snapshot.reachable = true;
+                snapshot.unreachable_since = None;
+                poller.succeeded();
+            }
+            Err(e) => {
+                warn!("Poll failed (ep {ep_id}): {e}");
+                snapshot
+                    .write()
+                    .expect("snapshot lock poisoned")
+                    .poll_failed();
+                poller.failed();
+            }
+        }
+
+        let rssi_age = snapshot
+            .read()
+            .expect("snapshot lock poisoned")
+            .rssi
+            .map(|(ts, _)| ts.elapsed());
+        if rssi_age.is_none_or(|age| age >= RSSI_TTL) {
+            match dk.get_info().await {
+                Ok(info) => {
+                    if let Some(rssi) = info.rssi {
+                        snapshot.write().expect("snapshot lock poisoned").rssi =
+                            Some((Instant::now(), rssi));
+                    }
+                }
+                Err(e) => debug!("Failed to get info for RSSI (ep {ep_id}): {e}"),
+            }
+        }
+
+        if changed.send(ep_id).is_err() {
+            return;
+        }
+    }
+}
+
+/// Map a client error to the status code a controller would have been
+/// given for it.
+fn error_code(error: &DaikinError) -> ErrorCode {
+    match error {
+        // The device may answer again later, so the controller can retry
+        DaikinError::Transport(_) | DaikinError::Timeout => ErrorCode::Busy,
+        // The device refused the request, e.g. a value out of range
+        DaikinError::StatusCode {
+            code: 4000..=4999, ..
+        } => ErrorCode::ConstraintError,
+        _ => ErrorCode::Failure,
+    }
+}
+
+/// Send the Identify nudge and remember how to undo it.
+async fn start_identify(
+    dk: &Daikin<ReqwestClient>,
+    snapshot: &RwLock<Snapshot>,
+    until: Instant,
+    nudge: ChangeSet,
+    ep_id: u16,
+) -> Option<Identifying> {
+    let status = snapshot
+        .read()
+        .expect("snapshot lock poisoned")
+        .status
+        .clone();
+    let mut nudged = status.clone();
+    nudge.apply_to(&mut nudged);
+    let restore = ChangeSet::diff(&nudged, &status);
+    info!("Identify ep {ep_id} for {:?}", until - Instant::now());
+    match dk.update(nudge.clone()).await {
+        Ok(()) => Some(Identifying {
+            until,
+            nudge,
+            restore,
+        }),
+        Err(e) => {
+            warn!("Failed to identify (ep {ep_id}): {e}");
+            snapshot
+                .write()
+                .expect("snapshot lock poisoned")
+                .identify_until = None;
+            None
+        }
+    }
+}
+
+/// Undo the Identify nudge, unless the settings were changed meanwhile.
+async fn end_identify(
+    dk: &Daikin<ReqwestClient>,
+    snapshot: &RwLock<Snapshot>,
+    id: Identifying,
+    ep_id: u16,
+) {
+    match dk.get_status_fresh().await {
+        Ok(status) if id.nudge.is_applied_to(&status) => {
+            if let Err(e) = dk.update(id.restore).await {
+                warn!("Failed to restore after identify (ep {ep_id}): {e}");
+            }
+        }
+        Ok(_) => debug!("Identify ep {ep_id}: settings changed, not restoring"),
+        Err(e) => warn!("Failed to restore after identify (ep {ep_id}): {e}"),
+    }
+    let mut snapshot = snapshot.write().expect("snapshot lock poisoned");
+    // A new Identify may have started in the meantime
+    if snapshot
+        .identify_until
+        .is_some_and(|until| until <= Instant::now())
+    {
+        snapshot.identify_until = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dsiot::{DaikinResponse, Mode, TemperatureTarget};
+
+    fn fixture() -> DaikinStatus {
+        let res: DaikinResponse =
+            serde_json::from_str(include_str!("../../dsiot/src/fixtures/status.json"))
+                .expect("Invalid JSON file.");
+        res.into()
+    }
+
+    fn device(status: DaikinStatus) -> (Device, UnboundedReceiver<(ChangeSet, ChangeSet)>) {
+        let (writes, rx) = unbounded_channel();
+        let (identify, _) = unbounded_channel();
+        let device = Device {
+            snapshot: Arc::new(RwLock::new(Snapshot::new(status))),
+            writes,
+            identify,
+        };
+        (device, rx)
+    }
+
+    #[test]
+    fn update_refuses_disallowed() {
+        let base = fixture();
+        let (device, mut rx) = device(base.clone());
+
+        let mut status = base.clone();
+        status.temperature.cooling.set_value(40.0);
+        let err = device.update(ChangeSet::diff(&base, &status)).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ConstraintError);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(device.get_status().temperature, base.temperature);
+
+        let changes = ChangeSet::builder(&base)
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+        device.update(changes.clone()).unwrap();
+        let (queued, undo) = rx.try_recv().unwrap();
+        assert_eq!(queued, changes);
+        let mut status = device.get_status();
+        assert!(changes.is_applied_to(&status));
+        undo.apply_to(&mut status);
+        assert_eq!(status.temperature, base.temperature);
+    }
+
+    #[test]
+    fn update_unreachable() {
+        let base = fixture();
+        let (device, mut rx) = device(base.clone());
+        device.snapshot.write().unwrap().reachable = false;
+        let changes = ChangeSet::builder(&base).mode(Mode::Heating).build();
+        assert_eq!(device.update(changes).unwrap_err().code(), ErrorCode::Busy);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn write_failed_keeps_later_writes() {
+        let base = fixture();
+        let (device, mut rx) = device(base.clone());
+        let cooling = ChangeSet::builder(&base)
+            .temperature(TemperatureTarget::cooling(26.0))
+            .build();
+        device.update(cooling).unwrap();
+        let (_, undo) = rx.try_recv().unwrap();
+        let mode = ChangeSet::builder(&base).mode(Mode::Heating).build();
+        device.update(mode).unwrap();
+
+        let mut snapshot = device.snapshot.write().unwrap();
+        // The first write was sent and refused; the second is still queued
+        snapshot.pending.pop_front();
+        snapshot.write_failed(&undo);
+        assert_eq!(
+            snapshot.status.temperature.cooling,
+            base.temperature.cooling
+        );
+        assert_eq!(snapshot.status.mode.get_enum(), Some(Mode::Heating));
+    }
+
+    #[test]
+    fn late_error_codes() {
+        let refused = DaikinError::StatusCode {
+            from: "/dsiot/edge/adr_0100.dgc_status".into(),
+            code: 4005,
+        };
+        assert_eq!(error_code(&refused), ErrorCode::ConstraintError);
+        assert_eq!(error_code(&DaikinError::Timeout), ErrorCode::Busy);
+        assert_eq!(
+            error_code(&DaikinError::HttpStatus(500)),
+            ErrorCode::Failure
+        );
+    }
+
+    fn poller() -> Poller {
+        Poller::new(PollConfig {
+            interval: Duration::from_secs(30),
+            fast_interval: Duration::from_secs(5),
+            fast_window: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(300),
+        })
+    }
+
+    #[test]
+    fn poll_interval() {
+        let mut poller = poller();
+        assert_eq!(poller.delay(), Duration::from_secs(30));
+        poller.succeeded();
+        assert_eq!(poller.delay(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn poll_hurry() {
+        let mut poller = poller();
+        poller.hurry();
+        assert_eq!(poller.delay(), Duration::from_secs(5));
+        // The fast window has passed
+        poller.fast_until = Some(Instant::now() - Duration::from_secs(1));
+        assert_eq!(poller.delay(), Duration::from_secs(30));
+
+        // A fast interval longer than the interval is not used
+        poller.config.fast_interval = Duration::from_secs(45);
+        poller.hurry();
+        assert_eq!(poller.delay(), Duration::from_secs(30));
+
+        // An empty window never polls fast
+        poller.config.fast_interval = Duration::from_secs(5);
+        poller.config.fast_window = Duration::ZERO;
+        poller.hurry();
+        assert_eq!(poller.delay(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn poll_backoff() {
+        let mut poller = poller();
+        poller.hurry();
+        let delays: Vec<_> = (0..6)
+            .map(|_| {
+                poller.failed();
+                poller.delay().as_secs()
+            })
+            .collect();
+        // Backing off takes precedence over the fast window
+        assert_eq!(delays, [60, 120, 240, 300, 300, 300]);
+
+        // Many failures neither overflow nor pass the bound
+        for _ in 0..100 {
+            poller.failed();
+        }
+        assert_eq!(poller.delay(), Duration::from_secs(300));
+
+        // A success goes back to the fast window still running
+        poller.succeeded();
+        assert_eq!(poller.delay(), Duration::from_secs(5));
+        poller.failed();
+        assert_eq!(poller.delay(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn poll_backoff_below_interval() {
+        let mut poller = poller();
+        poller.config.max_backoff = Duration::from_secs(10);
+        poller.failed();
+        assert_eq!(poller.delay(), Duration::from_secs(30));
+        poller.failed();
+        assert_eq!(poller.delay(), Duration::from_secs(30));
     }
 }
@@ -0,0 +1,329 @@
+//! Persistent assignment of Matter endpoint IDs to devices.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use dsiot::DaikinInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::store::write_atomically;
+
+const FILE_NAME: &str = "endpoints.toml";
+
+const HEADER: &str = "\
+# Matter endpoint IDs of bridged devices, kept across restarts.
+# A device is recognized by either its MAC address or its EDID. To move an
+# endpoint to a replaced adapter, change `mac` and `edid` of its entry, or
+# set `endpoint` for the device in the config file. `parts` holds the IDs of
+# the extra endpoints of the device, such as its louvers.
+
+";
+
+/// First endpoint ID available to bridged devices; 0 is the root node and 1
+/// the aggregator.
+const FIRST_BRIDGED_EP: u16 = 2;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    endpoint: u16,
+    mac: String,
+    edid: u64,
+    /// Device name when last seen, to help editing the file by hand.
+    #[serde(default)]
+    name: String,
+    /// Endpoint IDs of the parts of the device, by role.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    parts: BTreeMap<String, u16>,
+}
+
+impl Entry {
+    fn is(&self, info: &DaikinInfo) -> bool {
+        (!self.mac.is_empty() && self.mac == info.mac) || self.edid == info.edid
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct File {
+    #[serde(default, rename = "device")]
+    entries: Vec<Entry>,
+}
+
+/// Endpoint IDs assigned to devices, stored in the data directory.
+#[derive(Debug)]
+pub struct EndpointMap {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl EndpointMap {
+    /// Load the map from `data_dir`, or start an empty one.
+    pub fn load(data_dir: &Path) -> anyhow::Result<Self> {
+        let path = data_dir.join(FILE_NAME);
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let file: File = toml::from_str(&text)
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {e}", path.display()))?;
+                file.entries
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => anyhow::bail!("Failed to read {}: {e}", path.display()),
+        };
+        Ok(Self { path, entries })
+    }
+
+    /// Write the map back to the data directory.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let file = File {
+            entries: self.entries.clone(),
+        };
+        let text = format!("{HEADER}{}", toml::to_string(&file)?);
+        write_atomically(&self.path, &text)
+    }
+
+    /// Assign an endpoint ID to each device, given with its fixed ID from the
+    /// config if any, and return them in the same order.
+    ///
+    /// Fixed IDs win over stored ones. A known device keeps its stored ID
+    /// unless another device claimed it; a new device gets the low 16 bits of
+    /// its EDID if free, otherwise the lowest free ID.
+    pub fn assign(&mut self, devices: &[(&DaikinInfo, Option<u16>)]) -> Vec<u16> {
+        let mut assigned = vec![0; devices.len()];
+        let mut taken = HashSet::new();
+
+        for (i, (info, fixed)) in devices.iter().enumerate() {
+            if let Some(ep) = *fixed {
+                // A part using the ID gets a new one when it is assigned next
+                for entry in &mut self.entries {
+                    entry.parts.retain(|_, part| *part != ep);
+                }
+                self.entries.retain(|e| {
+                    let keep = e.endpoint != ep || e.is(info);
+                    if !keep {
+                        warn!(
+                            "Endpoint {ep} of {} (MAC: {}) is reassigned to {}",
+                            e.name, e.mac, info.name
+                        );
+                    }
+                    keep
+                });
+                self.set(info, ep);
+                taken.insert(ep);
+                assigned[i] = ep;
+            }
+        }
+
+        for (i, (info, fixed)) in devices.iter().enumerate() {
+            if fixed.is_some() {
+                continue;
+            }
+            let stored = self
+                .entries
+                .iter()
+                .find(|e| e.is(info))
+                .map(|e| e.endpoint)
+                .filter(|ep| *ep >= FIRST_BRIDGED_EP && !taken.contains(ep));
+            let ep = match stored {
+                Some(ep) => ep,
+                None => {
+                    let ep = self.allocate(info, &taken);
+                    info!(
+                        "Assigned endpoint {ep} to {} (MAC: {})",
+                        info.name, info.mac
+                    );
+                    ep
+                }
+            };
+            self.set(info, ep);
+            taken.insert(ep);
+            assigned[i] = ep;
+        }
+        assigned
+    }
+
+    /// Pick a free endpoint ID for a device without one.
+    fn allocate(&self, info: &DaikinInfo, taken: &HashSet<u16>) -> u16 {
+        let is_free = |ep: u16| {
+            ep >= FIRST_BRIDGED_EP
+                && !taken.contains(&ep)
+                && !self.entries.iter().any(|e| e.endpoint == ep && !e.is(info))
+                && !self.is_part(ep)
+        };
+        let preferred = (info.edid & 0xFFFF) as u16;
+        if is_free(preferred) {
+            return preferred;
+        }
+        (FIRST_BRIDGED_EP..=u16::MAX)
+            .find(|ep| is_free(*ep))
+            .expect("fewer devices than endpoint IDs")
+    }
+
+    /// Assign an endpoint ID to each part `role` of the device at endpoint
+    /// `ep`, and return them in the same order.
+    ///
+    /// A part keeps its stored ID unless another endpoint took it; a new part
+    /// gets the lowest free ID.
+    pub fn assign_parts(&mut self, ep: u16, roles: &[&str]) -> Vec<u16> {
+        let Some(owner) = self.entries.iter().position(|e| e.endpoint == ep) else {
+            return Vec::new();
+        };
+        let mut assigned = Vec::with_capacity(roles.len());
+        for role in roles {
+            let stored = self.entries[owner].parts.get(*role).copied();
+            let id = match stored.filter(|id| self.uses(*id) == 1) {
+                Some(id) => id,
+                None => {
+                    let id = (FIRST_BRIDGED_EP..=u16::MAX)
+                        .find(|id| self.uses(*id) == 0)
+                        .expect("fewer endpoints than endpoint IDs");
+                    info!("Assigned endpoint {id} to {role} of endpoint {ep}");
+                    self.entries[owner].parts.insert(role.to_string(), id);
+                    id
+                }
+            };
+            assigned.push(id);
+        }
+        assigned
+    }
+
+    /// Whether `ep` is the ID of a part of any device.
+    fn is_part(&self, ep: u16) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.parts.values().any(|part| *part == ep))
+    }
+
+    /// How many devices and parts use the endpoint ID `ep`.
+    fn uses(&self, ep: u16) -> usize {
+        self.entries
+            .iter()
+            .map(|e| {
+                usize::from(e.endpoint == ep) + e.parts.values().filter(|part| **part == ep).count()
+            })
+            .sum()
+    }
+
+    /// Record `ep` for the device, updating its MAC, EDID and name.
+    fn set(&mut self, info: &DaikinInfo, ep: u16) {
+        let parts = self
+            .entries
+            .iter()
+            .find(|e| e.is(info))
+            .map(|e| e.parts.clone())
+            .unwrap_or_default();
+        let entry = Entry {
+            endpoint: ep,
+            mac: info.mac.clone(),
+            edid: info.edid,
+            name: info.name.clone(),
+            parts,
+        };
+        self.entries.retain(|e| !e.is(info));
+        self.entries.push(entry);
+        self.entries.sort_by_key(|e| e.endpoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str, mac: &str, edid: u64) -> DaikinInfo {
+        DaikinInfo {
+            name: name.into(),
+            mac: mac.into(),
+            version: "1.0.0".into(),
+            edid,
+            en_ipower: false,
+            rssi: None,
+            ssid: None,
+            security_type: None,
+        }
+    }
+
+    /// An empty map stored in a directory of its own.
+    fn empty_map(test: &str) -> EndpointMap {
+        let dir = std::env::temp_dir().join(format!("daikin-matter-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        EndpointMap::load(&dir).unwrap()
+    }
+
+    #[test]
+    fn edid_collision() {
+        let mut map = empty_map("edid_collision");
+        let a = info("A", "00005E005301", 0x0001_0005);
+        let b = info("B", "00005E005302", 0x0002_0005);
+        assert_eq!(map.assign(&[(&a, None), (&b, None)]), vec![5, 2]);
+        // Each keeps its ID, whichever is found first
+        assert_eq!(map.assign(&[(&b, None), (&a, None)]), vec![2, 5]);
+    }
+
+    #[test]
+    fn fixed_endpoint() {
+        let mut map = empty_map("fixed_endpoint");
+        let a = info("A", "00005E005301", 10);
+        let b = info("B", "00005E005302", 20);
+        assert_eq!(map.assign(&[(&a, None), (&b, None)]), vec![10, 20]);
+        assert_eq!(map.assign(&[(&a, None), (&b, Some(10))]), vec![2, 10]);
+        assert_eq!(map.assign(&[(&a, None), (&b, None)]), vec![2, 10]);
+    }
+
+    #[test]
+    fn replaced_adapter() {
+        let mut map = empty_map("replaced_adapter");
+        let old = info("A", "00005E005301", 10);
+        assert_eq!(map.assign(&[(&old, None)]), vec![10]);
+
+        // Same EDID, new MAC
+        let by_edid = info("A", "00005E005399", 10);
+        assert_eq!(map.assign(&[(&by_edid, None)]), vec![10]);
+        // Same MAC, new EDID
+        let by_mac = info("A", "00005E005399", 30);
+        assert_eq!(map.assign(&[(&by_mac, None)]), vec![10]);
+        assert_eq!(map.entries.len(), 1);
+        assert_eq!(map.entries[0].edid, 30);
+    }
+
+    #[test]
+    fn parts() {
+        let mut map = empty_map("parts");
+        let a = info("A", "00005E005301", 2);
+        let b = info("B", "00005E005302", 3);
+        assert_eq!(map.assign(&[(&a, None)]), vec![2]);
+        assert_eq!(
+            map.assign_parts(2, &["vertical_louver", "horizontal_louver"]),
+            vec![3, 4]
+        );
+        // The preferred ID of B is a part of A
+        assert_eq!(map.assign(&[(&a, None), (&b, None)]), vec![2, 5]);
+        assert_eq!(map.assign_parts(5, &["vertical_louver"]), vec![6]);
+        assert_eq!(
+            map.assign_parts(2, &["vertical_louver", "horizontal_louver"]),
+            vec![3, 4]
+        );
+
+        // A fixed ID taking a part moves the part
+        let c = info("C", "00005E005303", 7);
+        assert_eq!(map.assign(&[(&c, Some(3))]), vec![3]);
+        assert_eq!(
+            map.assign_parts(2, &["vertical_louver", "horizontal_louver"]),
+            vec![7, 4]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut map = empty_map("round_trip");
+        let a = info("A", "00005E005301", 0x0001_0005);
+        let b = info("B", "00005E005302", 0x0002_0005);
+        map.assign(&[(&a, None), (&b, None)]);
+        map.assign_parts(5, &["outdoor_temperature"]);
+        map.save().unwrap();
+
+        let dir = map.path.parent().unwrap();
+        let mut loaded = EndpointMap::load(dir).unwrap();
+        assert_eq!(loaded.entries, map.entries);
+        assert_eq!(loaded.assign(&[(&b, None), (&a, None)]), vec![2, 5]);
+        assert_eq!(loaded.assign_parts(5, &["outdoor_temperature"]), vec![3]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
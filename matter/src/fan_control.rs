@@ -1,7 +1,8 @@
 use dsiot::mapping::fan::{self, FanSpeed};
+use dsiot::mapping::swing;
 use dsiot::{
-    AutoModeWindSpeed, DaikinStatus, HorizontalDirection, Mode, PowerState, StateTransition,
-    VerticalDirection, WindSpeed,
+    AutoModeWindSpeed, Capabilities, ChangeSet, DaikinStatus, HorizontalDirection, Mode,
+    PowerState, StateTransition, VerticalDirection, WindSpeed,
 };
 use rs_matter::dm::clusters::decl::fan_control;
 use rs_matter::dm::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
@@ -19,6 +20,40 @@ pub struct FanControlHandler {
 
 const SPEED_MAX: u8 = 5;
 
+/// Wind speeds the Step command moves through, slowest first.
+const STEP_SPEEDS: [WindSpeed; 6] = [
+    WindSpeed::Silent,
+    WindSpeed::Lev1,
+    WindSpeed::Lev2,
+    WindSpeed::Lev3,
+    WindSpeed::Lev4,
+    WindSpeed::Lev5,
+];
+
+/// Position in `STEP_SPEEDS` that Auto steps from, matching the Lev3 it is
+/// reported as in SpeedCurrent.
+const STEP_FROM_AUTO: usize = 3;
+
+/// Wind speeds the Step command moves through in Auto mode, slowest first.
+const AUTO_MODE_STEP_SPEEDS: [AutoModeWindSpeed; 2] =
+    [AutoModeWindSpeed::Silent, AutoModeWindSpeed::Auto];
+
+/// Attributes of the Fan Control cluster that do not depend on its
+/// features, together with the given feature-dependent ones.
+macro_rules! fan_control_attrs {
+    ($($attr:ident)|*) => {
+        with!(
+            required;
+            fan_control::AttributeId::FanMode
+            | fan_control::AttributeId::FanModeSequence
+            | fan_control::AttributeId::SpeedSetting
+            | fan_control::AttributeId::SpeedMax
+            | fan_control::AttributeId::SpeedCurrent
+            $(| fan_control::AttributeId::$attr)*
+        )
+    };
+}
+
 impl FanControlHandler {
     pub const CLUSTER: Cluster<'static> = fan_control::FULL_CLUSTER
         .with_revision(4)
@@ -26,39 +61,88 @@ impl FanControlHandler {
             fan_control::Feature::MULTI_SPEED.bits()
                 | fan_control::Feature::AUTO.bits()
                 | fan_control::Feature::ROCKING.bits()
-                | fan_control::Feature::WIND.bits(),
+                | fan_control::Feature::WIND.bits()
+                | fan_control::Feature::STEP.bits(),
         )
-        .with_attrs(with!(
-            required;
-            fan_control::AttributeId::FanMode
-            | fan_control::AttributeId::FanModeSequence
-            | fan_control::AttributeId::SpeedSetting
-            | fan_control::AttributeId::SpeedMax
-            | fan_control::AttributeId::SpeedCurrent
-            | fan_control::AttributeId::RockSupport
-            | fan_control::AttributeId::RockSetting
-            | fan_control::AttributeId::WindSupport
-            | fan_control::AttributeId::WindSetting
+        .with_attrs(fan_control_attrs!(
+            RockSupport | RockSetting | WindSupport | WindSetting
         ))
-        .with_cmds(with!());
+        .with_cmds(with!(fan_control::CommandId::Step));
+
+    /// Attributes of a unit whose louvers can swing but that makes no wind.
+    const ROCK_CLUSTER: Cluster<'static> =
+        Self::CLUSTER.with_attrs(fan_control_attrs!(RockSupport | RockSetting));
+
+    /// Attributes of a unit that makes wind but whose louvers cannot swing.
+    const WIND_CLUSTER: Cluster<'static> =
+        Self::CLUSTER.with_attrs(fan_control_attrs!(WindSupport | WindSetting));
+
+    /// Attributes of a unit that neither swings nor makes wind.
+    const SPEED_CLUSTER: Cluster<'static> = Self::CLUSTER.with_attrs(fan_control_attrs!());
+
+    /// The cluster with the features of a unit with `caps`, and only the
+    /// attributes those features call for.
+    pub fn cluster(caps: &Capabilities) -> Cluster<'static> {
+        let mut features =
+            fan_control::Feature::MULTI_SPEED.bits() | fan_control::Feature::STEP.bits();
+        if caps.wind_speeds.contains(&WindSpeed::Auto) {
+            features |= fan_control::Feature::AUTO.bits();
+        }
+        let rock = caps.can_swing();
+        if rock {
+            features |= fan_control::Feature::ROCKING.bits();
+        }
+        let wind = !wind_support(caps).is_empty();
+        if wind {
+            features |= fan_control::Feature::WIND.bits();
+        }
+        let cluster = match (rock, wind) {
+            (true, true) => Self::CLUSTER,
+            (true, false) => Self::ROCK_CLUSTER,
+            (false, true) => Self::WIND_CLUSTER,
+            (false, false) => Self::SPEED_CLUSTER,
+        };
+        cluster.with_features(features)
+    }
 
     pub fn new(dataver: Dataver, device: Device) -> Self {
         Self { dataver, device }
     }
 
-    fn get_status(&self) -> Result<DaikinStatus, Error> {
-        self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(ErrorCode::Busy)
-        })
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::from_status(&self.device.get_status())
+    }
+
+    fn update(&self, base: &DaikinStatus, status: &DaikinStatus) -> Result<(), Error> {
+        self.device.update(ChangeSet::diff(base, status))
+    }
+}
+
+/// Louvers of a unit with `caps` that can swing.
+fn rock_support(caps: &Capabilities) -> fan_control::RockBitmap {
+    let mut bits = fan_control::RockBitmap::empty();
+    if caps.vertical_directions.contains(&VerticalDirection::Swing) {
+        bits |= fan_control::RockBitmap::ROCK_UP_DOWN;
+    }
+    if caps
+        .horizontal_directions
+        .contains(&HorizontalDirection::Swing)
+    {
+        bits |= fan_control::RockBitmap::ROCK_LEFT_RIGHT;
     }
+    bits
+}
 
-    fn update(&self, status: DaikinStatus) -> Result<(), Error> {
-        self.device.update(status).map_err(|e| {
-            warn!("Failed to update: {e}");
-            Error::from(ErrorCode::Busy)
-        })
+/// Wind settings a unit with `caps` can make.
+fn wind_support(caps: &Capabilities) -> fan_control::WindBitmap {
+    let mut bits = fan_control::WindBitmap::empty();
+    if caps.wind_speeds.contains(&WindSpeed::Silent) {
+        bits |= fan_control::WindBitmap::SLEEP_WIND;
     }
+    if caps.vertical_directions.contains(&VerticalDirection::Nice) {
+        bits |= fan_control::WindBitmap::NATURAL_WIND;
+    }
+    bits
 }
 
 /// Get the current wind speed for the active HVAC mode.
@@ -114,7 +198,7 @@ fn wind_speed_to_fan_mode(speed: WindSpeed, is_off: bool) -> fan_control::FanMod
 }
 
 /// Apply wind speed to the current mode's wind settings.
-fn apply_wind_speed(status: &mut DaikinStatus, speed: WindSpeed) {
+pub(crate) fn apply_wind_speed(status: &mut DaikinStatus, speed: WindSpeed) {
     let mode = status.mode.get_enum().unwrap_or(Mode::Auto);
     match mode {
         Mode::Cooling => status.wind.cooling.speed.set_value(speed),
@@ -132,8 +216,40 @@ fn apply_wind_speed(status: &mut DaikinStatus, speed: WindSpeed) {
     }
 }
 
+/// Run the fan of the current mode silently.
+pub(crate) fn apply_silent(status: &mut DaikinStatus) {
+    if status.mode.get_enum() == Some(Mode::Auto) {
+        status.wind.auto.speed.set_value(AutoModeWindSpeed::Silent);
+    } else {
+        apply_wind_speed(status, WindSpeed::Silent);
+    }
+}
+
+/// Position after one Step along `len` speeds, or `None` for off.
+///
+/// `current` is `None` when the unit is off and off counts as a step.
+fn step_position(
+    current: Option<usize>,
+    len: usize,
+    increase: bool,
+    wrap: bool,
+    lowest_off: bool,
+) -> Option<usize> {
+    let last = len - 1;
+    match (current, increase) {
+        (None, true) => Some(0),
+        (None, false) => wrap.then_some(last),
+        (Some(i), true) if i < last => Some(i + 1),
+        (Some(_), true) if !wrap => Some(last),
+        (Some(_), true) => (!lowest_off).then_some(0),
+        (Some(0), false) if lowest_off => None,
+        (Some(0), false) => Some(if wrap { last } else { 0 }),
+        (Some(i), false) => Some(i - 1),
+    }
+}
+
 /// Get vertical/horizontal direction for the active HVAC mode.
-fn current_directions(
+pub(crate) fn current_directions(
     status: &DaikinStatus,
 ) -> (Option<VerticalDirection>, Option<HorizontalDirection>) {
     let mode = status.mode.get_enum();
@@ -162,8 +278,38 @@ fn current_directions(
     }
 }
 
+/// Get the vertical/horizontal directions the active HVAC mode allows.
+pub(crate) fn allowed_directions(
+    status: &DaikinStatus,
+) -> (Vec<VerticalDirection>, Vec<HorizontalDirection>) {
+    let mode = status.mode.get_enum();
+    match mode {
+        Some(Mode::Cooling) => (
+            status.wind.cooling.vertical_direction.allowed_enums(),
+            status.wind.cooling.horizontal_direction.allowed_enums(),
+        ),
+        Some(Mode::Heating) => (
+            status.wind.heating.vertical_direction.allowed_enums(),
+            status.wind.heating.horizontal_direction.allowed_enums(),
+        ),
+        Some(Mode::Dehumidify) => (
+            status.wind.dehumidify.vertical_direction.allowed_enums(),
+            status.wind.dehumidify.horizontal_direction.allowed_enums(),
+        ),
+        Some(Mode::Auto) => (
+            status.wind.auto.vertical_direction.allowed_enums(),
+            status.wind.auto.horizontal_direction.allowed_enums(),
+        ),
+        Some(Mode::Fan) => (
+            status.wind.fan.vertical_direction.allowed_enums(),
+            status.wind.fan.horizontal_direction.allowed_enums(),
+        ),
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
 /// Apply vertical/horizontal direction to the current mode's wind settings.
-fn apply_directions(
+pub(crate) fn apply_directions(
     status: &mut DaikinStatus,
     vertical: VerticalDirection,
     horizontal: HorizontalDirection,
@@ -221,7 +367,7 @@ impl fan_control::ClusterHandler for FanControlHandler {
     }
 
     fn fan_mode(&self, _ctx: impl ReadContext) -> Result<fan_control::FanModeEnum, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         let is_off = PowerState::from_status(&status) != Some(PowerState::On);
         let speed = current_wind_speed(&status).unwrap_or(WindSpeed::Auto);
         Ok(wind_speed_to_fan_mode(speed, is_off))
@@ -231,11 +377,15 @@ impl fan_control::ClusterHandler for FanControlHandler {
         &self,
         _ctx: impl ReadContext,
     ) -> Result<fan_control::FanModeSequenceEnum, Error> {
-        Ok(fan_control::FanModeSequenceEnum::OffLowMedHighAuto)
+        if self.capabilities().wind_speeds.contains(&WindSpeed::Auto) {
+            Ok(fan_control::FanModeSequenceEnum::OffLowMedHighAuto)
+        } else {
+            Ok(fan_control::FanModeSequenceEnum::OffLowMedHigh)
+        }
     }
 
     fn percent_setting(&self, _ctx: impl ReadContext) -> Result<Nullable<Percent>, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         match current_wind_speed(&status) {
             Some(WindSpeed::Auto) => Ok(Nullable::none()),
             Some(s) => Ok(Nullable::some(wind_speed_to_setting(s) * 20)),
@@ -244,7 +394,7 @@ impl fan_control::ClusterHandler for FanControlHandler {
     }
 
     fn percent_current(&self, _ctx: impl ReadContext) -> Result<Percent, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         Ok(current_wind_speed(&status)
             .map(|s| wind_speed_to_setting(s) * 20)
             .unwrap_or(0))
@@ -255,7 +405,7 @@ impl fan_control::ClusterHandler for FanControlHandler {
     }
 
     fn speed_setting(&self, _ctx: impl ReadContext) -> Result<Nullable<u8>, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         match current_wind_speed(&status) {
             Some(WindSpeed::Auto) => Ok(Nullable::none()),
             Some(s) => Ok(Nullable::some(wind_speed_to_setting(s))),
@@ -264,18 +414,18 @@ impl fan_control::ClusterHandler for FanControlHandler {
     }
 
     fn speed_current(&self, _ctx: impl ReadContext) -> Result<u8, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         Ok(current_wind_speed(&status)
             .map(wind_speed_to_setting)
             .unwrap_or(0))
     }
 
     fn rock_support(&self, _ctx: impl ReadContext) -> Result<fan_control::RockBitmap, Error> {
-        Ok(fan_control::RockBitmap::ROCK_UP_DOWN | fan_control::RockBitmap::ROCK_LEFT_RIGHT)
+        Ok(rock_support(&self.capabilities()))
     }
 
     fn rock_setting(&self, _ctx: impl ReadContext) -> Result<fan_control::RockBitmap, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         let (vert, horiz) = current_directions(&status);
         let mut bits = fan_control::RockBitmap::empty();
         if vert == Some(VerticalDirection::Swing) {
@@ -288,11 +438,11 @@ impl fan_control::ClusterHandler for FanControlHandler {
     }
 
     fn wind_support(&self, _ctx: impl ReadContext) -> Result<fan_control::WindBitmap, Error> {
-        Ok(fan_control::WindBitmap::SLEEP_WIND | fan_control::WindBitmap::NATURAL_WIND)
+        Ok(wind_support(&self.capabilities()))
     }
 
     fn wind_setting(&self, _ctx: impl ReadContext) -> Result<fan_control::WindBitmap, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         let speed = current_wind_speed(&status);
         let (vert, _) = current_directions(&status);
         let mut bits = fan_control::WindBitmap::empty();
@@ -313,7 +463,8 @@ impl fan_control::ClusterHandler for FanControlHandler {
     ) -> Result<(), Error> {
         let speed = match value {
             fan_control::FanModeEnum::Off => {
-                let mut status = self.get_status()?;
+                let base = self.device.get_status();
+                let mut status = base.clone();
                 StateTransition::new()
                     .power(PowerState::Off)
                     .apply_to_status(&mut status)
@@ -322,7 +473,7 @@ impl fan_control::ClusterHandler for FanControlHandler {
                         Error::from(ErrorCode::InvalidState)
                     })?;
                 debug!("FanControl: fan_mode → Off (power off)");
-                self.update(status)?;
+                self.update(&base, &status)?;
                 self.dataver.changed();
                 return Ok(());
             }
@@ -332,10 +483,11 @@ impl fan_control::ClusterHandler for FanControlHandler {
             fan_control::FanModeEnum::On => WindSpeed::Lev3,
             fan_control::FanModeEnum::Auto | fan_control::FanModeEnum::Smart => WindSpeed::Auto,
         };
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
         apply_wind_speed(&mut status, speed);
         debug!("FanControl: fan_mode → {:?}", value);
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
@@ -353,10 +505,11 @@ impl fan_control::ClusterHandler for FanControlHandler {
                 auto: false,
             }),
         };
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
         apply_wind_speed(&mut status, speed);
         debug!("FanControl: percent_setting → {:?}", opt);
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
@@ -367,10 +520,11 @@ impl fan_control::ClusterHandler for FanControlHandler {
             None => WindSpeed::Auto,
             Some(s) => setting_to_wind_speed(s),
         };
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
         apply_wind_speed(&mut status, speed);
         debug!("FanControl: speed_setting → {:?}", opt);
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
@@ -380,20 +534,21 @@ impl fan_control::ClusterHandler for FanControlHandler {
         _ctx: impl WriteContext,
         value: fan_control::RockBitmap,
     ) -> Result<(), Error> {
-        let vertical = if value.contains(fan_control::RockBitmap::ROCK_UP_DOWN) {
-            VerticalDirection::Swing
-        } else {
-            VerticalDirection::Auto
-        };
-        let horizontal = if value.contains(fan_control::RockBitmap::ROCK_LEFT_RIGHT) {
-            HorizontalDirection::Swing
-        } else {
-            HorizontalDirection::Auto
-        };
-        let mut status = self.get_status()?;
+        // An axis that stops swinging goes back to its last fixed position
+        let (vertical, horizontal) = self.device.fixed_directions();
+        let vertical = swing::vertical(
+            value.contains(fan_control::RockBitmap::ROCK_UP_DOWN),
+            vertical,
+        );
+        let horizontal = swing::horizontal(
+            value.contains(fan_control::RockBitmap::ROCK_LEFT_RIGHT),
+            horizontal,
+        );
+        let base = self.device.get_status();
+        let mut status = base.clone();
         apply_directions(&mut status, vertical, horizontal);
         debug!("FanControl: rock_setting → {:?}", value);
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
@@ -403,7 +558,8 @@ impl fan_control::ClusterHandler for FanControlHandler {
         _ctx: impl WriteContext,
         value: fan_control::WindBitmap,
     ) -> Result<(), Error> {
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
         if value.contains(fan_control::WindBitmap::SLEEP_WIND) {
             apply_wind_speed(&mut status, WindSpeed::Silent);
         }
@@ -436,7 +592,7 @@ impl fan_control::ClusterHandler for FanControlHandler {
             }
         }
         debug!("FanControl: wind_setting → {:?}", value);
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
@@ -444,8 +600,134 @@ impl fan_control::ClusterHandler for FanControlHandler {
     fn handle_step(
         &self,
         _ctx: impl InvokeContext,
-        _req: fan_control::StepRequest<'_>,
+        req: fan_control::StepRequest<'_>,
     ) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        let direction = req.direction()?;
+        let increase = direction == fan_control::StepDirectionEnum::Increase;
+        let wrap = req.wrap()?.unwrap_or(false);
+        let lowest_off = req.lowest_off()?.unwrap_or(true);
+
+        let base = self.device.get_status();
+        let mut status = base.clone();
+        let is_on = PowerState::from_status(&status) == Some(PowerState::On);
+        let auto_mode = status.mode.get_enum() == Some(Mode::Auto);
+        let (position, len) = if auto_mode {
+            let speed = status.wind.auto.speed.get_enum();
+            let position = AUTO_MODE_STEP_SPEEDS
+                .iter()
+                .position(|s| Some(*s) == speed)
+                .unwrap_or(1);
+            (position, AUTO_MODE_STEP_SPEEDS.len())
+        } else {
+            let speed = current_wind_speed(&status);
+            let position = STEP_SPEEDS
+                .iter()
+                .position(|s| Some(*s) == speed)
+                .unwrap_or(STEP_FROM_AUTO);
+            (position, STEP_SPEEDS.len())
+        };
+        let current = (is_on || !lowest_off).then_some(position);
+
+        let power = match step_position(current, len, increase, wrap, lowest_off) {
+            None => Some(PowerState::Off),
+            Some(next) => {
+                if auto_mode {
+                    status
+                        .wind
+                        .auto
+                        .speed
+                        .set_value(AUTO_MODE_STEP_SPEEDS[next]);
+                } else {
+                    apply_wind_speed(&mut status, STEP_SPEEDS[next]);
+                }
+                // Stepping up from off turns the unit on
+                current.is_none().then_some(PowerState::On)
+            }
+        };
+        if let Some(power) = power {
+            StateTransition::new()
+                .power(power)
+                .apply_to_status(&mut status)
+                .map_err(|e| {
+                    warn!("State transition failed: {e}");
+                    Error::from(ErrorCode::InvalidState)
+                })?;
+        }
+        debug!(
+            "FanControl: step {:?} (wrap={wrap}, lowest_off={lowest_off})",
+            direction
+        );
+        self.update(&base, &status)?;
+        self.dataver.changed();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every Step at both ends of a range of `len` speeds, and from off.
+    fn assert_ends(len: usize) {
+        let last = len - 1;
+        // (current, increase, wrap, lowest_off) → next position
+        let table = [
+            (None, true, false, false, Some(0)),
+            (None, true, false, true, Some(0)),
+            (None, true, true, false, Some(0)),
+            (None, true, true, true, Some(0)),
+            (None, false, false, false, None),
+            (None, false, false, true, None),
+            (None, false, true, false, Some(last)),
+            (None, false, true, true, Some(last)),
+            (Some(0), true, false, false, Some(1)),
+            (Some(0), true, false, true, Some(1)),
+            (Some(0), true, true, false, Some(1)),
+            (Some(0), true, true, true, Some(1)),
+            (Some(0), false, false, false, Some(0)),
+            (Some(0), false, false, true, None),
+            (Some(0), false, true, false, Some(last)),
+            (Some(0), false, true, true, None),
+            (Some(last), true, false, false, Some(last)),
+            (Some(last), true, false, true, Some(last)),
+            (Some(last), true, true, false, Some(0)),
+            (Some(last), true, true, true, None),
+            (Some(last), false, false, false, Some(last - 1)),
+            (Some(last), false, false, true, Some(last - 1)),
+            (Some(last), false, true, false, Some(last - 1)),
+            (Some(last), false, true, true, Some(last - 1)),
+        ];
+        for (current, increase, wrap, lowest_off, expected) in table {
+            assert_eq!(
+                step_position(current, len, increase, wrap, lowest_off),
+                expected,
+                "len={len} current={current:?} increase={increase} wrap={wrap} \
+                 lowest_off={lowest_off}"
+            );
+        }
+    }
+
+    #[test]
+    fn step_ends() {
+        assert_ends(STEP_SPEEDS.len());
+    }
+
+    #[test]
+    fn step_middle() {
+        for wrap in [false, true] {
+            for lowest_off in [false, true] {
+                let step = |current, increase| {
+                    step_position(Some(current), STEP_SPEEDS.len(), increase, wrap, lowest_off)
+                };
+                assert_eq!(step(STEP_FROM_AUTO, true), Some(STEP_FROM_AUTO + 1));
+                assert_eq!(step(STEP_FROM_AUTO, false), Some(STEP_FROM_AUTO - 1));
+            }
+        }
+    }
+
+    /// In Auto mode the two speeds are both ends of the range at once.
+    #[test]
+    fn step_auto_mode() {
+        assert_ends(AUTO_MODE_STEP_SPEEDS.len());
     }
 }
@@ -35,10 +35,7 @@ impl relative_humidity_measurement::ClusterHandler for HumidityHandler {
     }
 
     fn measured_value(&self, _ctx: impl ReadContext) -> Result<Nullable<u16>, Error> {
-        let status = self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(rs_matter::error::ErrorCode::Busy)
-        })?;
+        let status = self.device.get_status();
         match status.sensors.humidity.get_f32() {
             Some(h) => Ok(Nullable::some((h * 100.0) as u16)),
             None => Ok(Nullable::none()),
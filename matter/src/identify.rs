@@ -1,20 +1,98 @@
+use std::time::Duration;
+
+use dsiot::{ChangeSet, DaikinStatus, HorizontalDirection, VerticalDirection, WindSpeed};
 use rs_matter::dm::clusters::decl::identify;
 use rs_matter::dm::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
-use rs_matter::error::Error;
+use rs_matter::error::{Error, ErrorCode};
+
+use crate::device::Device;
+use crate::fan_control::{
+    allowed_directions, apply_directions, apply_wind_speed, current_directions,
+};
 
-pub(crate) struct StubIdentify {
+/// Identifies the unit by moving its louvers, or changing its fan speed,
+/// until IdentifyTime runs out, then puts them back. A unit that is off only
+/// beeps on the change.
+pub(crate) struct IdentifyHandler {
     pub(crate) dataver: Dataver,
+    device: Device,
 }
 
-impl StubIdentify {
+impl IdentifyHandler {
     pub(crate) const CLUSTER: Cluster<'static> = identify::FULL_CLUSTER.with_features(0);
 
-    pub(crate) fn new(dataver: Dataver) -> Self {
-        Self { dataver }
+    pub(crate) fn new(dataver: Dataver, device: Device) -> Self {
+        Self { dataver, device }
+    }
+
+    fn identify(&self, secs: u16) -> Result<(), Error> {
+        // Ending an Identify early needs no nudge
+        let nudge = if secs == 0 {
+            ChangeSet::new()
+        } else {
+            nudge(&self.device.get_status()).ok_or_else(|| {
+                warn!("Identify: no louver or fan setting to change");
+                Error::from(ErrorCode::InvalidState)
+            })?
+        };
+        self.device
+            .identify(Duration::from_secs(secs.into()), nudge)?;
+        self.dataver.changed();
+        Ok(())
+    }
+}
+
+/// A visible change to make while identifying: swing the louvers on an axis
+/// that is not swinging already, or stop the vertical swing if both are.
+/// Without louver directions the fan speed changes instead.
+///
+/// Returns `None` if the device allows none of these.
+fn nudge(status: &DaikinStatus) -> Option<ChangeSet> {
+    let mut candidates = Vec::new();
+    if let (Some(v), Some(h)) = current_directions(status) {
+        if v != VerticalDirection::Swing {
+            candidates.push((VerticalDirection::Swing, h));
+        }
+        if h != HorizontalDirection::Swing {
+            candidates.push((v, HorizontalDirection::Swing));
+        }
+        let (vertical, _) = allowed_directions(status);
+        if let Some(fixed) = vertical
+            .into_iter()
+            .find(|v| *v != VerticalDirection::Swing)
+        {
+            candidates.push((fixed, h));
+        }
+    }
+    let louvers = candidates.into_iter().map(|(v, h)| {
+        let mut nudged = status.clone();
+        apply_directions(&mut nudged, v, h);
+        nudged
+    });
+    let fan = [WindSpeed::Silent, WindSpeed::Auto].map(|speed| {
+        let mut nudged = status.clone();
+        apply_wind_speed(&mut nudged, speed);
+        nudged
+    });
+    louvers
+        .chain(fan)
+        .map(|nudged| ChangeSet::diff(status, &nudged))
+        .find(|changes| !changes.is_empty() && changes.disallowed(status).next().is_none())
+}
+
+/// How long to identify for a TriggerEffect, following the durations the
+/// spec gives for lights.
+fn effect_duration(effect: identify::EffectIdentifierEnum) -> u16 {
+    match effect {
+        identify::EffectIdentifierEnum::Blink | identify::EffectIdentifierEnum::Okay => 5,
+        identify::EffectIdentifierEnum::ChannelChange => 8,
+        identify::EffectIdentifierEnum::Breathe => 15,
+        identify::EffectIdentifierEnum::FinishEffect
+        | identify::EffectIdentifierEnum::StopEffect => 0,
     }
 }
 
-impl identify::ClusterHandler for StubIdentify {
+impl identify::ClusterHandler for IdentifyHandler {
     const CLUSTER: Cluster<'static> = Self::CLUSTER;
 
     fn dataver(&self) -> u32 {
@@ -25,31 +103,112 @@ impl identify::ClusterHandler for StubIdentify {
     }
 
     fn identify_time(&self, _ctx: impl ReadContext) -> Result<u16, Error> {
-        Ok(0)
+        let left = self.device.identify_remaining().unwrap_or_default();
+        Ok(left.as_millis().div_ceil(1000).min(u16::MAX.into()) as u16)
     }
 
     fn identify_type(&self, _ctx: impl ReadContext) -> Result<identify::IdentifyTypeEnum, Error> {
-        Ok(identify::IdentifyTypeEnum::None)
+        Ok(identify::IdentifyTypeEnum::Actuator)
     }
 
-    fn set_identify_time(&self, _ctx: impl WriteContext, _value: u16) -> Result<(), Error> {
-        Ok(())
+    fn set_identify_time(&self, _ctx: impl WriteContext, value: u16) -> Result<(), Error> {
+        self.identify(value)
     }
 
     fn handle_identify(
         &self,
         _ctx: impl InvokeContext,
-        _req: identify::IdentifyRequest<'_>,
+        req: identify::IdentifyRequest<'_>,
     ) -> Result<(), Error> {
-        info!("Identify requested");
-        Ok(())
+        let secs = req.identify_time()?;
+        info!("Identify requested for {secs}s");
+        self.identify(secs)
     }
 
     fn handle_trigger_effect(
         &self,
         _ctx: impl InvokeContext,
-        _req: identify::TriggerEffectRequest<'_>,
+        req: identify::TriggerEffectRequest<'_>,
     ) -> Result<(), Error> {
-        Ok(())
+        let effect = req.effect_identifier()?;
+        info!("Identify effect {effect:?} requested");
+        self.identify(effect_duration(effect))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dsiot::{DaikinResponse, Mode, PropValue};
+
+    /// The fixture in cooling mode with the louvers at `v` and `h`.
+    fn cooling(v: VerticalDirection, h: HorizontalDirection) -> DaikinStatus {
+        let res: DaikinResponse =
+            serde_json::from_str(include_str!("../../dsiot/src/fixtures/status.json"))
+                .expect("Invalid JSON file.");
+        let mut status: DaikinStatus = res.into();
+        status.mode.set_value(Mode::Cooling);
+        apply_directions(&mut status, v, h);
+        status
+    }
+
+    fn nudged(status: &DaikinStatus) -> DaikinStatus {
+        let mut nudged = status.clone();
+        nudge(status).expect("no nudge").apply_to(&mut nudged);
+        nudged
+    }
+
+    #[test]
+    fn nudge_swings() {
+        let status = cooling(VerticalDirection::Auto, HorizontalDirection::Auto);
+        assert_eq!(
+            current_directions(&nudged(&status)),
+            (
+                Some(VerticalDirection::Swing),
+                Some(HorizontalDirection::Auto)
+            )
+        );
+
+        let status = cooling(VerticalDirection::Swing, HorizontalDirection::Auto);
+        assert_eq!(
+            current_directions(&nudged(&status)),
+            (
+                Some(VerticalDirection::Swing),
+                Some(HorizontalDirection::Swing)
+            )
+        );
+    }
+
+    #[test]
+    fn nudge_stops_swing() {
+        let status = cooling(VerticalDirection::Swing, HorizontalDirection::Swing);
+        let (v, h) = current_directions(&nudged(&status));
+        assert_ne!(v, Some(VerticalDirection::Swing));
+        assert!(v.is_some_and(|v| allowed_directions(&status).0.contains(&v)));
+        assert_eq!(h, Some(HorizontalDirection::Swing));
+    }
+
+    #[test]
+    fn nudge_fan_speed() {
+        let mut status = cooling(VerticalDirection::Auto, HorizontalDirection::Auto);
+        status.wind.cooling.vertical_direction.value = PropValue::Null;
+        status.wind.cooling.speed.set_value(WindSpeed::Lev2);
+        let changed = nudged(&status);
+        assert_eq!(current_directions(&changed).0, None);
+        assert_eq!(
+            changed.wind.cooling.speed.get_enum(),
+            Some(WindSpeed::Silent)
+        );
+
+        status.wind.cooling.speed.set_value(WindSpeed::Silent);
+        let changed = nudged(&status);
+        assert_eq!(changed.wind.cooling.speed.get_enum(), Some(WindSpeed::Auto));
+    }
+
+    #[test]
+    fn nudge_impossible() {
+        let mut status = cooling(VerticalDirection::Auto, HorizontalDirection::Auto);
+        status.mode.set_value(Mode::Unknown);
+        assert_eq!(nudge(&status), None);
     }
 }
@@ -0,0 +1,207 @@
+use dsiot::{ChangeSet, HorizontalDirection, VerticalDirection};
+use rs_matter::dm::clusters::decl::mode_select;
+use rs_matter::dm::{ArrayAttributeRead, Cluster, Dataver, DeviceType, InvokeContext, ReadContext};
+use rs_matter::error::{Error, ErrorCode};
+use rs_matter::tlv::{Nullable, TLVBuilderParent, Utf8StrBuilder};
+use rs_matter::with;
+
+use crate::device::Device;
+use crate::fan_control::{allowed_directions, apply_directions, current_directions};
+
+pub(crate) const DEV_TYPE_MODE_SELECT: DeviceType = DeviceType {
+    dtype: 0x0027,
+    drev: 1,
+};
+
+/// Louver whose position an endpoint controls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Axis {
+    Vertical,
+    Horizontal,
+}
+
+/// A louver position offered as a mode, keyed by its Daikin value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Position {
+    mode: u8,
+    label: &'static str,
+}
+
+fn vertical_label(direction: VerticalDirection) -> &'static str {
+    match direction {
+        VerticalDirection::TopMost => "Top most",
+        VerticalDirection::Top => "Top",
+        VerticalDirection::Center => "Center",
+        VerticalDirection::Bottom => "Bottom",
+        VerticalDirection::BottomMost => "Bottom most",
+        VerticalDirection::Swing => "Swing",
+        VerticalDirection::Auto => "Auto",
+        VerticalDirection::Nice => "Comfort airflow",
+        VerticalDirection::Unknown => "Unknown",
+    }
+}
+
+fn horizontal_label(direction: HorizontalDirection) -> &'static str {
+    match direction {
+        HorizontalDirection::LeftMost => "Left most",
+        HorizontalDirection::Left => "Left",
+        HorizontalDirection::LeftCenter => "Left center",
+        HorizontalDirection::Center => "Center",
+        HorizontalDirection::RightCenter => "Right center",
+        HorizontalDirection::Right => "Right",
+        HorizontalDirection::RightMost => "Right most",
+        HorizontalDirection::Swing => "Swing",
+        HorizontalDirection::Auto => "Auto",
+        HorizontalDirection::Unknown => "Unknown",
+    }
+}
+
+/// Mode Select cluster for the position of one louver, offering the
+/// directions the device allows in its current operating mode.
+pub(crate) struct LouverHandler {
+    pub(crate) dataver: Dataver,
+    device: Device,
+    axis: Axis,
+}
+
+impl LouverHandler {
+    pub const CLUSTER: Cluster<'static> = mode_select::FULL_CLUSTER
+        .with_revision(2)
+        .with_features(0)
+        .with_attrs(with!(required))
+        .with_cmds(with!(mode_select::CommandId::ChangeToMode));
+
+    pub fn new(dataver: Dataver, device: Device, axis: Axis) -> Self {
+        Self {
+            dataver,
+            device,
+            axis,
+        }
+    }
+
+    fn positions(&self) -> Vec<Position> {
+        let (vertical, horizontal) = allowed_directions(&self.device.get_status());
+        match self.axis {
+            Axis::Vertical => vertical
+                .into_iter()
+                .map(|d| Position {
+                    mode: d as u8,
+                    label: vertical_label(d),
+                })
+                .collect(),
+            Axis::Horizontal => horizontal
+                .into_iter()
+                .map(|d| Position {
+                    mode: d as u8,
+                    label: horizontal_label(d),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Build a Mode Select option without semantic tags.
+pub(crate) fn build_mode_option<P: TLVBuilderParent>(
+    builder: mode_select::ModeOptionStructBuilder<P>,
+    mode: u8,
+    label: &str,
+) -> Result<P, Error> {
+    builder
+        .label(label)?
+        .mode(mode)?
+        .semantic_tags()?
+        .end()?
+        .end()
+}
+
+impl mode_select::ClusterHandler for LouverHandler {
+    const CLUSTER: Cluster<'static> = Self::CLUSTER;
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn description<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: Utf8StrBuilder<P>,
+    ) -> Result<P, Error> {
+        builder.set(match self.axis {
+            Axis::Vertical => "Vertical louver",
+            Axis::Horizontal => "Horizontal louver",
+        })
+    }
+
+    fn standard_namespace(&self, _ctx: impl ReadContext) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::none())
+    }
+
+    fn supported_modes<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: ArrayAttributeRead<
+            mode_select::ModeOptionStructArrayBuilder<P>,
+            mode_select::ModeOptionStructBuilder<P>,
+        >,
+    ) -> Result<P, Error> {
+        let positions = self.positions();
+        match builder {
+            ArrayAttributeRead::ReadAll(mut array) => {
+                for position in positions {
+                    array = build_mode_option(array.push()?, position.mode, position.label)?;
+                }
+                array.end()
+            }
+            ArrayAttributeRead::ReadOne(index, elem) => match positions.get(index as usize) {
+                Some(position) => build_mode_option(elem, position.mode, position.label),
+                None => Err(ErrorCode::ConstraintError.into()),
+            },
+            ArrayAttributeRead::ReadNone(array) => array.end(),
+        }
+    }
+
+    fn current_mode(&self, _ctx: impl ReadContext) -> Result<u8, Error> {
+        let (vertical, horizontal) = current_directions(&self.device.get_status());
+        let mode = match self.axis {
+            Axis::Vertical => vertical.map(|d| d as u8),
+            Axis::Horizontal => horizontal.map(|d| d as u8),
+        };
+        Ok(mode.unwrap_or(VerticalDirection::Auto as u8))
+    }
+
+    fn handle_change_to_mode(
+        &self,
+        _ctx: impl InvokeContext,
+        request: mode_select::ChangeToModeRequest<'_>,
+    ) -> Result<(), Error> {
+        let new_mode = request.new_mode()?;
+        let base = self.device.get_status();
+        let (allowed_vertical, allowed_horizontal) = allowed_directions(&base);
+        let (vertical, horizontal) = current_directions(&base);
+        let mut vertical = vertical.unwrap_or(VerticalDirection::Auto);
+        let mut horizontal = horizontal.unwrap_or(HorizontalDirection::Auto);
+        match self.axis {
+            Axis::Vertical => {
+                vertical = allowed_vertical
+                    .into_iter()
+                    .find(|d| *d as u8 == new_mode)
+                    .ok_or(Error::from(ErrorCode::ConstraintError))?;
+            }
+            Axis::Horizontal => {
+                horizontal = allowed_horizontal
+                    .into_iter()
+                    .find(|d| *d as u8 == new_mode)
+                    .ok_or(Error::from(ErrorCode::ConstraintError))?;
+            }
+        }
+        let mut status = base.clone();
+        apply_directions(&mut status, vertical, horizontal);
+        debug!("ModeSelect: {:?} louver → {new_mode:#04x}", self.axis);
+        self.device.update(ChangeSet::diff(&base, &status))?;
+        self.dataver.changed();
+        Ok(())
+    }
+}
@@ -3,27 +3,40 @@ extern crate log;
 
 mod bridge;
 mod bridged_info;
+mod config;
 mod device;
+mod endpoints;
 mod fan_control;
 mod humidity;
 mod identify;
+mod louver;
 mod onoff;
+mod operating_mode;
+mod outdoor;
 mod power;
+mod presets;
+mod schedules;
+mod store;
+mod tags;
 mod thermostat;
 mod wifi_diag;
 
 use core::pin::pin;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::net::{Ipv4Addr, UdpSocket};
 use std::path::PathBuf;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use clap::Parser;
 use daikin_client::{Daikin, ReqwestClient, discovery};
-use dsiot::DaikinInfo;
+use dsiot::{Capabilities, DaikinInfo, DaikinStatus};
 use futures_lite::StreamExt;
+use jiff::Zoned;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-use embassy_futures::select::{select, select4};
+use embassy_futures::select::{Either3, select, select3, select4};
 use static_cell::StaticCell;
 
 use rs_matter::crypto::{Crypto, CryptoSensitive, CryptoSensitiveRef, default_crypto};
@@ -36,9 +49,7 @@ use rs_matter::dm::events::NoEvents;
 use rs_matter::dm::networks::eth::EthNetwork;
 use rs_matter::dm::networks::unix::UnixNetifs;
 use rs_matter::dm::subscriptions::Subscriptions;
-use rs_matter::dm::{
-    Async, DataModel, Dataver, EmptyHandler, EpClMatcher, IMBuffer, Node, endpoints,
-};
+use rs_matter::dm::{Async, DataModel, Dataver, EmptyHandler, EpClMatcher, IMBuffer};
 use rs_matter::error::Error;
 use rs_matter::pairing::{DiscoveryCapabilities, qr::QrTextType};
 use rs_matter::persist::{DirKvBlobStore, SharedKvBlobStore};
@@ -50,7 +61,12 @@ use rs_matter::utils::select::Coalesce;
 use rs_matter::utils::storage::pooled::PooledBuffers;
 use rs_matter::{MATTER_PORT, Matter};
 
-use bridge::{BridgeHandler, BridgedMatcher};
+use bridge::{BridgeHandler, BridgedMatcher, DynamicNode, PartKind, Stores};
+use config::{Config, DeviceConfig};
+use device::PollConfig;
+use endpoints::EndpointMap;
+use presets::PresetStore;
+use schedules::ScheduleStore;
 
 static MATTER: StaticCell<Matter> = StaticCell::new();
 static BUFFERS: StaticCell<PooledBuffers<10, IMBuffer>> = StaticCell::new();
@@ -78,16 +94,37 @@ const BRIDGE_DEV_DET: BasicInfoConfig<'static> = BasicInfoConfig {
     ..BasicInfoConfig::new()
 };
 
+/// Descriptors of the root and aggregator endpoints, whose PartsList
+/// changes as bridged devices come and go.
+struct NodeDescs {
+    root: desc::HandlerAdaptor<desc::DescHandler<'static>>,
+    aggregator: desc::HandlerAdaptor<desc::DescHandler<'static>>,
+}
+
+impl NodeDescs {
+    fn new(rand: &mut impl rand::RngCore) -> Self {
+        Self {
+            root: desc::DescHandler::new(Dataver::new_rand(rand)).adapt(),
+            aggregator: desc::DescHandler::new_aggregator(Dataver::new_rand(rand)).adapt(),
+        }
+    }
+
+    /// Bump the Datavers after a bridged endpoint was added or removed.
+    fn parts_changed(&self) {
+        self.root.0.dataver_changed();
+        self.aggregator.0.dataver_changed();
+    }
+}
+
 fn dm_handler<'a>(
-    mut rand: impl rand::RngCore,
+    rand: impl rand::RngCore,
     bridge: &'a BridgeHandler,
-    node: Node<'static>,
+    node: &'a DynamicNode,
+    descs: &'a NodeDescs,
 ) -> impl rs_matter::dm::AsyncMetadata + rs_matter::dm::AsyncHandler + 'a {
-    let agg_desc_dataver = Dataver::new_rand(&mut rand);
-
     (
         node,
-        endpoints::with_eth_sys(
+        rs_matter::dm::endpoints::with_eth_sys(
             &false,
             &(),
             &UnixNetifs,
@@ -95,9 +132,15 @@ fn dm_handler<'a>(
             EmptyHandler
                 .chain(
                     EpClMatcher::new(Some(1), Some(desc::DescHandler::CLUSTER.id)),
-                    Async(desc::DescHandler::new_aggregator(agg_desc_dataver).adapt()),
+                    Async(&descs.aggregator),
                 )
                 .chain(BridgedMatcher, Async(bridge)),
+        )
+        // Matched ahead of the root Descriptor of `with_eth_sys`, so that
+        // its Dataver can be bumped
+        .chain(
+            EpClMatcher::new(Some(0), Some(desc::DescHandler::CLUSTER.id)),
+            Async(&descs.root),
         ),
     )
 }
@@ -209,17 +252,156 @@ struct Cli {
     #[arg(value_name = "ip_address")]
     ip_addrs: Vec<Ipv4Addr>,
 
-    /// Discovery timeout in milliseconds
-    #[arg(long, default_value = "3000")]
-    timeout: u64,
+    /// TOML configuration file. Command line options take precedence
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Discovery timeout in milliseconds [default: 3000]
+    #[arg(long)]
+    timeout: Option<u64>,
 
     /// Expected number of devices to discover
-    #[arg(long, default_value = "128", hide_default_value = true)]
-    count: usize,
+    #[arg(long)]
+    count: Option<usize>,
 
     /// Directory to store persistent data (pairing, fabrics, etc.)
     #[arg(long, value_name = "DIR")]
     data_dir: Option<PathBuf>,
+
+    /// Interval between status polls in seconds [default: 30]
+    #[arg(long, value_name = "SECS")]
+    poll_interval: Option<u64>,
+
+    /// Interval between status polls in seconds for a minute after a change [default: 5]
+    #[arg(long, value_name = "SECS")]
+    fast_poll_interval: Option<u64>,
+
+    /// Interval in seconds to look for devices that appear after startup, 0 to disable [default: 300]
+    #[arg(long, value_name = "SECS")]
+    discovery_interval: Option<u64>,
+
+    /// Remove a device after it has been unreachable for this many seconds
+    #[arg(long, value_name = "SECS")]
+    remove_after: Option<u64>,
+}
+
+/// A connected device and its settings.
+struct Connection {
+    dk: Daikin<ReqwestClient>,
+    info: DaikinInfo,
+    status: DaikinStatus,
+    config: DeviceConfig,
+}
+
+impl Connection {
+    async fn new(dk: Daikin<ReqwestClient>, config: DeviceConfig) -> anyhow::Result<Self> {
+        let mut info = dk.get_info().await?;
+        info!(
+            "Device: {} (MAC: {}, EDID: {})",
+            info.name, info.mac, info.edid
+        );
+        if let Some(name) = &config.name {
+            info.name = name.clone();
+        }
+        let status = dk.get_status().await?;
+        if status.indoor_failed() {
+            // Capabilities cannot be told from a status without settings
+            anyhow::bail!("Indoor unit of {} did not answer", info.name);
+        }
+        debug!("Status: {:?}", status);
+        Ok(Self {
+            dk,
+            info,
+            status,
+            config,
+        })
+    }
+}
+
+/// Settings for a discovered device, or `None` if it is not to be bridged.
+fn target_for(targets: &[DeviceConfig], mac: &str) -> Option<DeviceConfig> {
+    if targets.is_empty() {
+        Some(DeviceConfig::default())
+    } else {
+        targets.iter().find(|t| t.matches_mac(mac)).cloned()
+    }
+}
+
+/// Look for devices that appear after startup every `interval`: configured
+/// IP addresses that did not answer yet and newly discovered devices.
+async fn rediscover(
+    targets: Vec<DeviceConfig>,
+    mut known_ips: HashMap<Ipv4Addr, String>,
+    bridged: Arc<Mutex<HashSet<String>>>,
+    interval: Duration,
+    timeout: Duration,
+    added: UnboundedSender<Connection>,
+) {
+    let is_bridged = |mac: &str| bridged.lock().expect("bridged lock poisoned").contains(mac);
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let mut found: Vec<Connection> = Vec::new();
+        for target in &targets {
+            let Some(ip) = target.ip else {
+                continue;
+            };
+            if known_ips.get(&ip).is_some_and(|mac| is_bridged(mac)) {
+                continue;
+            }
+            let client = match ReqwestClient::try_new() {
+                Ok(client) => client,
+                Err(e) => {
+                    warn!("Failed to create HTTP client: {e}");
+                    continue;
+                }
+            };
+            match Connection::new(Daikin::new(ip, client), target.clone()).await {
+                Ok(conn) => {
+                    known_ips.insert(ip, conn.info.mac.clone());
+                    if !is_bridged(&conn.info.mac) {
+                        found.push(conn);
+                    }
+                }
+                Err(e) => debug!("Device {ip} is not reachable yet: {e}"),
+            }
+        }
+
+        if targets.is_empty() || targets.iter().any(|t| t.mac.is_some()) {
+            let stream = discovery(timeout).await;
+            let mut stream = pin!(stream);
+            while let Some(result) = stream.next().await {
+                let (dk, udp_info) = match result {
+                    Ok(device) => device,
+                    Err(e) => {
+                        debug!("Discovery error: {e}");
+                        continue;
+                    }
+                };
+                if is_bridged(&udp_info.mac) || found.iter().any(|c| c.info.mac == udp_info.mac) {
+                    continue;
+                }
+                let Some(config) = target_for(&targets, &udp_info.mac) else {
+                    continue;
+                };
+                match Connection::new(dk, config).await {
+                    Ok(conn) => found.push(conn),
+                    Err(e) => warn!("Failed to connect to {}: {e}", udp_info.name),
+                }
+            }
+        }
+
+        for conn in found {
+            info!("New device: {} (MAC: {})", conn.info.name, conn.info.mac);
+            bridged
+                .lock()
+                .expect("bridged lock poisoned")
+                .insert(conn.info.mac.clone());
+            if added.send(conn).is_err() {
+                return;
+            }
+        }
+    }
 }
 
 fn default_data_dir() -> PathBuf {
@@ -235,69 +417,235 @@ fn main() -> anyhow::Result<()> {
     .init();
 
     let cli = Cli::parse();
+    let config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+
+    // Positional IPs replace the device list, keeping settings listed for them
+    let targets: Vec<DeviceConfig> = if cli.ip_addrs.is_empty() {
+        config.devices.clone()
+    } else {
+        cli.ip_addrs
+            .iter()
+            .map(|ip| {
+                config
+                    .device_by_ip(*ip)
+                    .cloned()
+                    .unwrap_or_else(|| DeviceConfig::with_ip(*ip))
+            })
+            .collect()
+    };
+    let timeout = cli.timeout.or(config.discovery.timeout).unwrap_or(3000);
+    let count = cli.count.or(config.discovery.count);
+    let discovery_interval = cli
+        .discovery_interval
+        .or(config.discovery.interval)
+        .unwrap_or(300);
+    let rediscovery = discovery_interval > 0;
+    let remove_after = cli
+        .remove_after
+        .or(config.discovery.remove_after)
+        .map(|secs| Duration::from_secs(secs.max(1)));
 
     let rt = tokio::runtime::Runtime::new()?;
-    let connections: Vec<(Daikin<ReqwestClient>, DaikinInfo)> = rt.block_on(async {
+    let connections: Vec<Connection> = rt.block_on(async {
         let mut conns = Vec::new();
-        if cli.ip_addrs.is_empty() {
-            info!("No IP addresses specified, discovering devices...");
-            let timeout = Duration::from_millis(cli.timeout);
-            let stream = discovery(timeout).await;
+        for target in &targets {
+            if let Some(ip) = target.ip {
+                let dk = Daikin::new(ip, ReqwestClient::try_new()?);
+                match Connection::new(dk, target.clone()).await {
+                    Ok(conn) => conns.push(conn),
+                    // Bridged once it answers
+                    Err(e) if rediscovery => warn!("Device {ip} is not reachable: {e}"),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        let by_mac: Vec<&DeviceConfig> = targets.iter().filter(|t| t.mac.is_some()).collect();
+        if targets.is_empty() || !by_mac.is_empty() {
+            info!("Discovering devices...");
+            let stream = discovery(Duration::from_millis(timeout)).await;
             let mut stream = pin!(stream);
             while let Some(result) = stream.next().await {
                 match result {
                     Ok((dk, _udp_info)) => {
                         let info = dk.get_info().await?;
-                        debug!("Status: {:?}", dk.get_status().await?);
-                        conns.push((dk, info));
-                        if conns.len() >= cli.count {
+                        if conns.iter().any(|c: &Connection| c.info.mac == info.mac) {
+                            continue;
+                        }
+                        let Some(config) = target_for(&targets, &info.mac) else {
+                            debug!("Skipping {} (MAC: {}): not configured", info.name, info.mac);
+                            continue;
+                        };
+                        conns.push(Connection::new(dk, config).await?);
+                        let done = if targets.is_empty() {
+                            conns.len() >= count.unwrap_or(usize::MAX)
+                        } else {
+                            by_mac
+                                .iter()
+                                .all(|t| conns.iter().any(|c| t.matches_mac(&c.info.mac)))
+                        };
+                        if done {
                             break;
                         }
                     }
                     Err(e) => warn!("Discovery error: {e}"),
                 }
             }
+            for t in &by_mac {
+                if !conns.iter().any(|c| t.matches_mac(&c.info.mac)) {
+                    warn!("Device {} not found", t.mac.as_deref().unwrap_or_default());
+                }
+            }
+        }
+        if rediscovery {
+            if conns.is_empty() {
+                warn!("No devices found yet, looking again every {discovery_interval}s");
+            }
         } else {
-            for ip in &cli.ip_addrs {
-                let dk = Daikin::new(*ip, ReqwestClient::try_new()?);
-                let info = dk.get_info().await?;
-                info!(
-                    "Device: {} (MAC: {}, EDID: {})",
-                    info.name, info.mac, info.edid
+            if conns.is_empty() {
+                anyhow::bail!("No devices found");
+            }
+            if let Some(count) = count
+                && conns.len() < count
+            {
+                anyhow::bail!(
+                    "Found only {} devices, but requested {}",
+                    conns.len(),
+                    count
                 );
-                let status = dk.get_status().await?;
-                debug!("Status: {:?}", status);
-                conns.push((dk, info));
             }
         }
-        if conns.is_empty() {
-            anyhow::bail!("No devices found");
-        }
-        if cli.count != 128 && conns.len() < cli.count {
-            anyhow::bail!(
-                "Found only {} devices, but requested {}",
-                conns.len(),
-                cli.count
-            );
-        }
         anyhow::Ok(conns)
     })?;
 
+    let bridged = Arc::new(Mutex::new(
+        connections
+            .iter()
+            .map(|c| c.info.mac.clone())
+            .collect::<HashSet<_>>(),
+    ));
+    let (added_tx, added_rx) = tokio::sync::mpsc::unbounded_channel();
+    if rediscovery {
+        let known_ips = connections
+            .iter()
+            .filter_map(|c| Some((c.config.ip?, c.info.mac.clone())))
+            .collect();
+        rt.spawn(rediscover(
+            targets,
+            known_ips,
+            bridged.clone(),
+            Duration::from_secs(discovery_interval),
+            Duration::from_millis(timeout),
+            added_tx,
+        ));
+    }
+
     let rt_handle = rt.handle().clone();
-    let data_dir = cli.data_dir.unwrap_or_else(default_data_dir);
+    let data_dir = cli
+        .data_dir
+        .or(config.data_dir)
+        .unwrap_or_else(default_data_dir);
     info!("Data directory: {}", data_dir.display());
+    let defaults = PollConfig::default();
+    let poll = PollConfig {
+        interval: cli
+            .poll_interval
+            .or(config.poll.interval)
+            .map_or(defaults.interval, |secs| Duration::from_secs(secs.max(1))),
+        fast_interval: cli
+            .fast_poll_interval
+            .or(config.poll.fast_interval)
+            .map_or(defaults.fast_interval, |secs| {
+                Duration::from_secs(secs.max(1))
+            }),
+        fast_window: config
+            .poll
+            .fast_window
+            .map_or(defaults.fast_window, Duration::from_secs),
+        max_backoff: config
+            .poll
+            .max_backoff
+            .map_or(defaults.max_backoff, Duration::from_secs),
+    };
 
     let thread = std::thread::Builder::new()
         .stack_size(1024 * 1024)
-        .spawn(move || run_matter(connections, rt_handle, data_dir))
+        .spawn(move || {
+            run_matter(
+                connections,
+                added_rx,
+                rt_handle,
+                poll,
+                remove_after,
+                bridged,
+                data_dir,
+            )
+        })
         .unwrap();
 
     thread.join().unwrap()
 }
 
+/// What bridging a device needs besides the device itself.
+struct BridgeContext<'a> {
+    rt: &'a tokio::runtime::Handle,
+    poll: PollConfig,
+    changed: &'a UnboundedSender<u16>,
+    stores: &'a Stores,
+    node: &'a DynamicNode,
+}
+
+/// Start polling a connected device and build its bridged endpoint and
+/// parts, assigning endpoint IDs to the parts in `endpoint_map`.
+fn bridge_device(
+    conn: Connection,
+    ep_id: u16,
+    rand: &mut impl rand::RngCore,
+    ctx: &BridgeContext,
+    endpoint_map: &mut EndpointMap,
+) -> bridge::BridgedDevice {
+    let Connection {
+        dk,
+        info,
+        status,
+        config,
+    } = conn;
+    let poll = PollConfig {
+        interval: config
+            .poll_interval
+            .map_or(ctx.poll.interval, Duration::from_secs),
+        ..ctx.poll
+    };
+    let caps = Capabilities::from_status(&status);
+    debug!("Endpoint {ep_id} capabilities: {caps:?}");
+    let kinds = PartKind::for_device(&caps, &config);
+    let roles = kinds.iter().map(|k| k.role()).collect::<Vec<_>>();
+    let parts = kinds
+        .into_iter()
+        .zip(endpoint_map.assign_parts(ep_id, &roles))
+        .collect::<Vec<_>>();
+    let device = device::Device::spawn(dk, status, ctx.rt, poll, ep_id, ctx.changed.clone());
+    info!(
+        "Bridged endpoint {ep_id}: {} (power: {})",
+        info.name, info.en_ipower
+    );
+    bridge::BridgedDevice::new(ep_id, rand, device, info, caps, &config, ctx.stores)
+        .with_parts(&parts, rand, ctx.node)
+}
+
+/// How often weekly schedules are checked for transitions that are due.
+const SCHEDULE_TICK: Duration = Duration::from_secs(30);
+
 fn run_matter(
-    connections: Vec<(Daikin<ReqwestClient>, DaikinInfo)>,
+    connections: Vec<Connection>,
+    mut added_rx: UnboundedReceiver<Connection>,
     rt_handle: tokio::runtime::Handle,
+    poll: PollConfig,
+    remove_after: Option<Duration>,
+    bridged: Arc<Mutex<HashSet<String>>>,
     data_dir: PathBuf,
 ) -> anyhow::Result<()> {
     let matter = MATTER.uninit().init_with(Matter::init(
@@ -311,6 +659,18 @@ fn run_matter(
     matter.initialize_transport_buffers();
 
     let kv_buf = KV_BUF.uninit().init_zeroed().as_mut_slice();
+    let mut endpoint_map = EndpointMap::load(&data_dir)?;
+    let ep_ids = endpoint_map.assign(
+        &connections
+            .iter()
+            .map(|c| (&c.info, c.config.endpoint))
+            .collect::<Vec<_>>(),
+    );
+    let stores = Stores {
+        presets: Arc::new(PresetStore::load(&data_dir)?),
+        schedules: Arc::new(ScheduleStore::load(&data_dir)?),
+    };
+
     let mut kv = DirKvBlobStore::new(data_dir);
     futures_lite::future::block_on(matter.load_persist(&mut kv, kv_buf))?;
 
@@ -319,39 +679,30 @@ fn run_matter(
 
     let crypto = default_crypto(rand::thread_rng(), TEST_DEV_ATT.dac_priv_key());
     let mut rand = crypto.rand()?;
-
+    let mut dev_rand = crypto.rand()?;
+
+    let (changed_tx, mut changed_rx) = tokio::sync::mpsc::unbounded_channel();
+    let node = DynamicNode::new();
+    let ctx = BridgeContext {
+        rt: &rt_handle,
+        poll,
+        changed: &changed_tx,
+        stores: &stores,
+        node: &node,
+    };
     let mut devices = Vec::with_capacity(connections.len());
-    for (dk, info) in connections {
-        let ep_id = (info.edid & 0xFFFF) as u16;
-        assert!(
-            ep_id >= 2,
-            "edid-derived endpoint ID {ep_id} conflicts with root/aggregator"
-        );
-        let device = device::Device::new(dk, rt_handle.clone());
-        let bridged_info =
-            bridged_info::BridgedInfo::new(Dataver::new_rand(&mut rand), &info, device.clone());
-        info!(
-            "Bridged endpoint {ep_id}: {} (power: {})",
-            info.name, info.en_ipower
-        );
-        devices.push(bridge::BridgedDevice::new(
-            ep_id,
-            &mut rand,
-            bridged_info,
-            device,
-            info,
-        ));
+    for (conn, ep_id) in connections.into_iter().zip(ep_ids) {
+        let dev = bridge_device(conn, ep_id, &mut rand, &ctx, &mut endpoint_map);
+        node.add(&dev.endpoints())?;
+        devices.push(dev);
     }
-    let ep_devs: Vec<(u16, bool)> = devices
-        .iter()
-        .map(|d| (d.ep_id, d.power.is_some()))
-        .collect();
+    endpoint_map.save()?;
     let bridge_handler = BridgeHandler {
-        devices,
+        devices: RefCell::new(devices),
         subscriptions,
     };
-    let node = bridge::build_node(&ep_devs);
 
+    let descs = NodeDescs::new(&mut rand);
     let events = NoEvents::new_default();
 
     let dm = DataModel::new(
@@ -360,7 +711,7 @@ fn run_matter(
         buffers,
         subscriptions,
         &events,
-        dm_handler(rand, &bridge_handler, node),
+        dm_handler(rand, &bridge_handler, &node, &descs),
         SharedKvBlobStore::new(kv, kv_buf),
         SharedNetworks::new(EthNetwork::new_default()),
     );
@@ -380,82 +731,180 @@ fn run_matter(
         matter.open_basic_comm_window(MAX_COMM_WINDOW_TIMEOUT_SECS, &crypto, dm.change_notify())?;
     }
 
-    info!("Matter stack running ({} device(s))", ep_devs.len());
+    info!(
+        "Matter stack running ({} device(s))",
+        bridge_handler.devices.borrow().len()
+    );
 
     let notifier = dm.change_notify();
+    // The root and aggregator list the bridged endpoints in their PartsList
+    let notify_parts_list = || {
+        descs.parts_changed();
+        for ep in [0, 1] {
+            notifier.notify_attr_changed(ep, desc::DescHandler::CLUSTER.id, 0);
+        }
+    };
     let mut poll = pin!(async {
         let mut was_reachable: HashMap<u16, bool> = HashMap::new();
-        let mut prev: HashMap<u16, dsiot::DaikinStatus> = HashMap::new();
+        let mut prev: HashMap<u16, DaikinStatus> = HashMap::new();
+        let mut identifying: HashSet<u16> = HashSet::new();
+        let mut schedule_checked = Zoned::now();
+        let mut next_tick = Instant::now() + SCHEDULE_TICK;
         loop {
-            async_io::Timer::after(Duration::from_secs(30)).await;
-            for dev in &bridge_handler.devices {
+            let added = async {
+                match added_rx.recv().await {
+                    Some(conn) => conn,
+                    // Rediscovery is disabled
+                    None => core::future::pending().await,
+                }
+            };
+            let tick = async_io::Timer::at(next_tick);
+            let ep_id = match select3(changed_rx.recv(), added, tick).await {
+                Either3::First(Some(ep_id)) => ep_id,
+                Either3::First(None) => break,
+                Either3::Second(conn) => {
+                    let in_use = |ep| {
+                        bridge_handler
+                            .devices
+                            .borrow()
+                            .iter()
+                            .any(|d| d.ep_ids().contains(&ep))
+                    };
+                    if let Some(ep) = conn.config.endpoint
+                        && in_use(ep)
+                    {
+                        warn!(
+                            "Endpoint {ep} for {} is in use, not bridging it",
+                            conn.info.name
+                        );
+                        bridged
+                            .lock()
+                            .expect("bridged lock poisoned")
+                            .remove(&conn.info.mac);
+                        continue;
+                    }
+                    let ep_id = endpoint_map.assign(&[(&conn.info, conn.config.endpoint)])[0];
+                    let dev = bridge_device(conn, ep_id, &mut dev_rand, &ctx, &mut endpoint_map);
+                    if let Err(e) = endpoint_map.save() {
+                        warn!("Failed to save endpoint IDs: {e}");
+                    }
+                    let endpoints = dev.endpoints();
+                    bridge_handler.devices.borrow_mut().push(dev);
+                    while node.add(&endpoints).is_err() {
+                        // The node is being read; try again shortly
+                        async_io::Timer::after(Duration::from_millis(10)).await;
+                    }
+                    notify_parts_list();
+                    continue;
+                }
+                Either3::Third(_) => {
+                    next_tick = Instant::now() + SCHEDULE_TICK;
+                    // Run what fell due since the last check, in local time
+                    let now = Zoned::now();
+                    for dev in bridge_handler.devices.borrow().iter() {
+                        dev.therm.run_schedule(&schedule_checked, &now);
+                    }
+                    schedule_checked = now;
+                    continue;
+                }
+            };
+
+            let mut vanished: Option<(String, Vec<u16>)> = None;
+            for dev in bridge_handler
+                .devices
+                .borrow()
+                .iter()
+                .filter(|d| d.ep_id == ep_id)
+            {
                 let reachable_before = was_reachable.get(&dev.ep_id).copied().unwrap_or(true);
-                match dev.device.get_status() {
-                    Ok(status) => {
-                        let old = prev.get(&dev.ep_id);
-                        let mut changed = Vec::new();
-                        if old.is_none_or(|o| o.power != status.power || o.mode != status.mode) {
-                            dev.on_off.dataver.changed();
-                            notifier.notify_attr_changed(
-                                dev.ep_id,
-                                onoff::OnOffHandler::CLUSTER.id,
-                                0,
-                            );
-                            changed.push("OnOff");
-                        }
-                        if old.is_none_or(|o| {
-                            o.mode != status.mode
-                                || o.temperature != status.temperature
-                                || o.sensors.temperature != status.sensors.temperature
-                                || o.sensors.outdoor_temperature
-                                    != status.sensors.outdoor_temperature
-                        }) {
-                            dev.therm.dataver.changed();
-                            notifier.notify_attr_changed(
-                                dev.ep_id,
-                                thermostat::ThermostatHandler::CLUSTER.id,
-                                0,
-                            );
-                            changed.push("Thermostat");
-                        }
-                        if old.is_none_or(|o| o.wind != status.wind || o.mode != status.mode) {
-                            dev.fan_ctl.dataver.changed();
-                            notifier.notify_attr_changed(
-                                dev.ep_id,
-                                fan_control::FanControlHandler::CLUSTER.id,
-                                0,
-                            );
-                            changed.push("FanControl");
-                        }
-                        if old.is_none_or(|o| o.sensors.humidity != status.sensors.humidity) {
-                            dev.humidity.dataver.changed();
-                            notifier.notify_attr_changed(
-                                dev.ep_id,
-                                humidity::HumidityHandler::CLUSTER.id,
-                                0,
-                            );
-                            changed.push("Humidity");
-                        }
-                        if let Some(ref p) = dev.power
-                            && old.is_none_or(|o| o.power_consumption != status.power_consumption)
-                        {
-                            p.dataver.changed();
-                            notifier.notify_attr_changed(
-                                dev.ep_id,
-                                power::PowerHandler::CLUSTER.id,
-                                0,
-                            );
-                            changed.push("Power");
-                        }
-                        if changed.is_empty() {
-                            debug!("Poll ep {}: no changes", dev.ep_id);
-                        } else {
-                            debug!("Poll ep {}: notified [{}]", dev.ep_id, changed.join(", "));
+                let status = dev.device.get_status();
+                let old = prev.get(&dev.ep_id);
+                let mut changed = Vec::new();
+                if old.is_none_or(|o| o.power != status.power || o.mode != status.mode) {
+                    dev.on_off.dataver.changed();
+                    notifier.notify_attr_changed(dev.ep_id, onoff::OnOffHandler::CLUSTER.id, 0);
+                    changed.push("OnOff");
+                }
+                // ActivePresetHandle also depends on the wind speed
+                if old.is_none_or(|o| {
+                    o.mode != status.mode
+                        || o.temperature != status.temperature
+                        || o.wind != status.wind
+                        || o.sensors.temperature != status.sensors.temperature
+                        || o.sensors.outdoor_temperature != status.sensors.outdoor_temperature
+                }) {
+                    dev.therm.dataver.changed();
+                    notifier.notify_attr_changed(
+                        dev.ep_id,
+                        thermostat::ThermostatHandler::CLUSTER.id,
+                        0,
+                    );
+                    changed.push("Thermostat");
+                }
+                if let Some(ref f) = dev.fan_ctl
+                    && old.is_none_or(|o| o.wind != status.wind || o.mode != status.mode)
+                {
+                    f.dataver.changed();
+                    notifier.notify_attr_changed(
+                        dev.ep_id,
+                        fan_control::FanControlHandler::CLUSTER.id,
+                        0,
+                    );
+                    changed.push("FanControl");
+                }
+                if let Some(ref m) = dev.operating_mode
+                    && old.is_none_or(|o| o.mode != status.mode)
+                {
+                    m.dataver.changed();
+                    notifier.notify_attr_changed(
+                        dev.ep_id,
+                        operating_mode::OperatingModeHandler::CLUSTER.id,
+                        0,
+                    );
+                    changed.push("ModeSelect");
+                }
+                for part in &dev.parts {
+                    let part_changed = match part.kind {
+                        PartKind::VerticalLouver | PartKind::HorizontalLouver => {
+                            old.is_none_or(|o| o.wind != status.wind || o.mode != status.mode)
                         }
-                        prev.insert(dev.ep_id, status);
+                        PartKind::OutdoorTemperature => old.is_none_or(|o| {
+                            o.sensors.outdoor_temperature != status.sensors.outdoor_temperature
+                        }),
+                    };
+                    if part_changed {
+                        part.dataver().changed();
+                        notifier.notify_attr_changed(part.ep_id, part.cluster().id, 0);
+                        changed.push(match part.kind {
+                            PartKind::OutdoorTemperature => "TemperatureMeasurement",
+                            _ => "ModeSelect",
+                        });
                     }
-                    Err(e) => warn!("Poll failed (ep {}): {e}", dev.ep_id),
                 }
+                if let Some(ref h) = dev.humidity
+                    && old.is_none_or(|o| o.sensors.humidity != status.sensors.humidity)
+                {
+                    h.dataver.changed();
+                    notifier.notify_attr_changed(
+                        dev.ep_id,
+                        humidity::HumidityHandler::CLUSTER.id,
+                        0,
+                    );
+                    changed.push("Humidity");
+                }
+                if let Some(ref p) = dev.power
+                    && old.is_none_or(|o| o.power_consumption != status.power_consumption)
+                {
+                    p.dataver.changed();
+                    notifier.notify_attr_changed(dev.ep_id, power::PowerHandler::CLUSTER.id, 0);
+                    changed.push("Power");
+                }
+                if changed.is_empty() {
+                    debug!("Poll ep {}: no changes", dev.ep_id);
+                } else {
+                    debug!("Poll ep {}: notified [{}]", dev.ep_id, changed.join(", "));
+                }
+                prev.insert(dev.ep_id, status);
                 let reachable_now = dev.device.is_reachable();
                 if reachable_now != reachable_before {
                     dev.bridged_info.dataver.changed();
@@ -470,8 +919,66 @@ fn run_matter(
                     );
                 }
                 was_reachable.insert(dev.ep_id, reachable_now);
+                // Parts reachable on their own, such as the outdoor unit
+                for part in &dev.parts {
+                    let Some(info) = part.bridged_info() else {
+                        continue;
+                    };
+                    let before = was_reachable.get(&part.ep_id).copied().unwrap_or(true);
+                    let now = info.is_reachable();
+                    if now != before {
+                        info.dataver.changed();
+                        notifier.notify_attr_changed(
+                            part.ep_id,
+                            bridged_info::BridgedInfo::CLUSTER.id,
+                            0,
+                        );
+                        info!("Poll ep {}: reachable {} → {}", part.ep_id, before, now);
+                    }
+                    was_reachable.insert(part.ep_id, now);
+                }
+                let identifying_now = dev.device.identify_remaining().is_some();
+                if identifying_now != identifying.contains(&dev.ep_id) {
+                    // IdentifyTime is reported when it starts and reaches 0
+                    dev.identify.dataver.changed();
+                    notifier.notify_attr_changed(
+                        dev.ep_id,
+                        identify::IdentifyHandler::CLUSTER.id,
+                        0,
+                    );
+                    if identifying_now {
+                        identifying.insert(dev.ep_id);
+                    } else {
+                        identifying.remove(&dev.ep_id);
+                    }
+                }
+                if let Some(limit) = remove_after
+                    && dev.device.unreachable_for().is_some_and(|t| t >= limit)
+                {
+                    vanished = Some((dev.mac.clone(), dev.ep_ids()));
+                }
+            }
+
+            if let Some((mac, ep_ids)) = vanished {
+                info!("Removing endpoint {ep_id}: unreachable for too long");
+                while node.remove(&ep_ids).is_err() {
+                    async_io::Timer::after(Duration::from_millis(10)).await;
+                }
+                bridge_handler
+                    .devices
+                    .borrow_mut()
+                    .retain(|d| d.ep_id != ep_id);
+                prev.remove(&ep_id);
+                for id in &ep_ids {
+                    was_reachable.remove(id);
+                }
+                identifying.remove(&ep_id);
+                // Bridged again once rediscovered
+                bridged.lock().expect("bridged lock poisoned").remove(&mac);
+                notify_parts_list();
             }
         }
+        Ok(())
     });
 
     let mut core = pin!(select4(&mut transport, &mut mdns, &mut respond, &mut dm_job).coalesce());
@@ -1,4 +1,4 @@
-use dsiot::{PowerState, StateTransition};
+use dsiot::{ChangeSet, PowerState, StateTransition};
 use rs_matter::dm::clusters::decl::on_off;
 use rs_matter::dm::{Cluster, Dataver, InvokeContext, ReadContext};
 use rs_matter::error::{Error, ErrorCode};
@@ -25,21 +25,15 @@ impl OnOffHandler {
     }
 
     fn set_power(&self, power: PowerState) -> Result<(), Error> {
-        let mut status = self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(ErrorCode::Busy)
-        })?;
-        StateTransition::new()
-            .power(power)
-            .apply_to_status(&mut status)
+        let status = self.device.get_status();
+        let changes = ChangeSet::builder(&status)
+            .transition(&StateTransition::new().power(power))
             .map_err(|e| {
                 warn!("State transition failed: {e}");
                 Error::from(ErrorCode::InvalidState)
-            })?;
-        self.device.update(status).map_err(|e| {
-            warn!("Failed to update: {e}");
-            Error::from(ErrorCode::Busy)
-        })?;
+            })?
+            .build();
+        self.device.update(changes)?;
         self.dataver.changed();
         Ok(())
     }
@@ -56,10 +50,7 @@ impl on_off::ClusterHandler for OnOffHandler {
     }
 
     fn on_off(&self, _ctx: impl ReadContext) -> Result<bool, Error> {
-        let status = self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(ErrorCode::Busy)
-        })?;
+        let status = self.device.get_status();
         Ok(PowerState::from_status(&status) == Some(PowerState::On))
     }
 
@@ -74,10 +65,7 @@ impl on_off::ClusterHandler for OnOffHandler {
     }
 
     fn handle_toggle(&self, _ctx: impl InvokeContext) -> Result<(), Error> {
-        let status = self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(ErrorCode::Busy)
-        })?;
+        let status = self.device.get_status();
         let current = PowerState::from_status(&status).unwrap_or(PowerState::Off);
         let next = match current {
             PowerState::On => PowerState::Off,
@@ -0,0 +1,118 @@
+use dsiot::{ChangeSet, Mode};
+use rs_matter::dm::clusters::decl::mode_select;
+use rs_matter::dm::{ArrayAttributeRead, Cluster, Dataver, InvokeContext, ReadContext};
+use rs_matter::error::{Error, ErrorCode};
+use rs_matter::tlv::{Nullable, TLVBuilderParent, Utf8StrBuilder};
+use rs_matter::with;
+
+use crate::device::Device;
+use crate::louver::build_mode_option;
+
+/// Label of a Daikin operating mode. Modes without a name in dsiot are
+/// labelled by their number.
+fn mode_label(mode: u8) -> String {
+    let label = match mode {
+        m if m == Mode::Fan as u8 => "Fan",
+        m if m == Mode::Heating as u8 => "Heat",
+        m if m == Mode::Cooling as u8 => "Cool",
+        m if m == Mode::Auto as u8 => "Auto",
+        m if m == Mode::Dehumidify as u8 => "Dry",
+        _ => return format!("Mode {mode}"),
+    };
+    label.into()
+}
+
+/// Mode Select cluster offering every operating mode the device allows,
+/// including those Thermostat SystemMode cannot express. The mode value is
+/// the one Daikin uses.
+pub(crate) struct OperatingModeHandler {
+    pub(crate) dataver: Dataver,
+    device: Device,
+}
+
+impl OperatingModeHandler {
+    pub const CLUSTER: Cluster<'static> = mode_select::FULL_CLUSTER
+        .with_revision(2)
+        .with_features(0)
+        .with_attrs(with!(required))
+        .with_cmds(with!(mode_select::CommandId::ChangeToMode));
+
+    pub fn new(dataver: Dataver, device: Device) -> Self {
+        Self { dataver, device }
+    }
+
+    fn modes(&self) -> Vec<u8> {
+        self.device.get_status().mode.allowed_raw_enums()
+    }
+}
+
+impl mode_select::ClusterHandler for OperatingModeHandler {
+    const CLUSTER: Cluster<'static> = Self::CLUSTER;
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn description<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: Utf8StrBuilder<P>,
+    ) -> Result<P, Error> {
+        builder.set("Operating mode")
+    }
+
+    fn standard_namespace(&self, _ctx: impl ReadContext) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::none())
+    }
+
+    fn supported_modes<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: ArrayAttributeRead<
+            mode_select::ModeOptionStructArrayBuilder<P>,
+            mode_select::ModeOptionStructBuilder<P>,
+        >,
+    ) -> Result<P, Error> {
+        let modes = self.modes();
+        match builder {
+            ArrayAttributeRead::ReadAll(mut array) => {
+                for mode in modes {
+                    array = build_mode_option(array.push()?, mode, &mode_label(mode))?;
+                }
+                array.end()
+            }
+            ArrayAttributeRead::ReadOne(index, elem) => match modes.get(index as usize) {
+                Some(&mode) => build_mode_option(elem, mode, &mode_label(mode)),
+                None => Err(ErrorCode::ConstraintError.into()),
+            },
+            ArrayAttributeRead::ReadNone(array) => array.end(),
+        }
+    }
+
+    fn current_mode(&self, _ctx: impl ReadContext) -> Result<u8, Error> {
+        let status = self.device.get_status();
+        Ok(status.mode.get_raw_enum().unwrap_or(Mode::Auto as u8))
+    }
+
+    fn handle_change_to_mode(
+        &self,
+        _ctx: impl InvokeContext,
+        request: mode_select::ChangeToModeRequest<'_>,
+    ) -> Result<(), Error> {
+        let new_mode = request.new_mode()?;
+        if !self.modes().contains(&new_mode) {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+        // Only the mode changes; OnOff turns the unit on and off
+        let base = self.device.get_status();
+        let mut status = base.clone();
+        status.mode.set_raw_enum(new_mode);
+        debug!("ModeSelect: operating mode → {}", mode_label(new_mode));
+        self.device.update(ChangeSet::diff(&base, &status))?;
+        self.dataver.changed();
+        Ok(())
+    }
+}
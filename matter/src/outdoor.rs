@@ -0,0 +1,72 @@
+use dsiot::ValueConstraints;
+use rs_matter::dm::clusters::decl::temperature_measurement;
+use rs_matter::dm::{Cluster, Dataver, DeviceType, ReadContext};
+use rs_matter::error::Error;
+use rs_matter::tlv::Nullable;
+use rs_matter::with;
+
+use crate::device::Device;
+use crate::thermostat::temp_to_matter;
+
+pub(crate) const DEV_TYPE_TEMP_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0302,
+    drev: 2,
+};
+
+/// Temperature Measurement cluster for the outdoor temperature reported by
+/// the outdoor unit, on a child endpoint of its own so that controllers
+/// can use it in automations.
+pub(crate) struct OutdoorTemperatureHandler {
+    pub(crate) dataver: Dataver,
+    device: Device,
+}
+
+impl OutdoorTemperatureHandler {
+    pub const CLUSTER: Cluster<'static> = temperature_measurement::FULL_CLUSTER
+        .with_revision(4)
+        .with_features(0)
+        .with_attrs(with!(required))
+        .with_cmds(with!());
+
+    pub fn new(dataver: Dataver, device: Device) -> Self {
+        Self { dataver, device }
+    }
+
+    /// Range the outdoor unit reports, if it has said so.
+    fn range(&self) -> Option<ValueConstraints> {
+        ValueConstraints::from_item(&self.device.get_status().sensors.outdoor_temperature)
+    }
+}
+
+impl temperature_measurement::ClusterHandler for OutdoorTemperatureHandler {
+    const CLUSTER: Cluster<'static> = Self::CLUSTER;
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn measured_value(&self, _ctx: impl ReadContext) -> Result<Nullable<i16>, Error> {
+        let status = self.device.get_status();
+        match status.sensors.outdoor_temperature.get_f32() {
+            Some(t) => Ok(Nullable::some(temp_to_matter(t))),
+            None => Ok(Nullable::none()),
+        }
+    }
+
+    fn min_measured_value(&self, _ctx: impl ReadContext) -> Result<Nullable<i16>, Error> {
+        match self.range() {
+            Some(c) => Ok(Nullable::some(temp_to_matter(c.min))),
+            None => Ok(Nullable::none()),
+        }
+    }
+
+    fn max_measured_value(&self, _ctx: impl ReadContext) -> Result<Nullable<i16>, Error> {
+        match self.range() {
+            Some(c) => Ok(Nullable::some(temp_to_matter(c.max))),
+            None => Ok(Nullable::none()),
+        }
+    }
+}
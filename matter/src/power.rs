@@ -104,10 +104,7 @@ impl electrical_power_measurement::ClusterHandler for PowerHandler {
     }
 
     fn active_power(&self, _ctx: impl ReadContext) -> Result<Nullable<PowerMilliW>, Error> {
-        let status = self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(ErrorCode::Busy)
-        })?;
+        let status = self.device.get_status();
         match status.power_consumption.get_f32() {
             Some(watts) => Ok(Nullable::some((watts * 1000.0) as i64)),
             None => Ok(Nullable::none()),
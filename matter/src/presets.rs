@@ -0,0 +1,216 @@
+//! Thermostat presets and the active preset of each device.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use dsiot::Mode;
+use serde::{Deserialize, Serialize};
+
+use crate::store::write_atomically;
+
+const FILE_NAME: &str = "presets.toml";
+
+const HEADER: &str = "\
+# Thermostat presets offered to Matter controllers, and the preset last
+# activated on each device. Edit the presets while the bridge is stopped.
+#
+# scenario: occupied, unoccupied, sleep, wake, vacation, going_to_sleep or
+#           user_defined
+# mode:     cool, heat or auto; turns the unit on. Omit to keep the mode.
+# silent:   run the fan in silent mode
+
+";
+
+/// Matter preset scenario.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    Occupied,
+    Unoccupied,
+    Sleep,
+    Wake,
+    Vacation,
+    GoingToSleep,
+    UserDefined,
+}
+
+/// Operating mode a preset switches to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresetMode {
+    Cool,
+    Heat,
+    Auto,
+}
+
+impl From<PresetMode> for Mode {
+    fn from(mode: PresetMode) -> Self {
+        match mode {
+            PresetMode::Cool => Mode::Cooling,
+            PresetMode::Heat => Mode::Heating,
+            PresetMode::Auto => Mode::Auto,
+        }
+    }
+}
+
+/// A bundle of settings applied together.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// Identifier of the preset, up to 16 bytes.
+    pub handle: String,
+    pub scenario: Scenario,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<PresetMode>,
+    /// Cooling setpoint in °C.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooling_setpoint: Option<f32>,
+    /// Heating setpoint in °C.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heating_setpoint: Option<f32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub silent: bool,
+}
+
+impl Preset {
+    fn new(handle: &str, scenario: Scenario, name: &str, cooling: f32, heating: f32) -> Self {
+        Self {
+            handle: handle.into(),
+            scenario,
+            name: name.into(),
+            mode: None,
+            cooling_setpoint: Some(cooling),
+            heating_setpoint: Some(heating),
+            silent: false,
+        }
+    }
+}
+
+fn default_presets() -> Vec<Preset> {
+    vec![
+        Preset::new("comfort", Scenario::Occupied, "Comfort", 26.0, 21.0),
+        Preset::new("eco", Scenario::UserDefined, "Eco", 28.0, 19.0),
+        Preset::new("away", Scenario::Unoccupied, "Away", 30.0, 17.0),
+        Preset {
+            silent: true,
+            ..Preset::new("sleep", Scenario::Sleep, "Sleep", 27.0, 20.0)
+        },
+    ]
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Active {
+    mac: String,
+    handle: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct File {
+    #[serde(default, rename = "preset")]
+    presets: Vec<Preset>,
+    #[serde(default)]
+    active: Vec<Active>,
+}
+
+/// Presets shared by all devices, stored in the data directory.
+#[derive(Debug)]
+pub struct PresetStore {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl PresetStore {
+    /// Load the presets from `data_dir`, writing the defaults if there are none.
+    pub fn load(data_dir: &Path) -> anyhow::Result<Self> {
+        let path = data_dir.join(FILE_NAME);
+        let (file, created) = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let file: File = toml::from_str(&text)
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {e}", path.display()))?;
+                (file, false)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let file = File {
+                    presets: default_presets(),
+                    active: Vec::new(),
+                };
+                (file, true)
+            }
+            Err(e) => anyhow::bail!("Failed to read {}: {e}", path.display()),
+        };
+        for (i, preset) in file.presets.iter().enumerate() {
+            if preset.handle.is_empty() || preset.handle.len() > 16 {
+                anyhow::bail!(
+                    "Invalid {}: handle {:?} must be 1 to 16 bytes",
+                    path.display(),
+                    preset.handle
+                );
+            }
+            if file.presets[..i].iter().any(|p| p.handle == preset.handle) {
+                anyhow::bail!(
+                    "Invalid {}: handle {:?} is used twice",
+                    path.display(),
+                    preset.handle
+                );
+            }
+        }
+        let store = Self {
+            path,
+            file: Mutex::new(file),
+        };
+        if created {
+            store.save()?;
+        }
+        Ok(store)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let text = {
+            let file = self.file.lock().expect("presets lock poisoned");
+            format!("{HEADER}{}", toml::to_string(&*file)?)
+        };
+        write_atomically(&self.path, &text)
+    }
+
+    pub fn presets(&self) -> Vec<Preset> {
+        self.file
+            .lock()
+            .expect("presets lock poisoned")
+            .presets
+            .clone()
+    }
+
+    pub fn get(&self, handle: &[u8]) -> Option<Preset> {
+        let file = self.file.lock().expect("presets lock poisoned");
+        file.presets
+            .iter()
+            .find(|p| p.handle.as_bytes() == handle)
+            .cloned()
+    }
+
+    /// The preset last activated on the device with `mac`.
+    pub fn active(&self, mac: &str) -> Option<Preset> {
+        let file = self.file.lock().expect("presets lock poisoned");
+        let active = file.active.iter().find(|a| a.mac == mac)?;
+        file.presets
+            .iter()
+            .find(|p| p.handle == active.handle)
+            .cloned()
+    }
+
+    /// Record the active preset of the device with `mac`, or clear it.
+    pub fn set_active(&self, mac: &str, handle: Option<&str>) -> anyhow::Result<()> {
+        {
+            let mut file = self.file.lock().expect("presets lock poisoned");
+            file.active.retain(|a| a.mac != mac);
+            if let Some(handle) = handle {
+                file.active.push(Active {
+                    mac: mac.into(),
+                    handle: handle.into(),
+                });
+            }
+        }
+        self.save()
+    }
+}
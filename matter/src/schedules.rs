@@ -0,0 +1,392 @@
+//! Thermostat weekly schedules, run by the bridge itself.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use jiff::Zoned;
+use jiff::civil::{Date, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::store::write_atomically;
+
+const FILE_NAME: &str = "schedules.toml";
+
+const HEADER: &str = "\
+# Thermostat weekly schedules set by Matter controllers. The bridge runs them
+# in the local time zone of the host; set TZ to use another one.
+#
+# day:  sunday to saturday, or away (kept for controllers but never run)
+# time: minutes after midnight
+# heat, cool: setpoints in °C
+#
+# Transitions only hold setpoints, never a mode: the mode is left as it is.
+";
+
+/// Most transitions a day can have.
+pub const MAX_DAILY_TRANSITIONS: usize = 10;
+
+/// Day a sequence of transitions applies to, in Matter bitmap order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Day {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Away,
+}
+
+impl Day {
+    fn of(date: Date) -> Self {
+        match date.weekday() {
+            Weekday::Sunday => Day::Sunday,
+            Weekday::Monday => Day::Monday,
+            Weekday::Tuesday => Day::Tuesday,
+            Weekday::Wednesday => Day::Wednesday,
+            Weekday::Thursday => Day::Thursday,
+            Weekday::Friday => Day::Friday,
+            Weekday::Saturday => Day::Saturday,
+        }
+    }
+}
+
+/// Setpoints taking effect at a time of day.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    /// Minutes after midnight.
+    pub time: u16,
+    /// Heating setpoint in °C.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heat: Option<f32>,
+    /// Cooling setpoint in °C.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cool: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    mac: String,
+    day: Day,
+    #[serde(default, rename = "transition")]
+    transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct File {
+    #[serde(default, rename = "schedule")]
+    schedules: Vec<Entry>,
+}
+
+/// Weekly schedules of every device, stored in the data directory.
+#[derive(Debug)]
+pub struct ScheduleStore {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl ScheduleStore {
+    /// Load the schedules from `data_dir`. A missing file means no schedules.
+    pub fn load(data_dir: &Path) -> anyhow::Result<Self> {
+        let path = data_dir.join(FILE_NAME);
+        let file: File = match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => File::default(),
+            Err(e) => anyhow::bail!("Failed to read {}: {e}", path.display()),
+        };
+        for (i, entry) in file.schedules.iter().enumerate() {
+            let invalid = |msg: &str| {
+                anyhow::anyhow!("Invalid {}: schedule {}: {msg}", path.display(), i + 1)
+            };
+            if file.schedules[..i]
+                .iter()
+                .any(|e| e.mac == entry.mac && e.day == entry.day)
+            {
+                return Err(invalid("listed twice"));
+            }
+            if entry.transitions.len() > MAX_DAILY_TRANSITIONS {
+                return Err(invalid("too many transitions"));
+            }
+            for t in &entry.transitions {
+                if t.time >= 24 * 60 {
+                    return Err(invalid("time must be less than 1440"));
+                }
+                if t.heat.is_none() && t.cool.is_none() {
+                    return Err(invalid("transitions need `heat` or `cool`"));
+                }
+            }
+        }
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let text = {
+            let file = self.file.lock().expect("schedules lock poisoned");
+            format!("{HEADER}{}", toml::to_string(&*file)?)
+        };
+        write_atomically(&self.path, &text)
+    }
+
+    /// Transitions of the device with `mac` on `day`, earliest first.
+    pub fn get(&self, mac: &str, day: Day) -> Vec<Transition> {
+        let file = self.file.lock().expect("schedules lock poisoned");
+        file.schedules
+            .iter()
+            .find(|e| e.mac == mac && e.day == day)
+            .map(|e| e.transitions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replace the transitions of the device with `mac` on each of `days`.
+    pub fn set(&self, mac: &str, days: &[Day], transitions: &[Transition]) -> anyhow::Result<()> {
+        let mut transitions = transitions.to_vec();
+        transitions.sort_by_key(|t| t.time);
+        {
+            let mut file = self.file.lock().expect("schedules lock poisoned");
+            file.schedules
+                .retain(|e| e.mac != mac || !days.contains(&e.day));
+            if !transitions.is_empty() {
+                file.schedules.extend(days.iter().map(|&day| Entry {
+                    mac: mac.into(),
+                    day,
+                    transitions: transitions.clone(),
+                }));
+            }
+        }
+        self.save()
+    }
+
+    /// Remove every schedule of the device with `mac`.
+    pub fn clear(&self, mac: &str) -> anyhow::Result<()> {
+        self.file
+            .lock()
+            .expect("schedules lock poisoned")
+            .schedules
+            .retain(|e| e.mac != mac);
+        self.save()
+    }
+
+    /// The last transition of the device with `mac` that fell after `since`
+    /// and no later than `now`, in the time zone of `now`.
+    pub fn due(&self, mac: &str, since: &Zoned, now: &Zoned) -> Option<Transition> {
+        let file = self.file.lock().expect("schedules lock poisoned");
+        let mut latest: Option<(Zoned, Transition)> = None;
+        let mut date = since.date();
+        while date <= now.date() {
+            let day = Day::of(date);
+            let transitions = file
+                .schedules
+                .iter()
+                .filter(|e| e.mac == mac && e.day == day)
+                .flat_map(|e| &e.transitions);
+            for t in transitions {
+                let hour = (t.time / 60) as i8;
+                let minute = (t.time % 60) as i8;
+                // Times skipped by a DST change run as much later as the
+                // clocks jumped; repeated times run the first time only
+                let Ok(at) = date
+                    .at(hour, minute, 0, 0)
+                    .to_zoned(now.time_zone().clone())
+                else {
+                    continue;
+                };
+                if at > *since && at <= *now && latest.as_ref().is_none_or(|(l, _)| at >= *l) {
+                    latest = Some((at, *t));
+                }
+            }
+            let Ok(next) = date.tomorrow() else { break };
+            date = next;
+        }
+        latest.map(|(_, t)| t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: &str = "00005E005301";
+
+    /// An empty store in a directory of its own.
+    fn empty_store(test: &str) -> ScheduleStore {
+        let dir = std::env::temp_dir().join(format!("daikin-matter-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        ScheduleStore::load(&dir).unwrap()
+    }
+
+    fn at(time: &str) -> Zoned {
+        time.parse().unwrap()
+    }
+
+    fn heat(time: u16, heat: f32) -> Transition {
+        Transition {
+            time,
+            heat: Some(heat),
+            cool: None,
+        }
+    }
+
+    /// Heating setpoint of the transition due between `since` and `now`.
+    fn due(store: &ScheduleStore, since: &str, now: &str) -> Option<f32> {
+        store.due(MAC, &at(since), &at(now)).and_then(|t| t.heat)
+    }
+
+    #[test]
+    fn due_same_day() {
+        let store = empty_store("due_same_day");
+        // 2026-10-19 is a Monday
+        store
+            .set(
+                MAC,
+                &[Day::Monday],
+                &[heat(22 * 60, 18.0), heat(7 * 60, 21.0)],
+            )
+            .unwrap();
+        let due = |since, now| due(&store, since, now);
+        assert_eq!(
+            due("2026-10-19T06:59+00:00[UTC]", "2026-10-19T07:00+00:00[UTC]"),
+            Some(21.0)
+        );
+        // `since` itself is excluded, so a transition runs once
+        assert_eq!(
+            due("2026-10-19T07:00+00:00[UTC]", "2026-10-19T07:30+00:00[UTC]"),
+            None
+        );
+        // Only the last of several transitions runs
+        assert_eq!(
+            due("2026-10-19T06:00+00:00[UTC]", "2026-10-19T23:00+00:00[UTC]"),
+            Some(18.0)
+        );
+        // Another day or device has no schedule
+        assert_eq!(
+            due("2026-10-20T06:00+00:00[UTC]", "2026-10-20T23:00+00:00[UTC]"),
+            None
+        );
+        let other = store.due(
+            "00005E005302",
+            &at("2026-10-19T06:00+00:00[UTC]"),
+            &at("2026-10-19T23:00+00:00[UTC]"),
+        );
+        assert_eq!(other, None);
+    }
+
+    #[test]
+    fn due_across_days() {
+        let store = empty_store("due_across_days");
+        store
+            .set(MAC, &[Day::Sunday], &[heat(23 * 60, 17.0)])
+            .unwrap();
+        store.set(MAC, &[Day::Monday], &[heat(60, 19.0)]).unwrap();
+        store
+            .set(MAC, &[Day::Wednesday], &[heat(8 * 60, 22.0)])
+            .unwrap();
+        let due = |since, now| due(&store, since, now);
+        // Across midnight, from Sunday 2026-10-18 into Monday
+        assert_eq!(
+            due("2026-10-18T22:30+00:00[UTC]", "2026-10-19T00:30+00:00[UTC]"),
+            Some(17.0)
+        );
+        assert_eq!(
+            due("2026-10-18T22:30+00:00[UTC]", "2026-10-19T02:00+00:00[UTC]"),
+            Some(19.0)
+        );
+        // Across several days, the latest one wins
+        assert_eq!(
+            due("2026-10-18T12:00+00:00[UTC]", "2026-10-22T12:00+00:00[UTC]"),
+            Some(22.0)
+        );
+    }
+
+    #[test]
+    fn away_never_runs() {
+        let store = empty_store("away_never_runs");
+        store
+            .set(MAC, &[Day::Away], &[heat(12 * 60, 16.0)])
+            .unwrap();
+        assert_eq!(store.get(MAC, Day::Away), vec![heat(12 * 60, 16.0)]);
+        assert_eq!(
+            due(
+                &store,
+                "2026-10-18T00:00+00:00[UTC]",
+                "2026-10-25T00:00+00:00[UTC]"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn due_time_zone() {
+        let store = empty_store("due_time_zone");
+        store
+            .set(MAC, &[Day::Monday], &[heat(7 * 60, 21.0)])
+            .unwrap();
+        // 07:00 in Berlin is 05:00 UTC in October
+        let since = "2026-10-19T06:30+02:00[Europe/Berlin]";
+        assert_eq!(
+            due(&store, since, "2026-10-19T06:59+02:00[Europe/Berlin]"),
+            None
+        );
+        assert_eq!(
+            due(&store, since, "2026-10-19T07:00+02:00[Europe/Berlin]"),
+            Some(21.0)
+        );
+        // Transitions follow the time zone of `now`
+        assert_eq!(
+            due(
+                &store,
+                "2026-10-19T04:30+00:00[UTC]",
+                "2026-10-19T05:00+00:00[UTC]"
+            ),
+            None
+        );
+        assert_eq!(
+            due(
+                &store,
+                "2026-10-19T06:30+00:00[UTC]",
+                "2026-10-19T07:00+00:00[UTC]"
+            ),
+            Some(21.0)
+        );
+    }
+
+    #[test]
+    fn due_dst() {
+        let store = empty_store("due_dst");
+        // 2026-03-08 and 2026-11-01 are Sundays with DST changes in New York
+        store
+            .set(
+                MAC,
+                &[Day::Sunday],
+                &[heat(2 * 60 + 30, 20.0), heat(60 + 30, 18.0)],
+            )
+            .unwrap();
+        // 02:30 does not exist on 2026-03-08 and runs an hour later instead
+        let since = "2026-03-08T01:45-05:00[America/New_York]";
+        assert_eq!(
+            due(&store, since, "2026-03-08T03:15-04:00[America/New_York]"),
+            None
+        );
+        assert_eq!(
+            due(&store, since, "2026-03-08T03:30-04:00[America/New_York]"),
+            Some(20.0)
+        );
+        // 01:30 happens twice on 2026-11-01 and only runs the first time
+        let first = "2026-11-01T01:30-04:00[America/New_York]";
+        assert_eq!(
+            due(&store, "2026-11-01T01:00-04:00[America/New_York]", first),
+            Some(18.0)
+        );
+        assert_eq!(
+            due(&store, first, "2026-11-01T01:45-05:00[America/New_York]"),
+            None
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! Files the bridge keeps in its data directory.
+
+use std::path::Path;
+
+/// Write `text` to `path`, creating its directory if needed.
+///
+/// The file is replaced atomically so a crash cannot leave it truncated.
+pub fn write_atomically(path: &Path, text: &str) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("toml.tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
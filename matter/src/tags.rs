@@ -0,0 +1,156 @@
+use rs_matter::dm::clusters::decl::globals::{
+    SemanticTagStructArrayBuilder, SemanticTagStructBuilder,
+};
+use rs_matter::dm::clusters::desc::{self, ClusterHandler as _, DescHandler};
+use rs_matter::dm::{ArrayAttributeRead, Cluster, ReadContext};
+use rs_matter::error::{Error, ErrorCode};
+use rs_matter::tlv::{Nullable, TLVBuilderParent, ToTLVArrayBuilder, ToTLVBuilder};
+use rs_matter::with;
+
+/// Semantic tag from a standard namespace, with an optional label that
+/// sets apart endpoints whose tags are otherwise the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SemanticTag {
+    namespace: u8,
+    tag: u8,
+    label: Option<&'static str>,
+}
+
+/// Common Location namespace.
+const NAMESPACE_COMMON_LOCATION: u8 = 0x06;
+
+/// Common Number namespace.
+const NAMESPACE_COMMON_NUMBER: u8 = 0x07;
+
+/// Tag of an endpoint measuring something outdoors.
+///
+/// The tag only says where the sensor is. What links it to its AC is the
+/// composition: the endpoint is listed in the PartsList of the AC endpoint,
+/// and its Bridged Device Basic Information shares the AC's UniqueID with an
+/// `-outdoor` suffix.
+pub(crate) const OUTDOOR: SemanticTag = SemanticTag {
+    namespace: NAMESPACE_COMMON_LOCATION,
+    tag: 0x01,
+    label: None,
+};
+
+/// Tag of the louver endpoint for the up and down direction.
+pub(crate) const VERTICAL_LOUVER: SemanticTag = SemanticTag {
+    namespace: NAMESPACE_COMMON_NUMBER,
+    tag: 0x01,
+    label: Some("Vertical louver"),
+};
+
+/// Tag of the louver endpoint for the left and right direction.
+pub(crate) const HORIZONTAL_LOUVER: SemanticTag = SemanticTag {
+    namespace: NAMESPACE_COMMON_NUMBER,
+    tag: 0x02,
+    label: Some("Horizontal louver"),
+};
+
+/// Descriptor cluster with a TagList, so that controllers can tell apart the
+/// endpoints that make up one device. Everything else is left to the
+/// wrapped `DescHandler`.
+pub(crate) struct TaggedDescHandler {
+    inner: DescHandler<'static>,
+    tags: &'static [SemanticTag],
+}
+
+impl TaggedDescHandler {
+    pub const CLUSTER: Cluster<'static> = desc::FULL_CLUSTER
+        .with_revision(2)
+        .with_features(desc::Feature::TAG_LIST.bits())
+        .with_attrs(with!(required; desc::AttributeId::TagList))
+        .with_cmds(with!());
+
+    pub fn new(inner: DescHandler<'static>, tags: &'static [SemanticTag]) -> Self {
+        Self { inner, tags }
+    }
+
+    /// The cluster as listed on the endpoint; a plain Descriptor without tags.
+    pub fn cluster(&self) -> Cluster<'static> {
+        if self.tags.is_empty() {
+            DescHandler::CLUSTER
+        } else {
+            Self::CLUSTER
+        }
+    }
+}
+
+fn build_tag<P: TLVBuilderParent>(
+    builder: SemanticTagStructBuilder<P>,
+    tag: &SemanticTag,
+) -> Result<P, Error> {
+    builder
+        .mfg_code(Nullable::none())?
+        .namespace_id(tag.namespace)?
+        .tag(tag.tag)?
+        .label(tag.label.map(Nullable::some))?
+        .end()
+}
+
+impl desc::ClusterHandler for TaggedDescHandler {
+    const CLUSTER: Cluster<'static> = Self::CLUSTER;
+
+    fn dataver(&self) -> u32 {
+        self.inner.dataver()
+    }
+    fn dataver_changed(&self) {
+        self.inner.dataver_changed();
+    }
+
+    fn device_type_list<P: TLVBuilderParent>(
+        &self,
+        ctx: impl ReadContext,
+        builder: ArrayAttributeRead<
+            desc::DeviceTypeStructArrayBuilder<P>,
+            desc::DeviceTypeStructBuilder<P>,
+        >,
+    ) -> Result<P, Error> {
+        self.inner.device_type_list(ctx, builder)
+    }
+
+    fn server_list<P: TLVBuilderParent>(
+        &self,
+        ctx: impl ReadContext,
+        builder: ArrayAttributeRead<ToTLVArrayBuilder<P, u32>, ToTLVBuilder<P, u32>>,
+    ) -> Result<P, Error> {
+        self.inner.server_list(ctx, builder)
+    }
+
+    fn client_list<P: TLVBuilderParent>(
+        &self,
+        ctx: impl ReadContext,
+        builder: ArrayAttributeRead<ToTLVArrayBuilder<P, u32>, ToTLVBuilder<P, u32>>,
+    ) -> Result<P, Error> {
+        self.inner.client_list(ctx, builder)
+    }
+
+    fn parts_list<P: TLVBuilderParent>(
+        &self,
+        ctx: impl ReadContext,
+        builder: ArrayAttributeRead<ToTLVArrayBuilder<P, u16>, ToTLVBuilder<P, u16>>,
+    ) -> Result<P, Error> {
+        self.inner.parts_list(ctx, builder)
+    }
+
+    fn tag_list<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: ArrayAttributeRead<SemanticTagStructArrayBuilder<P>, SemanticTagStructBuilder<P>>,
+    ) -> Result<P, Error> {
+        match builder {
+            ArrayAttributeRead::ReadAll(mut array) => {
+                for tag in self.tags {
+                    array = build_tag(array.push()?, tag)?;
+                }
+                array.end()
+            }
+            ArrayAttributeRead::ReadOne(index, elem) => match self.tags.get(index as usize) {
+                Some(tag) => build_tag(elem, tag),
+                None => Err(ErrorCode::ConstraintError.into()),
+            },
+            ArrayAttributeRead::ReadNone(array) => array.end(),
+        }
+    }
+}
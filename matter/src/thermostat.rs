@@ -1,15 +1,110 @@
-use dsiot::{DaikinStatus, Mode, PowerState, StateTransition, TemperatureTarget, ValueConstraints};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dsiot::{
+    Capabilities, ChangeSet, DaikinStatus, Mode, PowerState, StateTransition, TemperatureTarget,
+    ValueConstraints,
+};
+use jiff::Zoned;
 use rs_matter::dm::clusters::decl::thermostat;
-use rs_matter::dm::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+use rs_matter::dm::{
+    ArrayAttributeRead, Cluster, Dataver, InvokeContext, ReadContext, WriteContext,
+};
 use rs_matter::error::{Error, ErrorCode};
-use rs_matter::tlv::{Nullable, TLVBuilderParent};
+use rs_matter::tlv::{Nullable, NullableBuilder, OctetStr, OctetsBuilder, TLVBuilderParent};
 use rs_matter::with;
 
+use crate::config::SetpointLimits;
 use crate::device::Device;
+use crate::fan_control::apply_silent;
+use crate::presets::{Preset, PresetStore, Scenario};
+use crate::schedules::{Day, MAX_DAILY_TRANSITIONS, ScheduleStore, Transition};
+
+/// Temperature that Auto mode is taken to aim for at offset 0.
+///
+/// Auto mode has no absolute setpoint, only an offset of -5 to +5 from a
+/// temperature the unit picks itself. To controllers it is shown as a
+/// heating and cooling setpoint pair centred on `AUTO_REFERENCE + offset`
+/// and `AUTO_DEADBAND` apart; moving either setpoint moves the offset.
+const AUTO_REFERENCE: f32 = 25.0;
+
+/// Distance between the heating and cooling setpoints in Auto mode, in °C.
+const AUTO_DEADBAND: f32 = 2.0;
+
+/// How soon a write to the other setpoint of the Auto mode pair is taken as
+/// part of the same request.
+const AUTO_PAIR_WINDOW: Duration = Duration::from_secs(1);
+
+/// Setpoint of the Auto mode pair.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PairSide {
+    Heating,
+    Cooling,
+}
+
+impl PairSide {
+    /// Centre of the pair when this setpoint is at `value`.
+    fn center(self, value: f32) -> f32 {
+        match self {
+            PairSide::Heating => value + AUTO_DEADBAND / 2.0,
+            PairSide::Cooling => value - AUTO_DEADBAND / 2.0,
+        }
+    }
+
+    /// This setpoint of a heating and cooling `pair`.
+    fn of(self, pair: (f32, f32)) -> f32 {
+        match self {
+            PairSide::Heating => pair.0,
+            PairSide::Cooling => pair.1,
+        }
+    }
+}
+
+/// A write to one setpoint of the Auto mode pair, waiting for the other.
+#[derive(Clone, Copy, Debug)]
+struct PairWrite {
+    at: Instant,
+    side: PairSide,
+    value: f32,
+    /// Heating and cooling setpoints reported before the write.
+    before: (f32, f32),
+}
 
 pub struct ThermostatHandler {
     pub(crate) dataver: Dataver,
     device: Device,
+    cooling_limits: SetpointLimits,
+    heating_limits: SetpointLimits,
+    presets: Arc<PresetStore>,
+    schedules: Arc<ScheduleStore>,
+    /// MAC address of the device, to persist its active preset and schedule.
+    mac: String,
+    /// Last write to one setpoint of the Auto mode pair.
+    pair_write: Cell<Option<PairWrite>>,
+}
+
+/// Attributes of the Thermostat cluster that do not depend on its features,
+/// together with the given feature-dependent ones.
+macro_rules! thermostat_attrs {
+    ($($attr:ident)|*) => {
+        with!(
+            required;
+            thermostat::AttributeId::LocalTemperature
+            | thermostat::AttributeId::OutdoorTemperature
+            | thermostat::AttributeId::SystemMode
+            | thermostat::AttributeId::ControlSequenceOfOperation
+            | thermostat::AttributeId::ThermostatRunningState
+            | thermostat::AttributeId::StartOfWeek
+            | thermostat::AttributeId::NumberOfWeeklyTransitions
+            | thermostat::AttributeId::NumberOfDailyTransitions
+            | thermostat::AttributeId::PresetTypes
+            | thermostat::AttributeId::NumberOfPresets
+            | thermostat::AttributeId::ActivePresetHandle
+            | thermostat::AttributeId::Presets
+            $(| thermostat::AttributeId::$attr)*
+        )
+    };
 }
 
 impl ThermostatHandler {
@@ -18,41 +113,276 @@ impl ThermostatHandler {
         .with_features(
             thermostat::Feature::HEATING.bits()
                 | thermostat::Feature::COOLING.bits()
-                | thermostat::Feature::AUTO_MODE.bits(),
+                | thermostat::Feature::AUTO_MODE.bits()
+                | thermostat::Feature::SCHEDULE_CONFIGURATION.bits()
+                | thermostat::Feature::PRESETS.bits(),
         )
-        .with_attrs(with!(
-            required;
-            thermostat::AttributeId::LocalTemperature
-            | thermostat::AttributeId::OutdoorTemperature
-            | thermostat::AttributeId::SystemMode
-            | thermostat::AttributeId::OccupiedCoolingSetpoint
-            | thermostat::AttributeId::OccupiedHeatingSetpoint
-            | thermostat::AttributeId::AbsMinHeatSetpointLimit
-            | thermostat::AttributeId::AbsMaxHeatSetpointLimit
-            | thermostat::AttributeId::AbsMinCoolSetpointLimit
-            | thermostat::AttributeId::AbsMaxCoolSetpointLimit
-            | thermostat::AttributeId::ControlSequenceOfOperation
-            | thermostat::AttributeId::ThermostatRunningMode
-            | thermostat::AttributeId::ThermostatRunningState
+        .with_attrs(thermostat_attrs!(
+            OccupiedCoolingSetpoint
+                | AbsMinCoolSetpointLimit
+                | AbsMaxCoolSetpointLimit
+                | OccupiedHeatingSetpoint
+                | AbsMinHeatSetpointLimit
+                | AbsMaxHeatSetpointLimit
+                | MinSetpointDeadBand
+                | ThermostatRunningMode
         ))
-        .with_cmds(with!(thermostat::CommandId::SetpointRaiseLower));
+        .with_cmds(with!(
+            thermostat::CommandId::SetpointRaiseLower
+                | thermostat::CommandId::SetWeeklySchedule
+                | thermostat::CommandId::GetWeeklySchedule
+                | thermostat::CommandId::ClearWeeklySchedule
+                | thermostat::CommandId::SetActivePresetRequest
+        ));
+
+    /// Attributes of a unit that only heats.
+    const HEAT_CLUSTER: Cluster<'static> = Self::CLUSTER.with_attrs(thermostat_attrs!(
+        OccupiedHeatingSetpoint | AbsMinHeatSetpointLimit | AbsMaxHeatSetpointLimit
+    ));
+
+    /// Attributes of a unit that only cools.
+    const COOL_CLUSTER: Cluster<'static> = Self::CLUSTER.with_attrs(thermostat_attrs!(
+        OccupiedCoolingSetpoint | AbsMinCoolSetpointLimit | AbsMaxCoolSetpointLimit
+    ));
+
+    /// Attributes of a unit that heats and cools but has no Auto mode.
+    const HEAT_COOL_CLUSTER: Cluster<'static> = Self::CLUSTER.with_attrs(thermostat_attrs!(
+        OccupiedCoolingSetpoint
+            | AbsMinCoolSetpointLimit
+            | AbsMaxCoolSetpointLimit
+            | OccupiedHeatingSetpoint
+            | AbsMinHeatSetpointLimit
+            | AbsMaxHeatSetpointLimit
+    ));
 
-    pub fn new(dataver: Dataver, device: Device) -> Self {
-        Self { dataver, device }
+    /// The cluster with the features of a unit with `caps`, and only the
+    /// attributes those features call for.
+    pub fn cluster(caps: &Capabilities) -> Cluster<'static> {
+        let (heat, cool) = match (caps.has_mode(Mode::Heating), caps.has_mode(Mode::Cooling)) {
+            // A thermostat heats or cools; a unit claiming neither gets both
+            (false, false) => (true, true),
+            modes => modes,
+        };
+        let mut features = thermostat::Feature::SCHEDULE_CONFIGURATION.bits()
+            | thermostat::Feature::PRESETS.bits();
+        if heat {
+            features |= thermostat::Feature::HEATING.bits();
+        }
+        if cool {
+            features |= thermostat::Feature::COOLING.bits();
+        }
+        let auto = heat && cool && caps.has_mode(Mode::Auto);
+        if auto {
+            features |= thermostat::Feature::AUTO_MODE.bits();
+        }
+        let cluster = match (heat, cool, auto) {
+            (_, _, true) => Self::CLUSTER,
+            (true, true, false) => Self::HEAT_COOL_CLUSTER,
+            (true, false, _) => Self::HEAT_CLUSTER,
+            (false, _, _) => Self::COOL_CLUSTER,
+        };
+        cluster.with_features(features)
     }
 
-    fn get_status(&self) -> Result<DaikinStatus, Error> {
-        self.device.get_status().map_err(|e| {
-            warn!("Failed to get status: {e}");
-            Error::from(ErrorCode::Busy)
-        })
+    pub fn new(
+        dataver: Dataver,
+        device: Device,
+        cooling_limits: SetpointLimits,
+        heating_limits: SetpointLimits,
+        presets: Arc<PresetStore>,
+        schedules: Arc<ScheduleStore>,
+        mac: String,
+    ) -> Self {
+        Self {
+            dataver,
+            device,
+            cooling_limits,
+            heating_limits,
+            presets,
+            schedules,
+            mac,
+            pair_write: Cell::new(None),
+        }
     }
 
-    fn update(&self, status: DaikinStatus) -> Result<(), Error> {
-        self.device.update(status).map_err(|e| {
-            warn!("Failed to update: {e}");
-            Error::from(ErrorCode::Busy)
-        })
+    /// Cooling setpoint range of the device, narrowed by the configured limits.
+    fn cooling_constraints(&self, status: &DaikinStatus) -> Option<ValueConstraints> {
+        ValueConstraints::from_item(&status.temperature.cooling)
+            .map(|c| self.cooling_limits.apply(c))
+    }
+
+    /// Heating setpoint range of the device, narrowed by the configured limits.
+    fn heating_constraints(&self, status: &DaikinStatus) -> Option<ValueConstraints> {
+        ValueConstraints::from_item(&status.temperature.heating)
+            .map(|c| self.heating_limits.apply(c))
+    }
+
+    /// Move the Auto mode offset so the setpoint pair centres on `center`,
+    /// kept where both setpoints are within their allowed range.
+    fn set_auto_center(&self, status: &mut DaikinStatus, center: f32) -> Result<(), Error> {
+        let center = clamp_center(
+            center,
+            self.heating_constraints(status),
+            self.cooling_constraints(status),
+        );
+        let offset = center - AUTO_REFERENCE;
+        let offset = match ValueConstraints::from_item(&status.temperature.automatic) {
+            Some(c) => validate_temp(offset, &c)?,
+            None => offset,
+        };
+        TemperatureTarget::auto(offset).apply_to_status(status);
+        debug!("Thermostat: auto offset → {offset:+}°C");
+        Ok(())
+    }
+
+    /// Move the Auto mode pair for a write of `value` to its `side` setpoint.
+    ///
+    /// Controllers often write both setpoints in one request, one after the
+    /// other, and the pair must not depend on the order. So a write to the
+    /// other setpoint within `AUTO_PAIR_WINDOW` is taken together with the
+    /// previous one: a setpoint written with the value reported before the
+    /// first write is unchanged and ignored, and when both changed the pair
+    /// centres on their midpoint.
+    fn set_auto_setpoint(
+        &self,
+        status: &mut DaikinStatus,
+        side: PairSide,
+        value: f32,
+    ) -> Result<(), Error> {
+        let center = auto_center(status);
+        let current = (center - AUTO_DEADBAND / 2.0, center + AUTO_DEADBAND / 2.0);
+        let (center, pending) =
+            pair_center(self.pair_write.take(), side, value, current, Instant::now());
+        self.pair_write.set(pending);
+        self.set_auto_center(status, center)
+    }
+
+    /// Status after applying `preset` to `base`, with its setpoints brought
+    /// within the allowed range. In Auto mode they move the setpoint pair.
+    fn preset_status(&self, preset: &Preset, base: &DaikinStatus) -> Result<DaikinStatus, Error> {
+        let mut status = base.clone();
+        if let Some(mode) = preset.mode {
+            StateTransition::new()
+                .turn_on()
+                .mode(mode.into())
+                .apply_to_status(&mut status)
+                .map_err(|e| {
+                    warn!("State transition failed: {e}");
+                    Error::from(ErrorCode::InvalidState)
+                })?;
+        }
+        if is_auto(&status) {
+            self.move_auto_pair(
+                &mut status,
+                preset.heating_setpoint,
+                preset.cooling_setpoint,
+            )?;
+        }
+        self.apply_setpoints(
+            &mut status,
+            preset.cooling_setpoint,
+            preset.heating_setpoint,
+        )?;
+        if preset.silent {
+            apply_silent(&mut status);
+        }
+        Ok(status)
+    }
+
+    /// Status after running the scheduled transition `t` on `base`.
+    ///
+    /// Only the setpoints the transition carries are applied; the mode is
+    /// left alone. In Auto mode they move the setpoint pair as a write of
+    /// them would, with both centring it on their midpoint.
+    fn transition_status(
+        &self,
+        t: &Transition,
+        base: &DaikinStatus,
+    ) -> Result<DaikinStatus, Error> {
+        let mut status = base.clone();
+        if is_auto(&status) {
+            self.move_auto_pair(&mut status, t.heat, t.cool)?;
+        }
+        self.apply_setpoints(&mut status, t.cool, t.heat)?;
+        Ok(status)
+    }
+
+    /// Move the Auto mode pair as a write of the given setpoints would, with
+    /// both centring it on their midpoint.
+    fn move_auto_pair(
+        &self,
+        status: &mut DaikinStatus,
+        heating: Option<f32>,
+        cooling: Option<f32>,
+    ) -> Result<(), Error> {
+        let center = match (heating, cooling) {
+            (Some(heat), Some(cool)) => (heat + cool) / 2.0,
+            (Some(heat), None) => PairSide::Heating.center(heat),
+            (None, Some(cool)) => PairSide::Cooling.center(cool),
+            (None, None) => return Ok(()),
+        };
+        let center = match ValueConstraints::from_item(&status.temperature.automatic) {
+            Some(c) => center.clamp(AUTO_REFERENCE + c.min, AUTO_REFERENCE + c.max),
+            None => center,
+        };
+        self.set_auto_center(status, center)
+    }
+
+    /// Set the cooling and heating setpoints, brought within the allowed range.
+    fn apply_setpoints(
+        &self,
+        status: &mut DaikinStatus,
+        cooling: Option<f32>,
+        heating: Option<f32>,
+    ) -> Result<(), Error> {
+        if let Some(temp) = cooling {
+            let temp = match self.cooling_constraints(status) {
+                Some(c) => validate_temp(temp.clamp(c.min, c.max), &c)?,
+                None => temp,
+            };
+            TemperatureTarget::cooling(temp).apply_to_status(status);
+        }
+        if let Some(temp) = heating {
+            let temp = match self.heating_constraints(status) {
+                Some(c) => validate_temp(temp.clamp(c.min, c.max), &c)?,
+                None => temp,
+            };
+            TemperatureTarget::heating(temp).apply_to_status(status);
+        }
+        Ok(())
+    }
+
+    /// Run the last scheduled transition that fell after `since` and no
+    /// later than `now`, if any.
+    pub(crate) fn run_schedule(&self, since: &Zoned, now: &Zoned) {
+        let Some(t) = self.schedules.due(&self.mac, since, now) else {
+            return;
+        };
+        info!(
+            "Thermostat: running scheduled transition at {:02}:{:02}",
+            t.time / 60,
+            t.time % 60
+        );
+        let base = self.device.get_status();
+        let result = self
+            .transition_status(&t, &base)
+            .and_then(|status| self.update(&base, &status));
+        if let Err(e) = result {
+            warn!("Scheduled transition failed: {e:?}");
+        }
+    }
+
+    /// The preset last activated, as long as the settings still match it.
+    fn active_preset(&self, status: &DaikinStatus) -> Option<Preset> {
+        let preset = self.presets.active(&self.mac)?;
+        let applied = self.preset_status(&preset, status).ok()?;
+        ChangeSet::diff(status, &applied)
+            .is_empty()
+            .then_some(preset)
+    }
+
+    fn update(&self, base: &DaikinStatus, status: &DaikinStatus) -> Result<(), Error> {
+        self.device.update(ChangeSet::diff(base, status))
     }
 }
 
@@ -70,8 +400,160 @@ fn system_mode_from_status(status: &DaikinStatus) -> thermostat::SystemModeEnum
     }
 }
 
+fn is_auto(status: &DaikinStatus) -> bool {
+    status.mode.get_enum() == Some(Mode::Auto)
+}
+
+/// Centre of the Auto mode setpoint pair.
+/// Centre of the Auto mode pair after a write of `value` to its `side`
+/// setpoint at `now`, with the pair reported as `current` and `previous` the
+/// last write to one of its setpoints. Also returns the write to remember
+/// for the next one, if it may still be paired.
+fn pair_center(
+    previous: Option<PairWrite>,
+    side: PairSide,
+    value: f32,
+    current: (f32, f32),
+    now: Instant,
+) -> (f32, Option<PairWrite>) {
+    let previous =
+        previous.filter(|w| w.side != side && now.duration_since(w.at) < AUTO_PAIR_WINDOW);
+    let before = previous.map_or(current, |w| w.before);
+    let changed = |side: PairSide, value: f32| (value - side.of(before)).abs() >= 0.01;
+    match previous {
+        Some(w) if !changed(side, value) => (w.side.center(w.value), None),
+        Some(w) if changed(w.side, w.value) => ((w.value + value) / 2.0, None),
+        Some(_) => (side.center(value), None),
+        None => {
+            let write = PairWrite {
+                at: now,
+                side,
+                value,
+                before,
+            };
+            (side.center(value), Some(write))
+        }
+    }
+}
+
+/// `center` of the Auto mode pair, moved so that its heating and cooling
+/// setpoints are within `heating` and `cooling` where both can be.
+fn clamp_center(
+    center: f32,
+    heating: Option<ValueConstraints>,
+    cooling: Option<ValueConstraints>,
+) -> f32 {
+    let half = AUTO_DEADBAND / 2.0;
+    let mut low = f32::NEG_INFINITY;
+    let mut high = f32::INFINITY;
+    if let Some(c) = heating {
+        low = low.max(c.min + half);
+        high = high.min(c.max + half);
+    }
+    if let Some(c) = cooling {
+        low = low.max(c.min - half);
+        high = high.min(c.max - half);
+    }
+    if low <= high {
+        center.clamp(low, high)
+    } else {
+        center
+    }
+}
+
+fn auto_center(status: &DaikinStatus) -> f32 {
+    AUTO_REFERENCE + status.temperature.automatic.get_f32().unwrap_or(0.0)
+}
+
+fn scenario_to_matter(scenario: Scenario) -> thermostat::PresetScenarioEnum {
+    match scenario {
+        Scenario::Occupied => thermostat::PresetScenarioEnum::Occupied,
+        Scenario::Unoccupied => thermostat::PresetScenarioEnum::Unoccupied,
+        Scenario::Sleep => thermostat::PresetScenarioEnum::Sleep,
+        Scenario::Wake => thermostat::PresetScenarioEnum::Wake,
+        Scenario::Vacation => thermostat::PresetScenarioEnum::Vacation,
+        Scenario::GoingToSleep => thermostat::PresetScenarioEnum::GoingToSleep,
+        Scenario::UserDefined => thermostat::PresetScenarioEnum::UserDefined,
+    }
+}
+
+const SCHEDULE_DAYS: [(Day, thermostat::ScheduleDayOfWeekBitmap); 8] = [
+    (Day::Sunday, thermostat::ScheduleDayOfWeekBitmap::SUNDAY),
+    (Day::Monday, thermostat::ScheduleDayOfWeekBitmap::MONDAY),
+    (Day::Tuesday, thermostat::ScheduleDayOfWeekBitmap::TUESDAY),
+    (
+        Day::Wednesday,
+        thermostat::ScheduleDayOfWeekBitmap::WEDNESDAY,
+    ),
+    (Day::Thursday, thermostat::ScheduleDayOfWeekBitmap::THURSDAY),
+    (Day::Friday, thermostat::ScheduleDayOfWeekBitmap::FRIDAY),
+    (Day::Saturday, thermostat::ScheduleDayOfWeekBitmap::SATURDAY),
+    (Day::Away, thermostat::ScheduleDayOfWeekBitmap::AWAY),
+];
+
+/// Days set in a Matter day-of-week bitmap, Sunday first.
+fn days_from_matter(bitmap: thermostat::ScheduleDayOfWeekBitmap) -> Vec<Day> {
+    SCHEDULE_DAYS
+        .iter()
+        .filter(|(_, bit)| bitmap.contains(*bit))
+        .map(|(day, _)| *day)
+        .collect()
+}
+
+fn day_to_matter(day: Day) -> thermostat::ScheduleDayOfWeekBitmap {
+    SCHEDULE_DAYS
+        .iter()
+        .find(|(d, _)| *d == day)
+        .map(|(_, bit)| *bit)
+        .unwrap_or(thermostat::ScheduleDayOfWeekBitmap::empty())
+}
+
+/// Scenarios used by `presets`, with how many presets each has.
+fn preset_types(presets: &[Preset]) -> Vec<(Scenario, u8)> {
+    let mut types: Vec<(Scenario, u8)> = Vec::new();
+    for preset in presets {
+        match types.iter_mut().find(|(s, _)| *s == preset.scenario) {
+            Some((_, count)) => *count += 1,
+            None => types.push((preset.scenario, 1)),
+        }
+    }
+    types
+}
+
+fn build_preset_type<P: TLVBuilderParent>(
+    builder: thermostat::PresetTypeStructBuilder<P>,
+    (scenario, count): (Scenario, u8),
+) -> Result<P, Error> {
+    builder
+        .preset_scenario(scenario_to_matter(scenario))?
+        .number_of_presets(count)?
+        .preset_type_features(thermostat::PresetTypeFeaturesBitmap::SUPPORTS_NAMES)?
+        .end()
+}
+
+fn build_preset<P: TLVBuilderParent>(
+    builder: thermostat::PresetStructBuilder<P>,
+    preset: &Preset,
+) -> Result<P, Error> {
+    builder
+        .preset_handle(Nullable::some(OctetStr::new(preset.handle.as_bytes())))?
+        .preset_scenario(scenario_to_matter(preset.scenario))?
+        .name(Some(Nullable::some(preset.name.as_str())))?
+        .cooling_setpoint(preset.cooling_setpoint.map(temp_to_matter))?
+        .heating_setpoint(preset.heating_setpoint.map(temp_to_matter))?
+        // Presets are edited in the data directory, not by controllers
+        .built_in(Nullable::some(true))?
+        .end()
+}
+
+/// Whether the unit has `mode`. Without metadata every mode is assumed.
+fn allows_mode(status: &DaikinStatus, mode: Mode) -> bool {
+    let modes = status.mode.allowed_enums();
+    modes.is_empty() || modes.contains(&mode)
+}
+
 /// Convert dsiot f32 °C to Matter 0.01°C i16.
-fn temp_to_matter(celsius: f32) -> i16 {
+pub(crate) fn temp_to_matter(celsius: f32) -> i16 {
     (celsius * 100.0) as i16
 }
 
@@ -101,7 +583,7 @@ impl thermostat::ClusterHandler for ThermostatHandler {
     }
 
     fn local_temperature(&self, _ctx: impl ReadContext) -> Result<Nullable<i16>, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         match status.sensors.temperature.get_f32() {
             Some(t) => Ok(Nullable::some(temp_to_matter(t))),
             None => Ok(Nullable::none()),
@@ -109,7 +591,7 @@ impl thermostat::ClusterHandler for ThermostatHandler {
     }
 
     fn outdoor_temperature(&self, _ctx: impl ReadContext) -> Result<Nullable<i16>, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         match status.sensors.outdoor_temperature.get_f32() {
             Some(t) => Ok(Nullable::some(temp_to_matter(t))),
             None => Ok(Nullable::none()),
@@ -117,7 +599,7 @@ impl thermostat::ClusterHandler for ThermostatHandler {
     }
 
     fn system_mode(&self, _ctx: impl ReadContext) -> Result<thermostat::SystemModeEnum, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         Ok(system_mode_from_status(&status))
     }
 
@@ -126,34 +608,38 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         _ctx: impl WriteContext,
         value: thermostat::SystemModeEnum,
     ) -> Result<(), Error> {
-        let mut status = self.get_status()?;
-        let transition = match value {
-            thermostat::SystemModeEnum::Off => StateTransition::new().turn_off(),
-            thermostat::SystemModeEnum::Auto => StateTransition::new().turn_on().mode(Mode::Auto),
-            thermostat::SystemModeEnum::Cool => {
-                StateTransition::new().turn_on().mode(Mode::Cooling)
-            }
-            thermostat::SystemModeEnum::Heat => {
-                StateTransition::new().turn_on().mode(Mode::Heating)
-            }
-            thermostat::SystemModeEnum::FanOnly => StateTransition::new().turn_on().mode(Mode::Fan),
-            thermostat::SystemModeEnum::Dry => {
-                StateTransition::new().turn_on().mode(Mode::Dehumidify)
-            }
+        let base = self.device.get_status();
+        let mut status = base.clone();
+        let mode = match value {
+            thermostat::SystemModeEnum::Off => None,
+            thermostat::SystemModeEnum::Auto => Some(Mode::Auto),
+            thermostat::SystemModeEnum::Cool => Some(Mode::Cooling),
+            thermostat::SystemModeEnum::Heat => Some(Mode::Heating),
+            thermostat::SystemModeEnum::FanOnly => Some(Mode::Fan),
+            thermostat::SystemModeEnum::Dry => Some(Mode::Dehumidify),
             _ => return Err(ErrorCode::ConstraintError.into()),
         };
+        let transition = match mode {
+            None => StateTransition::new().turn_off(),
+            Some(mode) if allows_mode(&base, mode) => StateTransition::new().turn_on().mode(mode),
+            // The unit does not have the mode
+            Some(_) => return Err(ErrorCode::ConstraintError.into()),
+        };
         transition.apply_to_status(&mut status).map_err(|e| {
             warn!("State transition failed: {e}");
             Error::from(ErrorCode::InvalidState)
         })?;
         debug!("Thermostat: system_mode → {:?}", value);
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
 
     fn occupied_cooling_setpoint(&self, _ctx: impl ReadContext) -> Result<i16, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
+        if is_auto(&status) {
+            return Ok(temp_to_matter(auto_center(&status) + AUTO_DEADBAND / 2.0));
+        }
         match status.temperature.cooling.get_f32() {
             Some(t) => Ok(temp_to_matter(t)),
             None => Ok(2600), // 26.0°C
@@ -165,21 +651,29 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         _ctx: impl WriteContext,
         value: i16,
     ) -> Result<(), Error> {
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
         let temp = temp_from_matter(value);
-        let temp = match ValueConstraints::from_item(&status.temperature.cooling) {
-            Some(c) => validate_temp(temp, &c)?,
-            None => temp,
-        };
-        TemperatureTarget::cooling(temp).apply_to_status(&mut status);
-        debug!("Thermostat: cooling setpoint → {temp}°C");
-        self.update(status)?;
+        if is_auto(&status) {
+            self.set_auto_setpoint(&mut status, PairSide::Cooling, temp)?;
+        } else {
+            let temp = match self.cooling_constraints(&status) {
+                Some(c) => validate_temp(temp, &c)?,
+                None => temp,
+            };
+            TemperatureTarget::cooling(temp).apply_to_status(&mut status);
+            debug!("Thermostat: cooling setpoint → {temp}°C");
+        }
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
 
     fn occupied_heating_setpoint(&self, _ctx: impl ReadContext) -> Result<i16, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
+        if is_auto(&status) {
+            return Ok(temp_to_matter(auto_center(&status) - AUTO_DEADBAND / 2.0));
+        }
         match status.temperature.heating.get_f32() {
             Some(t) => Ok(temp_to_matter(t)),
             None => Ok(2000), // 20.0°C
@@ -191,56 +685,73 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         _ctx: impl WriteContext,
         value: i16,
     ) -> Result<(), Error> {
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
         let temp = temp_from_matter(value);
-        let temp = match ValueConstraints::from_item(&status.temperature.heating) {
-            Some(c) => validate_temp(temp, &c)?,
-            None => temp,
-        };
-        TemperatureTarget::heating(temp).apply_to_status(&mut status);
-        debug!("Thermostat: heating setpoint → {temp}°C");
-        self.update(status)?;
+        if is_auto(&status) {
+            self.set_auto_setpoint(&mut status, PairSide::Heating, temp)?;
+        } else {
+            let temp = match self.heating_constraints(&status) {
+                Some(c) => validate_temp(temp, &c)?,
+                None => temp,
+            };
+            TemperatureTarget::heating(temp).apply_to_status(&mut status);
+            debug!("Thermostat: heating setpoint → {temp}°C");
+        }
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
 
     fn abs_min_heat_setpoint_limit(&self, _ctx: impl ReadContext) -> Result<i16, Error> {
-        let status = self.get_status()?;
-        match ValueConstraints::from_item(&status.temperature.heating) {
+        let status = self.device.get_status();
+        match self.heating_constraints(&status) {
             Some(c) => Ok(temp_to_matter(c.min)),
             None => Ok(700), // 7.0°C
         }
     }
 
     fn abs_max_heat_setpoint_limit(&self, _ctx: impl ReadContext) -> Result<i16, Error> {
-        let status = self.get_status()?;
-        match ValueConstraints::from_item(&status.temperature.heating) {
+        let status = self.device.get_status();
+        match self.heating_constraints(&status) {
             Some(c) => Ok(temp_to_matter(c.max)),
             None => Ok(3000), // 30.0°C
         }
     }
 
     fn abs_min_cool_setpoint_limit(&self, _ctx: impl ReadContext) -> Result<i16, Error> {
-        let status = self.get_status()?;
-        match ValueConstraints::from_item(&status.temperature.cooling) {
+        let status = self.device.get_status();
+        match self.cooling_constraints(&status) {
             Some(c) => Ok(temp_to_matter(c.min)),
             None => Ok(1600), // 16.0°C
         }
     }
 
     fn abs_max_cool_setpoint_limit(&self, _ctx: impl ReadContext) -> Result<i16, Error> {
-        let status = self.get_status()?;
-        match ValueConstraints::from_item(&status.temperature.cooling) {
+        let status = self.device.get_status();
+        match self.cooling_constraints(&status) {
             Some(c) => Ok(temp_to_matter(c.max)),
             None => Ok(3200), // 32.0°C
         }
     }
 
+    fn min_setpoint_dead_band(&self, _ctx: impl ReadContext) -> Result<i8, Error> {
+        // In 0.1°C
+        Ok((AUTO_DEADBAND * 10.0) as i8)
+    }
+
     fn control_sequence_of_operation(
         &self,
         _ctx: impl ReadContext,
     ) -> Result<thermostat::ControlSequenceOfOperationEnum, Error> {
-        Ok(thermostat::ControlSequenceOfOperationEnum::CoolingAndHeating)
+        let status = self.device.get_status();
+        let heat = allows_mode(&status, Mode::Heating);
+        let cool = allows_mode(&status, Mode::Cooling);
+        Ok(match (heat, cool) {
+            (true, false) => thermostat::ControlSequenceOfOperationEnum::HeatingOnly,
+            (false, true) => thermostat::ControlSequenceOfOperationEnum::CoolingOnly,
+            _ => thermostat::ControlSequenceOfOperationEnum::CoolingAndHeating,
+        })
     }
 
     fn set_control_sequence_of_operation(
@@ -255,7 +766,7 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         &self,
         _ctx: impl ReadContext,
     ) -> Result<thermostat::ThermostatRunningModeEnum, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         match PowerState::from_status(&status) {
             Some(PowerState::On) => match status.mode.get_enum() {
                 Some(Mode::Cooling) | Some(Mode::Dehumidify) => {
@@ -272,7 +783,7 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         &self,
         _ctx: impl ReadContext,
     ) -> Result<thermostat::RelayStateBitmap, Error> {
-        let status = self.get_status()?;
+        let status = self.device.get_status();
         match PowerState::from_status(&status) {
             Some(PowerState::On) => match status.mode.get_enum() {
                 Some(Mode::Cooling) | Some(Mode::Dehumidify) => {
@@ -289,6 +800,83 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         }
     }
 
+    fn start_of_week(&self, _ctx: impl ReadContext) -> Result<thermostat::StartOfWeekEnum, Error> {
+        Ok(thermostat::StartOfWeekEnum::Sunday)
+    }
+
+    fn number_of_weekly_transitions(&self, _ctx: impl ReadContext) -> Result<u8, Error> {
+        // Every day of the week, Away excluded
+        Ok((MAX_DAILY_TRANSITIONS * 7) as u8)
+    }
+
+    fn number_of_daily_transitions(&self, _ctx: impl ReadContext) -> Result<u8, Error> {
+        Ok(MAX_DAILY_TRANSITIONS as u8)
+    }
+
+    fn preset_types<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: ArrayAttributeRead<
+            thermostat::PresetTypeStructArrayBuilder<P>,
+            thermostat::PresetTypeStructBuilder<P>,
+        >,
+    ) -> Result<P, Error> {
+        let types = preset_types(&self.presets.presets());
+        match builder {
+            ArrayAttributeRead::ReadAll(mut array) => {
+                for preset_type in types {
+                    array = build_preset_type(array.push()?, preset_type)?;
+                }
+                array.end()
+            }
+            ArrayAttributeRead::ReadOne(index, elem) => match types.get(index as usize) {
+                Some(preset_type) => build_preset_type(elem, *preset_type),
+                None => Err(ErrorCode::ConstraintError.into()),
+            },
+            ArrayAttributeRead::ReadNone(array) => array.end(),
+        }
+    }
+
+    fn number_of_presets(&self, _ctx: impl ReadContext) -> Result<u8, Error> {
+        Ok(self.presets.presets().len().min(u8::MAX.into()) as u8)
+    }
+
+    fn active_preset_handle<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: NullableBuilder<P, OctetsBuilder<P>>,
+    ) -> Result<P, Error> {
+        let status = self.device.get_status();
+        match self.active_preset(&status) {
+            Some(preset) => builder.non_null()?.set(preset.handle.as_bytes()),
+            None => builder.null(),
+        }
+    }
+
+    fn presets<P: TLVBuilderParent>(
+        &self,
+        _ctx: impl ReadContext,
+        builder: ArrayAttributeRead<
+            thermostat::PresetStructArrayBuilder<P>,
+            thermostat::PresetStructBuilder<P>,
+        >,
+    ) -> Result<P, Error> {
+        let presets = self.presets.presets();
+        match builder {
+            ArrayAttributeRead::ReadAll(mut array) => {
+                for preset in &presets {
+                    array = build_preset(array.push()?, preset)?;
+                }
+                array.end()
+            }
+            ArrayAttributeRead::ReadOne(index, elem) => match presets.get(index as usize) {
+                Some(preset) => build_preset(elem, preset),
+                None => Err(ErrorCode::ConstraintError.into()),
+            },
+            ArrayAttributeRead::ReadNone(array) => array.end(),
+        }
+    }
+
     fn handle_setpoint_raise_lower(
         &self,
         _ctx: impl InvokeContext,
@@ -296,39 +884,46 @@ impl thermostat::ClusterHandler for ThermostatHandler {
     ) -> Result<(), Error> {
         let mode = req.mode()?;
         let amount = req.amount()? as f32 * 0.1;
-        let mut status = self.get_status()?;
+        let base = self.device.get_status();
+        let mut status = base.clone();
 
         use thermostat::SetpointRaiseLowerModeEnum;
-        if matches!(
-            mode,
-            SetpointRaiseLowerModeEnum::Cool | SetpointRaiseLowerModeEnum::Both
-        ) {
-            let current = status.temperature.cooling.get_f32().unwrap_or(26.0);
-            let new_temp = current + amount;
-            let new_temp = match ValueConstraints::from_item(&status.temperature.cooling) {
-                Some(c) => validate_temp(new_temp, &c)?,
-                None => new_temp,
-            };
-            TemperatureTarget::cooling(new_temp).apply_to_status(&mut status);
-        }
-        if matches!(
-            mode,
-            SetpointRaiseLowerModeEnum::Heat | SetpointRaiseLowerModeEnum::Both
-        ) {
-            let current = status.temperature.heating.get_f32().unwrap_or(20.0);
-            let new_temp = current + amount;
-            let new_temp = match ValueConstraints::from_item(&status.temperature.heating) {
-                Some(c) => validate_temp(new_temp, &c)?,
-                None => new_temp,
-            };
-            TemperatureTarget::heating(new_temp).apply_to_status(&mut status);
+        if is_auto(&status) {
+            // Both setpoints move together, so every mode shifts the offset once
+            let center = auto_center(&status) + amount;
+            self.set_auto_center(&mut status, center)?;
+        } else {
+            if matches!(
+                mode,
+                SetpointRaiseLowerModeEnum::Cool | SetpointRaiseLowerModeEnum::Both
+            ) {
+                let current = status.temperature.cooling.get_f32().unwrap_or(26.0);
+                let new_temp = current + amount;
+                let new_temp = match self.cooling_constraints(&status) {
+                    Some(c) => validate_temp(new_temp, &c)?,
+                    None => new_temp,
+                };
+                TemperatureTarget::cooling(new_temp).apply_to_status(&mut status);
+            }
+            if matches!(
+                mode,
+                SetpointRaiseLowerModeEnum::Heat | SetpointRaiseLowerModeEnum::Both
+            ) {
+                let current = status.temperature.heating.get_f32().unwrap_or(20.0);
+                let new_temp = current + amount;
+                let new_temp = match self.heating_constraints(&status) {
+                    Some(c) => validate_temp(new_temp, &c)?,
+                    None => new_temp,
+                };
+                TemperatureTarget::heating(new_temp).apply_to_status(&mut status);
+            }
         }
 
         debug!(
             "Thermostat: setpoint_raise_lower mode={:?} amount={amount}°C",
             mode
         );
-        self.update(status)?;
+        self.update(&base, &status)?;
         self.dataver.changed();
         Ok(())
     }
@@ -336,22 +931,114 @@ impl thermostat::ClusterHandler for ThermostatHandler {
     fn handle_set_weekly_schedule(
         &self,
         _ctx: impl InvokeContext,
-        _req: thermostat::SetWeeklyScheduleRequest<'_>,
+        req: thermostat::SetWeeklyScheduleRequest<'_>,
     ) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        let count = req.number_of_transitions_for_sequence()? as usize;
+        if count > MAX_DAILY_TRANSITIONS {
+            return Err(ErrorCode::ResourceExhausted.into());
+        }
+        let days = days_from_matter(req.day_of_week_for_sequence()?);
+        let mode = req.mode_for_sequence()?;
+        if days.is_empty() || mode.is_empty() {
+            return Err(ErrorCode::InvalidCommand.into());
+        }
+        let heat = mode.contains(thermostat::ScheduleModeBitmap::HEAT_SETPOINT_PRESENT);
+        let cool = mode.contains(thermostat::ScheduleModeBitmap::COOL_SETPOINT_PRESENT);
+
+        let mut transitions = Vec::with_capacity(count);
+        for t in req.transitions()?.iter() {
+            let t = t?;
+            let time = t.transition_time()?;
+            if time >= 24 * 60 {
+                return Err(ErrorCode::ConstraintError.into());
+            }
+            let setpoint = |present: bool, value: Nullable<i16>| match value.into_option() {
+                Some(v) if present => Ok(Some(temp_from_matter(v))),
+                None if !present => Ok(None),
+                // Setpoints must be given exactly for the modes in the bitmap
+                _ => Err(Error::from(ErrorCode::InvalidCommand)),
+            };
+            transitions.push(Transition {
+                time,
+                heat: setpoint(heat, t.heat_setpoint()?)?,
+                cool: setpoint(cool, t.cool_setpoint()?)?,
+            });
+        }
+        if transitions.len() != count {
+            return Err(ErrorCode::InvalidCommand.into());
+        }
+
+        debug!("Thermostat: weekly schedule for {days:?} → {transitions:?}");
+        self.schedules
+            .set(&self.mac, &days, &transitions)
+            .map_err(|e| {
+                warn!("Failed to save weekly schedule: {e}");
+                Error::from(ErrorCode::Failure)
+            })
     }
 
     fn handle_get_weekly_schedule<P: TLVBuilderParent>(
         &self,
         _ctx: impl InvokeContext,
-        _req: thermostat::GetWeeklyScheduleRequest<'_>,
-        _response: thermostat::GetWeeklyScheduleResponseBuilder<P>,
+        req: thermostat::GetWeeklyScheduleRequest<'_>,
+        response: thermostat::GetWeeklyScheduleResponseBuilder<P>,
     ) -> Result<P, Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        let mode = req.mode_to_return()?;
+        let heat = mode.contains(thermostat::ScheduleModeBitmap::HEAT_SETPOINT_PRESENT);
+        let cool = mode.contains(thermostat::ScheduleModeBitmap::COOL_SETPOINT_PRESENT);
+        // Only one day is returned: the first requested one that has a schedule
+        let (day, transitions) = days_from_matter(req.days_to_return()?)
+            .into_iter()
+            .map(|day| {
+                let transitions: Vec<_> = self
+                    .schedules
+                    .get(&self.mac, day)
+                    .into_iter()
+                    .map(|t| Transition {
+                        heat: t.heat.filter(|_| heat),
+                        cool: t.cool.filter(|_| cool),
+                        ..t
+                    })
+                    .filter(|t| t.heat.is_some() || t.cool.is_some())
+                    .collect();
+                (day, transitions)
+            })
+            .find(|(_, transitions)| !transitions.is_empty())
+            .ok_or(Error::from(ErrorCode::NotFound))?;
+
+        let mut returned = thermostat::ScheduleModeBitmap::empty();
+        if transitions.iter().any(|t| t.heat.is_some()) {
+            returned |= thermostat::ScheduleModeBitmap::HEAT_SETPOINT_PRESENT;
+        }
+        if transitions.iter().any(|t| t.cool.is_some()) {
+            returned |= thermostat::ScheduleModeBitmap::COOL_SETPOINT_PRESENT;
+        }
+        let mut array = response
+            .number_of_transitions_for_sequence(transitions.len() as u8)?
+            .day_of_week_for_sequence(day_to_matter(day))?
+            .mode_for_sequence(returned)?
+            .transitions()?;
+        for t in &transitions {
+            let setpoint = |temp: Option<f32>| match temp {
+                Some(t) => Nullable::some(temp_to_matter(t)),
+                None => Nullable::none(),
+            };
+            array = array
+                .push()?
+                .transition_time(t.time)?
+                .heat_setpoint(setpoint(t.heat))?
+                .cool_setpoint(setpoint(t.cool))?
+                .end()?;
+        }
+        array.end()?.end()
     }
 
     fn handle_clear_weekly_schedule(&self, _ctx: impl InvokeContext) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        debug!("Thermostat: weekly schedule cleared");
+        self.schedules.clear(&self.mac).map_err(|e| {
+            warn!("Failed to save weekly schedule: {e}");
+            Error::from(ErrorCode::Failure)
+        })
     }
 
     fn handle_set_active_schedule_request(
@@ -365,9 +1052,29 @@ impl thermostat::ClusterHandler for ThermostatHandler {
     fn handle_set_active_preset_request(
         &self,
         _ctx: impl InvokeContext,
-        _req: thermostat::SetActivePresetRequestRequest<'_>,
+        req: thermostat::SetActivePresetRequestRequest<'_>,
     ) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        let Some(handle) = req.preset_handle()?.into_option() else {
+            debug!("Thermostat: active preset → none");
+            if let Err(e) = self.presets.set_active(&self.mac, None) {
+                warn!("Failed to save active preset: {e}");
+            }
+            self.dataver.changed();
+            return Ok(());
+        };
+        let preset = self
+            .presets
+            .get(handle.0)
+            .ok_or(Error::from(ErrorCode::InvalidCommand))?;
+        let base = self.device.get_status();
+        let status = self.preset_status(&preset, &base)?;
+        debug!("Thermostat: active preset → {}", preset.name);
+        self.update(&base, &status)?;
+        if let Err(e) = self.presets.set_active(&self.mac, Some(&preset.handle)) {
+            warn!("Failed to save active preset: {e}");
+        }
+        self.dataver.changed();
+        Ok(())
     }
 
     fn handle_add_thermostat_suggestion<P: TLVBuilderParent>(
@@ -387,6 +1094,8 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         Err(ErrorCode::InvalidCommand.into())
     }
 
+    // Presets are edited in `presets.toml` only, so the atomic writes the
+    // Presets feature calls for are not supported; see the README
     fn handle_atomic_request<P: TLVBuilderParent>(
         &self,
         _ctx: impl InvokeContext,
@@ -396,3 +1105,87 @@ impl thermostat::ClusterHandler for ThermostatHandler {
         Err(ErrorCode::InvalidCommand.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURRENT: (f32, f32) = (24.0, 26.0);
+
+    /// Centre after writing `writes` in order, `gap` apart.
+    fn write_pair(writes: &[(PairSide, f32)], gap: Duration) -> f32 {
+        let mut now = Instant::now();
+        let mut previous = None;
+        let mut center = 25.0;
+        for &(side, value) in writes {
+            (center, previous) = pair_center(previous, side, value, CURRENT, now);
+            now += gap;
+        }
+        center
+    }
+
+    #[test]
+    fn pair_single_write() {
+        let (center, pending) = pair_center(None, PairSide::Cooling, 27.0, CURRENT, Instant::now());
+        assert_eq!(center, 26.0);
+        assert!(pending.is_some());
+        assert_eq!(
+            write_pair(&[(PairSide::Heating, 22.0)], Duration::ZERO),
+            23.0
+        );
+    }
+
+    #[test]
+    fn pair_order() {
+        let gap = Duration::from_millis(100);
+        let heat_first = [(PairSide::Heating, 22.0), (PairSide::Cooling, 28.0)];
+        let cool_first = [(PairSide::Cooling, 28.0), (PairSide::Heating, 22.0)];
+        assert_eq!(write_pair(&heat_first, gap), 25.0);
+        assert_eq!(write_pair(&cool_first, gap), 25.0);
+    }
+
+    #[test]
+    fn pair_unchanged_side() {
+        let gap = Duration::from_millis(100);
+        // The cooling setpoint is written with the value reported before
+        let heat_first = [(PairSide::Heating, 21.0), (PairSide::Cooling, 26.0)];
+        let cool_first = [(PairSide::Cooling, 26.0), (PairSide::Heating, 21.0)];
+        assert_eq!(write_pair(&heat_first, gap), 22.0);
+        assert_eq!(write_pair(&cool_first, gap), 22.0);
+    }
+
+    #[test]
+    fn pair_window() {
+        // Too far apart, so the second write moves the pair on its own
+        let late = AUTO_PAIR_WINDOW + Duration::from_millis(1);
+        let writes = [(PairSide::Heating, 22.0), (PairSide::Cooling, 28.0)];
+        assert_eq!(write_pair(&writes, late), 27.0);
+        // Two writes to the same setpoint are not a pair
+        let gap = Duration::from_millis(100);
+        let same = [(PairSide::Heating, 22.0), (PairSide::Heating, 20.0)];
+        assert_eq!(write_pair(&same, gap), 21.0);
+        // Nor is a third write after a pair
+        let three = [
+            (PairSide::Heating, 22.0),
+            (PairSide::Cooling, 28.0),
+            (PairSide::Cooling, 30.0),
+        ];
+        assert_eq!(write_pair(&three, gap), 29.0);
+    }
+
+    #[test]
+    fn center_limits() {
+        let heating = Some(ValueConstraints::new(10.0, 24.0, 0.5));
+        let cooling = Some(ValueConstraints::new(22.0, 30.0, 0.5));
+        // The heating setpoint may not exceed 24 and cooling not drop below 22
+        assert_eq!(clamp_center(27.0, heating.clone(), cooling.clone()), 25.0);
+        assert_eq!(clamp_center(20.0, heating.clone(), cooling.clone()), 21.0);
+        assert_eq!(clamp_center(24.0, heating.clone(), cooling.clone()), 24.0);
+        assert_eq!(clamp_center(27.0, None, cooling), 27.0);
+        assert_eq!(clamp_center(27.0, heating, None), 25.0);
+        // Limits the pair cannot meet leave the centre alone
+        let heating = Some(ValueConstraints::new(10.0, 15.0, 0.5));
+        let cooling = Some(ValueConstraints::new(25.0, 30.0, 0.5));
+        assert_eq!(clamp_center(20.0, heating, cooling), 20.0);
+    }
+}
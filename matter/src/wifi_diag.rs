@@ -1,6 +1,3 @@
-use std::cell::Cell;
-use std::time::Instant;
-
 use dsiot::protocol::DaikinInfo;
 use rs_matter::dm::clusters::decl::wi_fi_network_diagnostics;
 use rs_matter::dm::{Cluster, Dataver, InvokeContext, ReadContext};
@@ -12,13 +9,10 @@ use wi_fi_network_diagnostics::SecurityTypeEnum;
 
 use crate::device::Device;
 
-const RSSI_CACHE_TTL_SECS: u64 = 60;
-
 pub struct WifiDiagHandler {
     pub(crate) dataver: Dataver,
     info: DaikinInfo,
     device: Device,
-    cached_rssi: Cell<Option<(Instant, i8)>>,
 }
 
 impl WifiDiagHandler {
@@ -33,27 +27,6 @@ impl WifiDiagHandler {
             dataver,
             info,
             device,
-            cached_rssi: Cell::new(None),
-        }
-    }
-
-    fn get_rssi(&self) -> Option<i8> {
-        if let Some((ts, rssi)) = self.cached_rssi.get()
-            && ts.elapsed().as_secs() < RSSI_CACHE_TTL_SECS
-        {
-            return Some(rssi);
-        }
-        match self.device.get_info() {
-            Ok(info) => {
-                if let Some(rssi) = info.rssi {
-                    self.cached_rssi.set(Some((Instant::now(), rssi)));
-                }
-                info.rssi
-            }
-            Err(e) => {
-                warn!("Failed to get info for RSSI: {e}");
-                self.cached_rssi.get().map(|(_, rssi)| rssi)
-            }
         }
     }
 }
@@ -104,7 +77,7 @@ impl wi_fi_network_diagnostics::ClusterHandler for WifiDiagHandler {
     }
 
     fn rssi(&self, _ctx: impl ReadContext) -> Result<Nullable<i8>, Error> {
-        Ok(match self.get_rssi() {
+        Ok(match self.device.rssi() {
             Some(v) => Nullable::some(v),
             None => Nullable::none(),
         })
@@ -0,0 +1,25 @@
+//! UDP responder for the `DAIKIN_UDP/common/basic_info` discovery broadcast.
+
+use crate::Simulator;
+use std::io;
+use tokio::net::UdpSocket;
+
+const DISCOVERY_PAYLOAD: &[u8] = b"DAIKIN_UDP/common/basic_info";
+
+/// Answer discovery broadcasts on the given socket until an I/O error occurs.
+///
+/// The socket is normally bound to port 30050, which is where
+/// `daikin_client::discovery` sends its broadcast.
+pub async fn serve_discovery(sim: Simulator, socket: UdpSocket) -> io::Result<()> {
+    info!("listening for discovery on {}", socket.local_addr()?);
+    let mut buf = [0; 256];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        if &buf[..len] != DISCOVERY_PAYLOAD {
+            debug!("ignoring unknown UDP payload from {peer}");
+            continue;
+        }
+        debug!("discovery request from {peer}");
+        socket.send_to(sim.basic_info().as_bytes(), peer).await?;
+    }
+}
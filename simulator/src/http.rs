@@ -0,0 +1,76 @@
+//! Minimal HTTP/1.1 server for the `/dsiot/multireq` endpoint.
+
+use crate::Simulator;
+use serde_json::Value;
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MULTIREQ_PATH: &str = "/dsiot/multireq";
+
+/// Serve `/dsiot/multireq` requests on the given listener until an I/O error occurs.
+pub async fn serve_http(sim: Simulator, listener: TcpListener) -> io::Result<()> {
+    info!("listening for HTTP on {}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let sim = sim.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(&sim, stream).await {
+                warn!("HTTP connection from {peer} failed: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(sim: &Simulator, mut stream: TcpStream) -> io::Result<()> {
+    let mut buf = Vec::new();
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let mut chunk = [0; 1024];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buf.split_off(header_end);
+    while body.len() < content_length {
+        let mut chunk = [0; 1024];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, response) = match (parts.next(), parts.next()) {
+        (Some("POST"), Some(MULTIREQ_PATH)) => match serde_json::from_slice::<Value>(&body) {
+            Ok(payload) => {
+                debug!("multireq: {payload}");
+                ("200 OK", sim.handle_multireq(&payload).to_string())
+            }
+            Err(e) => ("400 Bad Request", format!("{{\"error\":\"{e}\"}}")),
+        },
+        _ => ("404 Not Found", String::from("{}")),
+    };
+
+    let reply = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    stream.shutdown().await
+}
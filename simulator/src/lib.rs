@@ -0,0 +1,487 @@
+//! Local dsiot device simulator.
+//!
+//! This crate emulates the HTTP and UDP endpoints of a Daikin Wi-Fi adapter so
+//! that clients can be exercised end to end without real hardware.
+
+#[macro_use]
+extern crate log;
+
+mod discovery;
+mod http;
+
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub use discovery::serve_discovery;
+pub use http::serve_http;
+
+/// Response status code for a successful read (`op: 2`).
+pub const RSC_OK: u32 = 2000;
+/// Response status code for a successful write (`op: 3`).
+pub const RSC_CHANGED: u32 = 2004;
+/// Response status code for a malformed request.
+pub const RSC_BAD_REQUEST: u32 = 4000;
+/// Response status code for a write to a read-only property.
+pub const RSC_READ_ONLY: u32 = 4005;
+/// Response status code for an unknown path or property.
+pub const RSC_NOT_FOUND: u32 = 4004;
+
+const STATUS_FIXTURE: &str = include_str!("../../dsiot/src/fixtures/status.json");
+const INFO_FIXTURE: &str = include_str!("../../dsiot/src/fixtures/info.json");
+
+/// Simulated Daikin adapter holding a mutable property tree per path.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    trees: Arc<Mutex<HashMap<String, Value>>>,
+}
+
+impl Default for Simulator {
+    /// Create a simulator seeded with the bundled dsiot fixtures.
+    fn default() -> Self {
+        Self::from_fixtures(&[STATUS_FIXTURE, INFO_FIXTURE])
+            .expect("bundled fixtures must be valid")
+    }
+}
+
+impl Simulator {
+    /// Create a simulator from `/dsiot/multireq` response bodies.
+    ///
+    /// Each `fr` path in the given responses becomes addressable by `op: 2`
+    /// reads and `op: 3` writes.
+    pub fn from_fixtures(fixtures: &[&str]) -> anyhow::Result<Self> {
+        let mut trees = HashMap::new();
+        for fixture in fixtures {
+            let body: Value = serde_json::from_str(fixture)?;
+            let responses = body["responses"]
+                .as_array()
+                .ok_or_else(|| anyhow::anyhow!("fixture has no responses array"))?;
+            for res in responses {
+                let (Some(from), Some(content)) = (res["fr"].as_str(), res.get("pc")) else {
+                    continue;
+                };
+                trees.insert(from.to_string(), content.clone());
+            }
+        }
+        Ok(Self {
+            trees: Arc::new(Mutex::new(trees)),
+        })
+    }
+
+    /// Handle a `/dsiot/multireq` request body and build the response body.
+    pub fn handle_multireq(&self, payload: &Value) -> Value {
+        let Some(requests) = payload["requests"].as_array() else {
+            return json!({ "responses": [] });
+        };
+        let mut trees = self.trees.lock().unwrap();
+        let responses: Vec<Value> = requests
+            .iter()
+            .map(|req| {
+                let to = req["to"].as_str().unwrap_or_default();
+                let path = to.split('?').next().unwrap_or_default();
+                match (req["op"].as_u64(), trees.get_mut(path)) {
+                    (_, None) => json!({ "fr": path, "rsc": RSC_NOT_FOUND }),
+                    (Some(2), Some(tree)) => json!({ "fr": path, "pc": tree, "rsc": RSC_OK }),
+                    (Some(3), Some(tree)) => {
+                        let rsc = match req.get("pc") {
+                            Some(pc) if pc["pn"] == tree["pn"] => merge(tree, pc),
+                            _ => RSC_BAD_REQUEST,
+                        };
+                        debug!("write {path}: rsc {rsc}");
+                        json!({ "fr": path, "rsc": rsc })
+                    }
+                    (_, Some(_)) => json!({ "fr": path, "rsc": RSC_BAD_REQUEST }),
+                }
+            })
+            .collect();
+        json!({ "responses": responses })
+    }
+
+    /// Read the raw `pv` of a property, e.g. `value("/dsiot/edge/adr_0100.dgc_status", &["e_1002", "e_A002", "p_01"])`.
+    pub fn value(&self, to: &str, path: &[&str]) -> Option<Value> {
+        let trees = self.trees.lock().unwrap();
+        let mut node = trees.get(to)?;
+        for name in path {
+            node = find_child(node, name)?;
+        }
+        node.get("pv").cloned()
+    }
+
+    /// Overwrite the raw `pv` of a property, as if the device changed on its own
+    /// (e.g. from the IR remote). Returns `false` if the property does not exist.
+    pub fn set_value(&self, to: &str, path: &[&str], pv: Value) -> bool {
+        let mut trees = self.trees.lock().unwrap();
+        let Some(mut node) = trees.get_mut(to) else {
+            return false;
+        };
+        for name in path {
+            let Some(child) = find_child_mut(node, name) else {
+                return false;
+            };
+            node = child;
+        }
+        match node.get_mut("pv") {
+            Some(value) => {
+                *value = pv;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Build the `DAIKIN_UDP/common/basic_info` reply text.
+    pub fn basic_info(&self) -> String {
+        let string = |to: &str, name: &str| {
+            self.value(to, &[name])
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default()
+        };
+        let name: String = string("/dsiot/edge.adp_d", "name")
+            .bytes()
+            .map(|b| format!("%{b:02x}"))
+            .collect();
+        format!(
+            "ret=OK,type=GPF,cdev=RA,protocol=DGC,reg={},ver={},rev={},comm_err=0,lpw_flag=0,adp_kind=4,mac={},ssid={},adp_mode=ap_run,method=polling,name={name},edid={},api_ver=2_2",
+            string("/dsiot/edge.adp_i", "reg"),
+            string("/dsiot/edge.adp_i", "ver"),
+            string("/dsiot/edge.adp_i", "rev"),
+            string("/dsiot/edge.adp_i", "mac"),
+            string("/dsiot/edge.adp_i", "ssid"),
+            string("/dsiot/edge.adp_i", "edid"),
+        )
+    }
+}
+
+fn find_child<'a>(node: &'a Value, name: &str) -> Option<&'a Value> {
+    node["pch"].as_array()?.iter().find(|c| c["pn"] == name)
+}
+
+fn find_child_mut<'a>(node: &'a mut Value, name: &str) -> Option<&'a mut Value> {
+    node.get_mut("pch")?
+        .as_array_mut()?
+        .iter_mut()
+        .find(|c| c["pn"] == name)
+}
+
+/// Apply the `pv` values of a write request tree onto a stored tree.
+///
+/// The stored tree is only modified when every requested property exists and
+/// is writable, mirroring the all-or-nothing behavior of the device.
+fn merge(tree: &mut Value, request: &Value) -> u32 {
+    let mut updated = tree.clone();
+    let rsc = merge_into(&mut updated, request);
+    if rsc == RSC_CHANGED {
+        *tree = updated;
+    }
+    rsc
+}
+
+fn merge_into(node: &mut Value, request: &Value) -> u32 {
+    let Some(children) = request["pch"].as_array() else {
+        return RSC_BAD_REQUEST;
+    };
+    for child in children {
+        let Some(name) = child["pn"].as_str() else {
+            return RSC_BAD_REQUEST;
+        };
+        let Some(target) = find_child_mut(node, name) else {
+            return RSC_NOT_FOUND;
+        };
+        let rsc = if child.get("pch").is_some() {
+            merge_into(target, child)
+        } else if let Some(pv) = child.get("pv") {
+            // pt 2 is read-write, pt 3 is read-only
+            if target["pt"] != 2 {
+                return RSC_READ_ONLY;
+            }
+            target["pv"] = pv.clone();
+            RSC_CHANGED
+        } else {
+            RSC_BAD_REQUEST
+        };
+        if rsc != RSC_CHANGED {
+            return rsc;
+        }
+    }
+    RSC_CHANGED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use daikin_client::{Daikin, DaikinError, ReqwestClient, StatusEvent};
+    use dsiot::protocol::{DaikinRequest, DaikinResponse, DaikinStatus};
+    use dsiot::{Mode, PowerState};
+    use futures::StreamExt;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::time::Duration;
+
+    const ADR_0100: &str = "/dsiot/edge/adr_0100.dgc_status";
+
+    #[test]
+    fn read_status() {
+        let sim = Simulator::default();
+        let body = sim.handle_multireq(&json!({"requests": [
+            { "op": 2, "to": "/dsiot/edge/adr_0100.dgc_status?filter=pv,md" },
+            { "op": 2, "to": "/dsiot/edge/adr_0200.dgc_status?filter=pv,md" }
+        ]}));
+        let status: DaikinStatus = serde_json::from_value::<DaikinResponse>(body)
+            .expect("Invalid response.")
+            .into();
+        assert_eq!(status.mode.get_enum(), Some(Mode::Cooling));
+        assert_eq!(status.sensors.outdoor_temperature.get_f32(), Some(19.0));
+    }
+
+    #[test]
+    fn write_status() {
+        let sim = Simulator::default();
+        let body = sim.handle_multireq(&json!({"requests": [{ "op": 2, "to": ADR_0100 }]}));
+        let mut status: DaikinStatus = serde_json::from_value::<DaikinResponse>(body)
+            .unwrap()
+            .into();
+        status.power.set_value(1.0);
+        status.mode.set_value(Mode::Heating);
+        let req = serde_json::to_value(DaikinRequest::from(status)).unwrap();
+
+        let body = sim.handle_multireq(&req);
+        assert_eq!(body["responses"][0]["rsc"], RSC_CHANGED);
+        assert_eq!(
+            sim.value(ADR_0100, &["e_1002", "e_A002", "p_01"]),
+            Some(json!("01"))
+        );
+        assert_eq!(
+            sim.value(ADR_0100, &["e_1002", "e_3001", "p_01"]),
+            Some(json!("0100"))
+        );
+    }
+
+    #[test]
+    fn write_rejected() {
+        let sim = Simulator::default();
+        let write = |entity: &str, prop: &str| {
+            sim.handle_multireq(&json!({"requests": [{
+                "op": 3,
+                "to": ADR_0100,
+                "pc": {"pn": "dgc_status", "pch": [{"pn": "e_1002", "pch": [
+                    {"pn": "e_A002", "pch": [{"pn": "p_01", "pv": "01"}]},
+                    {"pn": entity, "pch": [{"pn": prop, "pv": "00"}]}
+                ]}]}
+            }]}))["responses"][0]["rsc"]
+                .clone()
+        };
+        assert_eq!(write("e_A00B", "p_01"), RSC_READ_ONLY);
+        assert_eq!(write("e_3001", "p_FF"), RSC_NOT_FOUND);
+        // Rejected writes leave the whole tree untouched
+        assert_eq!(
+            sim.value(ADR_0100, &["e_1002", "e_A002", "p_01"]),
+            Some(json!("00"))
+        );
+
+        let body = sim.handle_multireq(&json!({"requests": [{ "op": 2, "to": "/hoge" }]}));
+        assert_eq!(body["responses"][0]["rsc"], RSC_NOT_FOUND);
+    }
+
+    #[test]
+    fn basic_info() {
+        let sim = Simulator::default();
+        let info: dsiot::DaikinInfo =
+            serde_qs::from_str(&sim.basic_info().replace(',', "&")).expect("Invalid basic_info.");
+        assert_eq!(info.name, "display_name");
+        assert_eq!(info.mac, "00005E005342");
+        assert_eq!(info.version, "2.7.0");
+        assert_eq!(info.edid, 19088743);
+    }
+
+    #[tokio::test]
+    async fn client_roundtrip() {
+        let sim = Simulator::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = match listener.local_addr().unwrap() {
+            std::net::SocketAddr::V4(addr) => addr,
+            _ => unreachable!(),
+        };
+        tokio::spawn(serve_http(sim.clone(), listener));
+
+        let daikin = Daikin::with_socket_addr(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, addr.port()),
+            ReqwestClient::try_new().unwrap(),
+        );
+        let info = daikin.get_info().await.unwrap();
+        assert_eq!(info.name, "display_name");
+
+        let path = "adr_0200.dgc_status/e_1003/e_2009/p_01".parse().unwrap();
+        let temp: dsiot::Item = daikin.get_property(&path).await.unwrap();
+        assert_eq!(temp.get_f32(), Some(19.0));
+
+        let mut status = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::Off));
+        status.power.set_value(1.0);
+        daikin.update(status).await.unwrap();
+        assert_eq!(
+            sim.value(ADR_0100, &["e_1002", "e_A002", "p_01"]),
+            Some(json!("01"))
+        );
+    }
+
+    #[tokio::test]
+    async fn client_discovery() {
+        let sim = Simulator::default();
+        // `daikin_client::discovery` broadcasts to this fixed port
+        let socket = tokio::net::UdpSocket::bind("0.0.0.0:30050").await.unwrap();
+        tokio::spawn(serve_discovery(sim, socket));
+
+        let found: Vec<_> = daikin_client::discovery(Duration::from_millis(500))
+            .await
+            .collect()
+            .await;
+        let infos: Vec<_> = found.into_iter().map(|res| res.unwrap().1).collect();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].name, "display_name");
+        assert_eq!(infos[0].mac, "00005E005342");
+    }
+
+    #[tokio::test]
+    async fn client_errors() {
+        let sim = Simulator::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve_http(sim, listener));
+
+        let daikin = Daikin::with_socket_addr(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            ReqwestClient::try_new().unwrap(),
+        );
+        let path = "adr_0300.dgc_status/e_1003/p_01".parse().unwrap();
+        let err = daikin.get_property::<f32>(&path).await.unwrap_err();
+        assert!(matches!(
+            err,
+            DaikinError::StatusCode {
+                code: RSC_NOT_FOUND,
+                ..
+            }
+        ));
+
+        let path = "adr_0200.dgc_status/e_1003/e_2009/p_FF".parse().unwrap();
+        let err = daikin.get_property::<f32>(&path).await.unwrap_err();
+        assert!(matches!(err, DaikinError::MissingProperty(_)));
+
+        // Nothing listens on port 9 of localhost
+        let daikin = Daikin::with_socket_addr(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 9),
+            ReqwestClient::try_new().unwrap(),
+        );
+        let err = daikin.get_info().await.unwrap_err();
+        assert!(matches!(err, DaikinError::Transport(_)));
+    }
+
+    #[tokio::test]
+    async fn partial_status() {
+        // A unit without the outdoor unit resource answers 4004 for that path
+        let mut fixture: Value = serde_json::from_str(STATUS_FIXTURE).unwrap();
+        fixture["responses"].as_array_mut().unwrap().truncate(1);
+        let sim = Simulator::from_fixtures(&[&fixture.to_string()]).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve_http(sim, listener));
+
+        let daikin = Daikin::with_socket_addr(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            ReqwestClient::try_new().unwrap(),
+        );
+        let status = daikin.get_status().await.unwrap();
+        assert_eq!(status.failed_paths, vec!["/dsiot/edge/adr_0200.dgc_status"]);
+        assert_eq!(status.mode.get_enum(), Some(Mode::Cooling));
+        assert_eq!(status.sensors.outdoor_temperature.get_f32(), None);
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let sim = Simulator::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve_http(sim.clone(), listener));
+
+        let daikin = Daikin::with_socket_addr(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            ReqwestClient::try_new().unwrap(),
+        );
+        daikin.get_info().await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let events = daikin.watch(Duration::from_millis(20));
+            futures::pin_mut!(events);
+            while let Some(event) = events.next().await {
+                let _ = tx.send(event);
+            }
+        });
+
+        // Let the first poll set the baseline, then change the unit behind the client's back
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        sim.set_value(ADR_0100, &["e_1002", "e_A002", "p_01"], json!("01"));
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await;
+        assert_eq!(
+            event.unwrap(),
+            Some(StatusEvent::PowerChanged(PowerState::On))
+        );
+
+        // Nothing listens on port 9 of localhost
+        let daikin = Daikin::with_socket_addr(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 9),
+            ReqwestClient::try_new().unwrap(),
+        );
+        let events = daikin.watch(Duration::from_millis(20));
+        futures::pin_mut!(events);
+        assert_eq!(
+            events.next().await,
+            Some(StatusEvent::ReachabilityChanged(false))
+        );
+    }
+
+    #[tokio::test]
+    async fn cache() {
+        let sim = Simulator::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve_http(sim.clone(), listener));
+        let power = |on: bool| {
+            let pv = if on { "01" } else { "00" };
+            sim.set_value(ADR_0100, &["e_1002", "e_A002", "p_01"], json!(pv));
+        };
+        let builder =
+            || Daikin::builder(Ipv4Addr::LOCALHOST, ReqwestClient::try_new().unwrap()).port(port);
+
+        // Default TTL serves the cached status until invalidated
+        let daikin = builder().build();
+        daikin.get_status().await.unwrap();
+        power(true);
+        let status = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::Off));
+        let status = daikin.get_status_fresh().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::On));
+        power(false);
+        daikin.invalidate().await;
+        let status = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::Off));
+
+        // Zero TTL always asks the device
+        let daikin = builder().cache_ttl(Duration::ZERO).build();
+        daikin.get_status().await.unwrap();
+        power(true);
+        let status = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::On));
+
+        // Stale-while-revalidate answers from the cache and refreshes it
+        let daikin = builder()
+            .cache_ttl(Duration::ZERO)
+            .stale_while_revalidate(true)
+            .build();
+        daikin.get_status().await.unwrap();
+        power(false);
+        let status = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::On));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = daikin.get_status().await.unwrap();
+        assert_eq!(PowerState::from_status(&status), Some(PowerState::Off));
+    }
+}
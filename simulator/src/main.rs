@@ -0,0 +1,53 @@
+use clap::Parser;
+use daikin_simulator::{Simulator, serve_discovery, serve_http};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::net::{TcpListener, UdpSocket};
+
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Cli {
+    /// Address to serve /dsiot/multireq on
+    #[arg(long, default_value = "0.0.0.0:80")]
+    http: SocketAddr,
+
+    /// Address to answer discovery broadcasts on
+    #[arg(long, default_value = "0.0.0.0:30050")]
+    discovery: SocketAddr,
+
+    /// Response fixtures to seed the device state with (defaults to the dsiot fixtures)
+    #[arg(value_name = "FIXTURE")]
+    fixtures: Vec<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("daikin_simulator=debug"),
+    )
+    .init();
+
+    let cli = Cli::parse();
+
+    let sim = if cli.fixtures.is_empty() {
+        Simulator::default()
+    } else {
+        let fixtures = cli
+            .fixtures
+            .iter()
+            .map(std::fs::read_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let fixtures: Vec<&str> = fixtures.iter().map(String::as_str).collect();
+        Simulator::from_fixtures(&fixtures)?
+    };
+
+    let listener = TcpListener::bind(cli.http).await?;
+    let socket = UdpSocket::bind(cli.discovery).await?;
+    socket.set_broadcast(true)?;
+
+    tokio::try_join!(
+        serve_http(sim.clone(), listener),
+        serve_discovery(sim, socket)
+    )?;
+    Ok(())
+}